@@ -0,0 +1,18 @@
+#![allow(clippy::needless_return)]
+
+use vergen_gitcl::{Build, Emitter, Gitcl, Rustc};
+
+// Exposes VERGEN_BUILD_TIMESTAMP, VERGEN_GIT_SHA, VERGEN_GIT_DIRTY and VERGEN_RUSTC_SEMVER
+// to the crate for `GET /version`. Without git (e.g. a source tarball) the git values
+// fall back to a placeholder instead of failing the build.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let build = Build::builder().build_timestamp(true).build();
+    let git = Gitcl::builder().sha(false).dirty(false).build();
+    let rustc = Rustc::builder().semver(true).build();
+    Emitter::default()
+        .add_instructions(&build)?
+        .add_instructions(&git)?
+        .add_instructions(&rustc)?
+        .emit()?;
+    return Ok(());
+}
@@ -0,0 +1,81 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, extract::State};
+use axum_extra::extract::WithRejection;
+use chrono::{DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    auth::Admin, cron::CronSchedule, error::AppError, event::EventResponse, state::AppState,
+};
+
+/// A message broadcast to every open stream as `event: announcement` whenever its
+/// schedule fires, e.g. planned downtime every Sunday.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Announcement {
+    pub name: String,
+    /// Five-field cron expression in UTC.
+    pub cron: CronSchedule,
+    pub message: String,
+}
+
+/// Body of `PUT /admin/announcements`; replaces the whole list.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AnnouncementsUpdate {
+    announcements: Vec<Announcement>,
+}
+
+/// Wakes at the start of every minute and broadcasts the announcements due in it.
+pub async fn run(app_state: Arc<AppState>) {
+    loop {
+        let now = Utc::now();
+        let next = now.duration_trunc(TimeDelta::minutes(1)).unwrap() + TimeDelta::minutes(1);
+        let wait = (next - now).to_std().unwrap_or_default();
+        tokio::time::sleep(wait + Duration::from_millis(10)).await;
+
+        let now = Utc::now();
+        let due: Vec<Announcement> = app_state
+            .announcements
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|announcement| announcement.cron.matches(now))
+            .cloned()
+            .collect();
+        for announcement in due {
+            let reached = app_state.hub.announce(json!({
+                "name": announcement.name,
+                "message": announcement.message,
+                "at": now.duration_trunc(TimeDelta::minutes(1)).unwrap(),
+            }));
+            tracing::info!("Announced {} to {} subscribers", announcement.name, reached);
+        }
+    }
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<Vec<Announcement>>>, AppError> {
+    admin.require_operator()?;
+    let announcements = state.announcements.read().unwrap().clone();
+    return Ok(Json(EventResponse::ok(announcements)));
+}
+
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<AnnouncementsUpdate>, AppError>,
+) -> Result<Json<EventResponse<Vec<Announcement>>>, AppError> {
+    admin.require_operator()?;
+    *state.announcements.write().unwrap() = payload.announcements.clone();
+    tracing::info!(
+        "{} set {} recurring announcements",
+        admin.subject,
+        payload.announcements.len()
+    );
+    return Ok(Json(EventResponse::ok(payload.announcements)));
+}
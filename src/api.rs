@@ -0,0 +1,177 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    error_handling::HandleErrorLayer,
+    extract::{DefaultBodyLimit, Request},
+    http::{HeaderValue, header},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, post, put},
+};
+use tower::ServiceBuilder;
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::{
+    announcement, api_key, application, appointment, audit, breaker, checklist, client_ip,
+    config::Config, discovery, document, drain, error, event, fanout, flags, history, import,
+    login, note, oidc, outbox, quota, reload, retention, retraction, room, schedule, share,
+    signature, state::AppState, stats, tasks, telemetry, version, webhook,
+};
+
+/// When the unversioned paths were deprecated, as an RFC 9745 `Deprecation` date.
+const UNVERSIONED_DEPRECATED_AT: &str = "@1792022400";
+
+/// Every JSON and SSE endpoint of the first API version, relative to its `/api/v1` prefix.
+/// A version with a changed event schema gets its own router, nested next to this one.
+pub fn v1(config: &Config, app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    // JSON endpoints get a request timeout; SSE routes stay outside of it
+    let json_routes = Router::new()
+        .route(
+            "/events/send",
+            post(event::send)
+                .layer((
+                    middleware::from_fn_with_state(app_state.clone(), audit::record_send),
+                    middleware::from_fn(error::payload_too_large_envelope),
+                    middleware::from_fn_with_state(app_state.clone(), signature::verify),
+                    DefaultBodyLimit::disable(),
+                    RequestBodyLimitLayer::new(config.limits.send_body_bytes),
+                ))
+                .options(discovery::send_options),
+        )
+        .route("/login", post(login::login).get(login::current))
+        .route("/login/oidc", get(oidc::start))
+        .route("/login/oidc/callback", get(oidc::callback))
+        .route("/logout", post(login::logout))
+        .route("/events/schedule", post(schedule::create))
+        .route("/events/scheduled", get(schedule::list))
+        .route("/events/scheduled/{id}", delete(schedule::cancel))
+        .route("/events/{id}/retract", post(retraction::retract))
+        .route("/admin/api-keys", post(api_key::create).get(api_key::list))
+        .route("/admin/api-keys/{id}", delete(api_key::revoke))
+        .route("/admin/audit", get(audit::list))
+        .route("/admin/breakers", get(breaker::list))
+        .route("/admin/config/reload", post(reload::trigger))
+        .route("/admin/drain", get(drain::get).post(drain::start))
+        .route("/admin/prune", post(retention::trigger))
+        .route(
+            "/admin/fanout",
+            get(fanout::utilization).put(fanout::resize),
+        )
+        .route("/admin/flags", get(flags::get).put(flags::update))
+        .route(
+            "/admin/log-level",
+            get(telemetry::get_log_level).put(telemetry::set_log_level),
+        )
+        .route(
+            "/admin/announcements",
+            get(announcement::get).put(announcement::update),
+        )
+        .route("/admin/outbox", get(outbox::list))
+        .route("/admin/quotas", get(quota::list))
+        .route("/admin/tasks", get(tasks::list))
+        .route("/admin/webhooks", post(webhook::create).get(webhook::list))
+        .route("/admin/webhooks/{id}", delete(webhook::delete))
+        .route("/admin/webhooks/{id}/replay", post(webhook::replay))
+        .route("/admin/webhooks/{id}/deliveries", get(webhook::deliveries))
+        // the path receivers' developers were first given; registration lives under /admin
+        .route("/webhooks/{id}/deliveries", get(webhook::deliveries))
+        .route(
+            "/applications",
+            post(application::create).get(application::search),
+        )
+        .route(
+            "/applications/{id}",
+            get(application::get).patch(application::update),
+        )
+        .route("/applications/{id}/archive", post(application::archive))
+        .route("/applications/{id}/data", delete(application::purge))
+        .route(
+            "/applications/{id}/appointments",
+            post(appointment::create).get(appointment::list),
+        )
+        .route("/applications/{id}/appointments.ics", get(appointment::ics))
+        .route(
+            "/applications/{id}/documents",
+            post(document::upsert).get(document::list),
+        )
+        .route("/applications/{id}/history", get(history::list))
+        .route("/applications/{id}/history/export", get(history::export))
+        .route("/applications/{id}/history/series", get(history::series))
+        .route(
+            "/applications/{id}/notes",
+            post(note::create).get(note::list),
+        )
+        .route("/applications/{id}/pause", post(application::pause))
+        .route("/applications/{id}/resume", post(application::resume))
+        .route("/applications/{id}/qr.png", get(share::qr))
+        .route("/applications/{id}/share", post(share::create))
+        .route(
+            "/applications/{id}/stages/{stage}/complete",
+            post(checklist::complete),
+        )
+        .route("/applications/{id}/unarchive", post(application::unarchive))
+        .route("/rooms", get(room::list))
+        .route("/rooms/{name}", get(room::get).delete(room::delete))
+        .route(
+            "/rooms/{name}/applications/{id}",
+            put(room::add_application).delete(room::remove_application),
+        )
+        .route("/stats/applications", get(stats::applications))
+        .route("/stats/clients", get(stats::clients))
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(error::handle_middleware_error))
+                .timeout(Duration::from_millis(config.limits.request_timeout_ms)),
+        );
+
+    #[cfg(feature = "chaos")]
+    let json_routes = json_routes.merge(crate::chaos::routes(config.profile));
+
+    return Router::new()
+        .route(
+            "/events",
+            get(event::subscribe).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                client_ip::deny_subscribers,
+            )),
+        )
+        .route(
+            "/events/all",
+            get(event::subscribe_all).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                client_ip::deny_subscribers,
+            )),
+        )
+        .route(
+            "/rooms/{name}/events",
+            get(event::subscribe_room).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                client_ip::deny_subscribers,
+            )),
+        )
+        // imports stream uploads of any size, so they are kept clear of the request timeout
+        .route("/admin/import", post(import::import))
+        .route("/stats", get(stats::get))
+        .route("/version", get(version::get))
+        .merge(json_routes);
+}
+
+/// Layered onto the paths from before versioning, which stay as aliases of `/api/v1` until
+/// clients have moved. Points each response at its successor.
+pub async fn deprecated_alias(request: Request, next: Next) -> Response {
+    let successor = format!(
+        "</api/v1{}>; rel=\"successor-version\"",
+        request.uri().path()
+    );
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        "deprecation",
+        HeaderValue::from_static(UNVERSIONED_DEPRECATED_AT),
+    );
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append(header::LINK, link);
+    }
+    return response;
+}
@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    auth::{Admin, Principal, Role},
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+    store::Store,
+};
+
+/// Every minted key starts with this, which lets the authenticator tell keys from JWTs.
+pub const KEY_PREFIX: &str = "vt_";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Send,
+    Subscribe,
+    Admin,
+}
+
+impl Scope {
+    fn role(&self) -> Role {
+        match self {
+            Scope::Send => return Role::Publisher,
+            Scope::Subscribe => return Role::Viewer,
+            Scope::Admin => return Role::Admin,
+        }
+    }
+}
+
+/// Only the SHA-256 of the key is stored; the plaintext is shown once when minted.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    hash: String,
+    pub scopes: Vec<Scope>,
+    /// Keys of a tenant only ever act within it; keys without one are operator keys.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Accurate to [`LAST_USED_STEP`].
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+fn hash_key(key: &str) -> String {
+    return hex::encode(Sha256::digest(key.as_bytes()));
+}
+
+/// Granularity of `last_used_at`.
+const LAST_USED_STEP: chrono::Duration = chrono::Duration::minutes(1);
+
+/// Subjects of key holders are this followed by the key id.
+const SUBJECT_PREFIX: &str = "api-key:";
+
+fn principal(api_key: &ApiKey) -> Principal {
+    return Principal {
+        subject: format!("{}{}", SUBJECT_PREFIX, api_key.id),
+        roles: api_key.scopes.iter().map(Scope::role).collect(),
+        tenant: api_key.tenant.clone(),
+        anonymous: false,
+        application_id: None,
+        expires_at: None,
+    };
+}
+
+/// Resolves a presented key to a principal and records its use. Uses less than
+/// [`LAST_USED_STEP`] apart are recorded once, so busy keys neither queue behind the store
+/// lock nor rewrite the snapshot.
+pub fn authenticate(store: &Store, key: &str) -> Option<Principal> {
+    let hash = hash_key(key);
+    let now = Utc::now();
+    let (id, principal, stale) = store.read(|data| {
+        let api_key = data
+            .api_keys
+            .iter()
+            .find(|api_key| api_key.hash == hash && api_key.revoked_at.is_none())?;
+        let stale = api_key
+            .last_used_at
+            .is_none_or(|last_used_at| now - last_used_at >= LAST_USED_STEP);
+        return Some((api_key.id, principal(api_key), stale));
+    })?;
+    if stale {
+        store.write(|data| {
+            if let Some(api_key) = data.api_keys.iter_mut().find(|api_key| api_key.id == id) {
+                api_key.last_used_at = Some(now);
+            }
+        });
+    }
+    return Some(principal);
+}
+
+/// The id of the key `principal` authenticated with, if it did with one.
+pub fn id_of(principal: &Principal) -> Option<Uuid> {
+    return principal.subject.strip_prefix(SUBJECT_PREFIX)?.parse().ok();
+}
+
+/// The holder of the key with `id`, unless the key was revoked or deleted since.
+pub fn find(store: &Store, id: Uuid) -> Option<Principal> {
+    return store.read(|data| {
+        data.api_keys
+            .iter()
+            .find(|api_key| api_key.id == id && api_key.revoked_at.is_none())
+            .map(principal)
+    });
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateApiKeyRequest {
+    name: String,
+    scopes: Vec<Scope>,
+    /// Defaults to the minting admin's tenant; only operators may mint for other tenants.
+    tenant: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ApiKeyView {
+    id: Uuid,
+    name: String,
+    scopes: Vec<Scope>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+    /// Only present in the response that minted the key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+}
+
+impl From<&ApiKey> for ApiKeyView {
+    fn from(value: &ApiKey) -> Self {
+        return Self {
+            id: value.id,
+            name: value.name.clone(),
+            scopes: value.scopes.clone(),
+            tenant: value.tenant.clone(),
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+            revoked_at: value.revoked_at,
+            key: None,
+        };
+    }
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<CreateApiKeyRequest>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<ApiKeyView>>), AppError> {
+    if payload.scopes.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::EmptyScopesError,
+            "An API key needs at least one scope",
+        ));
+    }
+
+    let tenant = payload.tenant.or_else(|| admin.tenant.clone());
+    if !admin.can_access(tenant.as_deref()) {
+        return Err(AppError::new(
+            ErrorCode::TenantForbidden,
+            "API keys can only be minted for your own tenant",
+        ));
+    }
+
+    let mut secret = [0u8; 32];
+    rand::rng().fill_bytes(&mut secret);
+    let key = format!("{}{}", KEY_PREFIX, hex::encode(secret));
+
+    let api_key = ApiKey {
+        id: Uuid::new_v4(),
+        name: payload.name,
+        hash: hash_key(&key),
+        scopes: payload.scopes,
+        tenant,
+        created_at: Utc::now(),
+        last_used_at: None,
+        revoked_at: None,
+    };
+    tracing::info!("{} minted API key {}", admin.subject, api_key.id);
+
+    let mut view = ApiKeyView::from(&api_key);
+    view.key = Some(key);
+    state.store.write(|data| data.api_keys.push(api_key));
+
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(view))));
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Json<EventResponse<Vec<ApiKeyView>>> {
+    let keys = state.store.read(|data| {
+        data.api_keys
+            .iter()
+            .filter(|api_key| admin.can_access(api_key.tenant.as_deref()))
+            .map(ApiKeyView::from)
+            .collect()
+    });
+    return Json(EventResponse::ok(keys));
+}
+
+pub async fn revoke(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<ApiKeyView>>, AppError> {
+    let revoked = state.store.write(|data| {
+        // other tenants' keys are reported as missing rather than forbidden
+        let api_key = data
+            .api_keys
+            .iter_mut()
+            .find(|api_key| api_key.id == id && admin.can_access(api_key.tenant.as_deref()))?;
+        api_key.revoked_at.get_or_insert_with(Utc::now);
+        return Some(ApiKeyView::from(&*api_key));
+    });
+
+    match revoked {
+        Some(view) => {
+            tracing::info!("{} revoked API key {}", admin.subject, id);
+            return Ok(Json(EventResponse::ok(view)));
+        }
+        None => {
+            return Err(AppError::new(
+                ErrorCode::ApiKeyNotFound,
+                format!("API key {} does not exist", id),
+            ));
+        }
+    }
+}
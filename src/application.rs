@@ -0,0 +1,699 @@
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Instant};
+
+use axum::{
+    Json,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{HeaderValue, StatusCode, header, request::Parts},
+    response::Response,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::{
+    appointment::Appointment,
+    auth::{Admin, Principal, Publisher, Viewer},
+    checklist,
+    content::{self, CsvRecord, Negotiated},
+    document::Document,
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    fanout::Disconnect,
+    note::Note,
+    quota, room,
+    state::AppState,
+};
+
+/// A visa case being tracked. Holds the applicant's personal data, which is why
+/// [`purge`] exists.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Application {
+    pub id: Uuid,
+    /// The agency handling the case; `None` for applications created by operators.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub applicant_name: String,
+    pub applicant_email: Option<String>,
+    pub visa_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Incremented on every change to the record. Sent as the `ETag`; updates that send it
+    /// back in `If-Match` are refused if someone else changed the case in the meantime.
+    #[serde(default)]
+    pub version: u64,
+    /// Set while the case is archived: it refuses events and is hidden from listings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Set while broadcasting is paused: events are still recorded, but held back from
+    /// subscribers until the case is resumed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_at: Option<DateTime<Utc>>,
+    /// Checklist stages marked complete, in completion order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completed_stages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<Document>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub appointments: Vec<Appointment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Note>,
+}
+
+impl Application {
+    /// Records a change to the case, invalidating versions handed out before.
+    pub fn touch(&mut self) {
+        self.version += 1;
+    }
+
+    pub fn etag(&self) -> String {
+        return format!("\"{}\"", self.version);
+    }
+}
+
+/// The `If-Match` header of an update: the application version the caller last read.
+/// Updates without one keep last-write-wins.
+pub struct IfMatch(Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = parts
+            .headers
+            .get(header::IF_MATCH)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string());
+        return Ok(IfMatch(value));
+    }
+}
+
+impl IfMatch {
+    /// Refuses the update with 409 and the current version as `ETag` when the application
+    /// changed since the caller read it. Call it inside the store write that applies the
+    /// update, so no other change can slip in between.
+    pub fn check(&self, application: &Application) -> Result<(), AppError> {
+        let Some(expected) = &self.0 else {
+            return Ok(());
+        };
+        let current = application.etag();
+        let matches = expected.split(',').map(str::trim).any(|tag| {
+            return tag == "*" || tag.trim_start_matches("W/") == current;
+        });
+        if matches {
+            return Ok(());
+        }
+        return Err(AppError::new(
+            ErrorCode::VersionConflict,
+            format!(
+                "Application {} was changed concurrently and is now at version {}; reload it and retry",
+                application.id, application.version
+            ),
+        )
+        .etag(current));
+    }
+}
+
+impl CsvRecord for Application {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "tenant",
+        "applicant_name",
+        "applicant_email",
+        "visa_type",
+        "created_at",
+        "archived_at",
+        "completed_stages",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![
+            self.id.to_string(),
+            self.tenant.clone().unwrap_or_default(),
+            self.applicant_name.clone(),
+            self.applicant_email.clone().unwrap_or_default(),
+            self.visa_type.clone().unwrap_or_default(),
+            self.created_at.to_rfc3339(),
+            self.archived_at
+                .map(|archived_at| archived_at.to_rfc3339())
+                .unwrap_or_default(),
+            self.completed_stages.join(";"),
+        ];
+    }
+}
+
+/// Proof that an applicant's data was erased; contains no personal data itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErasureReceipt {
+    pub receipt_id: Uuid,
+    pub application_id: Uuid,
+    pub purged_at: DateTime<Utc>,
+    pub purged_by: String,
+    pub events_deleted: usize,
+    pub audit_entries_deleted: usize,
+}
+
+/// Where a case stands, derived from its progress: the checklist when one is configured,
+/// otherwise the latest progress event.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicationStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+impl ApplicationStatus {
+    pub fn from_percentage(percentage: f64) -> Self {
+        if percentage >= 100.0 {
+            return ApplicationStatus::Completed;
+        }
+        if percentage > 0.0 {
+            return ApplicationStatus::InProgress;
+        }
+        return ApplicationStatus::Pending;
+    }
+}
+
+pub fn not_found(id: Uuid) -> AppError {
+    return AppError::new(
+        ErrorCode::ApplicationNotFound,
+        format!("Application {} does not exist", id),
+    );
+}
+
+/// Checks that the application exists and belongs to the caller's tenant, and returns that
+/// tenant. Other tenants' applications are reported as missing so their ids can't be probed.
+pub fn authorize(
+    state: &AppState,
+    principal: &Principal,
+    id: Uuid,
+) -> Result<Option<String>, AppError> {
+    return state
+        .store
+        .read(|data| {
+            data.applications
+                .get(&id)
+                .map(|application| application.tenant.clone())
+        })
+        .filter(|tenant| principal.can_access(tenant.as_deref()))
+        .filter(|_| principal.application_id.is_none_or(|scoped| scoped == id))
+        .ok_or_else(|| not_found(id));
+}
+
+/// Like [`authorize`], but also refuses archived applications with 410 Gone. Use it
+/// wherever something would be recorded or broadcast for the case.
+pub fn authorize_active(
+    state: &AppState,
+    principal: &Principal,
+    id: Uuid,
+) -> Result<Option<String>, AppError> {
+    let tenant = authorize(state, principal, id)?;
+    let archived = state.store.read(|data| {
+        data.applications
+            .get(&id)
+            .is_some_and(|application| application.archived_at.is_some())
+    });
+    if archived {
+        return Err(AppError::new(
+            ErrorCode::ApplicationArchived,
+            format!("Application {} is archived; unarchive it first", id),
+        ));
+    }
+    return Ok(tenant);
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateApplicationRequest {
+    applicant_name: String,
+    applicant_email: Option<String>,
+    visa_type: Option<String>,
+    /// Defaults to the caller's tenant; only operators may file for another tenant.
+    tenant: Option<String>,
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Json(payload), _): WithRejection<Json<CreateApplicationRequest>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<Application>>), AppError> {
+    let tenant = payload.tenant.or_else(|| publisher.tenant.clone());
+    if !publisher.can_access(tenant.as_deref()) {
+        return Err(AppError::new(
+            ErrorCode::TenantForbidden,
+            "Applications can only be created for your own tenant",
+        ));
+    }
+
+    let application = Application {
+        id: Uuid::new_v4(),
+        tenant,
+        applicant_name: payload.applicant_name,
+        applicant_email: payload.applicant_email,
+        visa_type: payload.visa_type,
+        created_at: Utc::now(),
+        version: 1,
+        archived_at: None,
+        paused_at: None,
+        completed_stages: Vec::new(),
+        documents: Vec::new(),
+        appointments: Vec::new(),
+        notes: Vec::new(),
+    };
+    let quotas = state.config().quotas;
+    state.store.write(|data| -> Result<(), AppError> {
+        quota::check_active_applications(&quotas, data, application.tenant.as_deref())?;
+        data.applications
+            .insert(application.id, application.clone());
+        return Ok(());
+    })?;
+
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(application))));
+}
+
+/// Body of `PATCH /applications/{id}`; omitted fields keep their value.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateApplicationRequest {
+    applicant_name: Option<String>,
+    applicant_email: Option<String>,
+    visa_type: Option<String>,
+}
+
+/// Corrects the applicant details. Send the `ETag` from the last read as `If-Match` so
+/// concurrent edits by two officers are refused instead of overwriting each other.
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    if_match: IfMatch,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Json(payload), _): WithRejection<Json<UpdateApplicationRequest>, AppError>,
+) -> Result<Json<EventResponse<Application>>, AppError> {
+    authorize_active(&state, &publisher, id)?;
+    let application = state.store.write(|data| -> Result<Application, AppError> {
+        let application = data
+            .applications
+            .get_mut(&id)
+            .ok_or_else(|| not_found(id))?;
+        if_match.check(application)?;
+        if let Some(applicant_name) = payload.applicant_name {
+            application.applicant_name = applicant_name;
+        }
+        if let Some(applicant_email) = payload.applicant_email {
+            application.applicant_email = Some(applicant_email);
+        }
+        if let Some(visa_type) = payload.visa_type {
+            application.visa_type = Some(visa_type);
+        }
+        application.touch();
+        return Ok(application.clone());
+    })?;
+    tracing::info!(
+        "{} updated application {} to version {}",
+        publisher.subject,
+        id,
+        application.version
+    );
+    return Ok(Json(EventResponse::ok(application)));
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(format): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Response, AppError> {
+    authorize(&state, &viewer, id)?;
+    let mut application = state
+        .store
+        .read(|data| data.applications.get(&id).cloned())
+        .ok_or_else(|| not_found(id))?;
+    application
+        .notes
+        .retain(|note| viewer.can_see(note.visibility));
+    let etag = HeaderValue::from_str(&application.etag()).unwrap();
+    let mut response = content::respond_one(format, application);
+    response.headers_mut().insert(header::ETAG, etag);
+    return Ok(response);
+}
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Deserialize, Debug)]
+pub struct SearchQuery {
+    status: Option<ApplicationStatus>,
+    /// Exact match, ignoring case.
+    visa_type: Option<String>,
+    /// Case-insensitive substring of the applicant's name or email.
+    q: Option<String>,
+    /// `created_at` (the default), `applicant_name` or `visa_type`; prefix with `-` for
+    /// descending order.
+    sort: Option<String>,
+    /// `true` lists archived applications instead of active ones.
+    #[serde(default)]
+    archived: bool,
+    /// 1-based.
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    CreatedAt,
+    ApplicantName,
+    VisaType,
+}
+
+fn sort_order(sort: Option<&str>) -> Result<(SortKey, bool), AppError> {
+    let sort = sort.unwrap_or("created_at");
+    let (field, descending) = match sort.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (sort, false),
+    };
+    let key = match field {
+        "created_at" => SortKey::CreatedAt,
+        "applicant_name" => SortKey::ApplicantName,
+        "visa_type" => SortKey::VisaType,
+        _ => {
+            return Err(AppError::new(
+                ErrorCode::InvalidQueryParameter,
+                format!(
+                    "Cannot sort by {}; use created_at, applicant_name or visa_type",
+                    field
+                ),
+            ));
+        }
+    };
+    return Ok((key, descending));
+}
+
+/// `GET /applications`: finds cases of the caller's tenant (every tenant for operators).
+/// The page is returned in the negotiated format with the number of matches before
+/// paging in `X-Total-Count`.
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    Negotiated(format): Negotiated,
+    WithRejection(Query(query), _): WithRejection<Query<SearchQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let (sort_key, descending) = sort_order(query.sort.as_deref())?;
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page == 0 || per_page == 0 || per_page > MAX_PAGE_SIZE {
+        return Err(AppError::new(
+            ErrorCode::InvalidQueryParameter,
+            format!(
+                "page starts at 1 and per_page must be between 1 and {}",
+                MAX_PAGE_SIZE
+            ),
+        ));
+    }
+    let needle = query.q.as_deref().map(str::to_lowercase);
+    let config = state.config();
+
+    let mut matches: Vec<Application> = state.store.read(|data| {
+        // latest progress event per application, only needed to filter by status
+        let mut latest: HashMap<Uuid, f64> = HashMap::new();
+        if query.status.is_some() && config.checklist.stages.is_empty() {
+            for stored in data
+                .events
+                .iter()
+                .filter(|stored| stored.retracted.is_none())
+            {
+                if let Some(id) = stored.event.application_id {
+                    latest.insert(id, stored.event.percentage);
+                }
+            }
+        }
+        return data
+            .applications
+            .values()
+            .filter(|application| admin.can_access(application.tenant.as_deref()))
+            .filter(|application| application.archived_at.is_some() == query.archived)
+            .filter(|application| {
+                query.visa_type.as_deref().is_none_or(|visa_type| {
+                    application
+                        .visa_type
+                        .as_deref()
+                        .is_some_and(|own| own.eq_ignore_ascii_case(visa_type))
+                })
+            })
+            .filter(|application| {
+                needle.as_deref().is_none_or(|needle| {
+                    application.applicant_name.to_lowercase().contains(needle)
+                        || application
+                            .applicant_email
+                            .as_deref()
+                            .is_some_and(|email| email.to_lowercase().contains(needle))
+                })
+            })
+            .filter(|application| {
+                query.status.is_none_or(|status| {
+                    let percentage = if config.checklist.stages.is_empty() {
+                        latest.get(&application.id).copied().unwrap_or(0.0)
+                    } else {
+                        checklist::percentage(&config.checklist, &application.completed_stages)
+                    };
+                    ApplicationStatus::from_percentage(percentage) == status
+                })
+            })
+            .cloned()
+            .collect();
+    });
+
+    matches.sort_by(|a, b| {
+        let ordering = match sort_key {
+            SortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+            SortKey::ApplicantName => a
+                .applicant_name
+                .to_lowercase()
+                .cmp(&b.applicant_name.to_lowercase()),
+            SortKey::VisaType => a.visa_type.cmp(&b.visa_type),
+        };
+        // ties keep a stable order across pages
+        let ordering = ordering.then(a.id.cmp(&b.id));
+        if descending {
+            return ordering.reverse();
+        }
+        return ordering;
+    });
+
+    let total = matches.len();
+    let page_items: Vec<Application> = matches
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect();
+    let mut response = content::respond(format, page_items);
+    response
+        .headers_mut()
+        .insert("x-total-count", HeaderValue::from(total));
+    return Ok(response);
+}
+
+/// Archives a case: it stops accepting events, drops out of default listings, and its
+/// channel is torn down after subscribers get a final `event: archived`.
+pub async fn archive(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    if_match: IfMatch,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<Application>>, AppError> {
+    let tenant = authorize(&state, &admin, id)?;
+    let (application, newly_archived) =
+        state
+            .store
+            .write(|data| -> Result<(Application, bool), AppError> {
+                let application = data
+                    .applications
+                    .get_mut(&id)
+                    .ok_or_else(|| not_found(id))?;
+                if_match.check(application)?;
+                let newly_archived = application.archived_at.is_none();
+                if newly_archived {
+                    application.archived_at = Some(Utc::now());
+                    application.touch();
+                }
+                return Ok((application.clone(), newly_archived));
+            })?;
+
+    if newly_archived {
+        tracing::info!("{} archived application {}", admin.subject, id);
+        // subscribers get what was held back before the channel closes
+        flush_held(&state, id);
+        let _ = state.broadcast(
+            Some(id),
+            tenant,
+            "archived",
+            json!({ "application_id": id, "archived_at": application.archived_at }),
+        );
+        state.hub.close_channel(id, Disconnect::Archived);
+        state.stats.forget_application(id);
+    }
+    return Ok(Json(EventResponse::ok(application)));
+}
+
+pub async fn unarchive(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    if_match: IfMatch,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<Application>>, AppError> {
+    authorize(&state, &admin, id)?;
+    let quotas = state.config().quotas;
+    let application = state.store.write(|data| -> Result<Application, AppError> {
+        let application = data.applications.get(&id).ok_or_else(|| not_found(id))?;
+        if_match.check(application)?;
+        if application.archived_at.is_some() {
+            let tenant = application.tenant.clone();
+            quota::check_active_applications(&quotas, data, tenant.as_deref())?;
+        }
+        let application = data.applications.get_mut(&id).unwrap();
+        if application.archived_at.take().is_some() {
+            application.touch();
+        }
+        return Ok(application.clone());
+    })?;
+    tracing::info!("{} unarchived application {}", admin.subject, id);
+    return Ok(Json(EventResponse::ok(application)));
+}
+
+/// Stops broadcasting the case's events, e.g. while its data is being corrected. Events
+/// are still accepted and recorded; subscribers get an `event: paused` and then nothing
+/// until [`resume`].
+pub async fn pause(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<Application>>, AppError> {
+    let tenant = authorize_active(&state, &admin, id)?;
+    if state.is_paused(id) {
+        let application = state.store.read(|data| data.applications.get(&id).cloned());
+        return Ok(Json(EventResponse::ok(
+            application.ok_or_else(|| not_found(id))?,
+        )));
+    }
+    let _ = state.broadcast(Some(id), tenant, "paused", json!({ "application_id": id }));
+    let application = state
+        .store
+        .write(|data| {
+            let application = data.applications.get_mut(&id)?;
+            application.paused_at = Some(Utc::now());
+            application.touch();
+            return Some(application.clone());
+        })
+        .ok_or_else(|| not_found(id))?;
+    tracing::info!("{} paused broadcasts of application {}", admin.subject, id);
+    return Ok(Json(EventResponse::ok(application)));
+}
+
+/// Resumes broadcasting: everything held back since [`pause`] is delivered in order,
+/// followed by an `event: resumed`.
+pub async fn resume(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<Application>>, AppError> {
+    let tenant = authorize(&state, &admin, id)?;
+    if !state.is_paused(id) {
+        let application = state.store.read(|data| data.applications.get(&id).cloned());
+        return Ok(Json(EventResponse::ok(
+            application.ok_or_else(|| not_found(id))?,
+        )));
+    }
+    let flushed = flush_held(&state, id);
+    tracing::info!(
+        "{} resumed broadcasts of application {}, flushing {} held events",
+        admin.subject,
+        id,
+        flushed
+    );
+    let _ = state.broadcast(
+        Some(id),
+        tenant,
+        "resumed",
+        json!({ "application_id": id, "flushed": flushed }),
+    );
+    let application = state.store.read(|data| data.applications.get(&id).cloned());
+    return Ok(Json(EventResponse::ok(
+        application.ok_or_else(|| not_found(id))?,
+    )));
+}
+
+/// Unpauses the case and publishes its held events in order, returning how many there were.
+fn flush_held(state: &AppState, id: Uuid) -> usize {
+    // holding the lock keeps new events from overtaking the held ones
+    let mut held = state.held.lock().unwrap();
+    state.store.write(|data| {
+        if let Some(application) = data.applications.get_mut(&id)
+            && application.paused_at.take().is_some()
+        {
+            application.touch();
+        }
+    });
+    let events = held.remove(&id).unwrap_or_default();
+    let flushed = events.len();
+    for mut event in events {
+        event.ingested_at = Instant::now();
+        state.stats.record_broadcast();
+        state.hub.publish(event);
+    }
+    return flushed;
+}
+
+/// GDPR erasure: removes the applicant record together with every stored event and audit
+/// entry that references it, then tells live subscribers the case is gone.
+pub async fn purge(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<ErasureReceipt>>, AppError> {
+    let tenant = authorize(&state, &admin, id)?;
+    let id_value = Value::String(id.to_string());
+    let receipt = state.store.write(|data| {
+        data.applications.remove(&id)?;
+
+        let events_before = data.events.len();
+        data.events
+            .retain(|stored| stored.event.application_id != Some(id));
+        data.progress.remove(&Some(id));
+        // queued notifications carry the event data too
+        data.outbox
+            .retain(|entry| entry.payload.get("application_id") != Some(&id_value));
+        data.scheduled
+            .retain(|scheduled| scheduled.event.application_id != Some(id));
+        data.webhook_deliveries
+            .retain(|attempt| attempt.application_id != Some(id));
+        data.share_links.retain(|link| link.application_id != id);
+        let audit_before = data.audit.len();
+        // enveloped bodies carry the event under `data`
+        data.audit.retain(|entry| {
+            entry.payload.get("application_id") != Some(&id_value)
+                && entry.payload.pointer("/data/application_id") != Some(&id_value)
+        });
+
+        let receipt = ErasureReceipt {
+            receipt_id: Uuid::new_v4(),
+            application_id: id,
+            purged_at: Utc::now(),
+            purged_by: admin.subject.clone(),
+            events_deleted: events_before - data.events.len(),
+            audit_entries_deleted: audit_before - data.audit.len(),
+        };
+        data.erasures.push(receipt.clone());
+        return Some(receipt);
+    });
+    let receipt = receipt.ok_or_else(|| not_found(id))?;
+    room::forget(&state, id);
+    state.stats.forget_application(id);
+
+    // the held broadcasts carry the erased data too
+    state.held.lock().unwrap().remove(&id);
+    tracing::info!("{} purged application {}", admin.subject, id);
+    // no receivers is fine, the data is gone either way
+    let _ = state.broadcast(Some(id), tenant, "purged", json!({ "application_id": id }));
+    // kept until now so the notice continues the sequence subscribers know
+    state.store.write(|data| data.sequences.remove(&id));
+
+    return Ok(Json(EventResponse::ok(receipt)));
+}
@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    application::{authorize, authorize_active, not_found},
+    auth::{Publisher, Viewer},
+    content::{self, CsvRecord, Negotiated},
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+};
+
+const ICS_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AppointmentKind {
+    Biometrics,
+    Interview,
+}
+
+impl AppointmentKind {
+    fn title(&self) -> &'static str {
+        match self {
+            AppointmentKind::Biometrics => return "Visa biometrics appointment",
+            AppointmentKind::Interview => return "Visa interview",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Appointment {
+    pub id: Uuid,
+    pub kind: AppointmentKind,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub location: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CsvRecord for Appointment {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "kind",
+        "starts_at",
+        "ends_at",
+        "location",
+        "created_at",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![
+            self.id.to_string(),
+            content::variant_name(&self.kind),
+            self.starts_at.to_rfc3339(),
+            self.ends_at.to_rfc3339(),
+            self.location.clone(),
+            self.created_at.to_rfc3339(),
+        ];
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateAppointmentRequest {
+    kind: AppointmentKind,
+    starts_at: DateTime<Utc>,
+    /// Defaults to an hour after `starts_at`.
+    ends_at: Option<DateTime<Utc>>,
+    location: String,
+}
+
+fn appointments_of(state: &AppState, id: Uuid) -> Result<Vec<Appointment>, AppError> {
+    return state
+        .store
+        .read(|data| {
+            data.applications
+                .get(&id)
+                .map(|application| application.appointments.clone())
+        })
+        .ok_or_else(|| not_found(id));
+}
+
+/// Schedules an appointment and announces it with `event: appointment`.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Json(payload), _): WithRejection<Json<CreateAppointmentRequest>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<Appointment>>), AppError> {
+    let tenant = authorize_active(&state, &publisher, id)?;
+    let ends_at = payload
+        .ends_at
+        .unwrap_or(payload.starts_at + chrono::Duration::hours(1));
+    if ends_at <= payload.starts_at {
+        return Err(AppError::new(
+            ErrorCode::InvalidAppointment,
+            "ends_at must be after starts_at",
+        ));
+    }
+
+    let appointment = Appointment {
+        id: Uuid::new_v4(),
+        kind: payload.kind,
+        starts_at: payload.starts_at,
+        ends_at,
+        location: payload.location,
+        created_at: Utc::now(),
+    };
+    state
+        .store
+        .write(|data| {
+            let application = data.applications.get_mut(&id)?;
+            application.appointments.push(appointment.clone());
+            application.touch();
+            return Some(());
+        })
+        .ok_or_else(|| not_found(id))?;
+
+    let _ = state.broadcast(
+        Some(id),
+        tenant,
+        "appointment",
+        json!({ "application_id": id, "appointment": appointment }),
+    );
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(appointment))));
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(format): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Response, AppError> {
+    authorize(&state, &viewer, id)?;
+    return Ok(content::respond(format, appointments_of(&state, id)?));
+}
+
+/// Escapes TEXT values per RFC 5545 section 3.3.11.
+fn ics_text(value: &str) -> String {
+    return value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n");
+}
+
+fn render_ics(appointments: &[Appointment]) -> String {
+    let stamp = Utc::now().format(ICS_TIME_FORMAT);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//axum-visa-tracker-sse//appointments//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for appointment in appointments {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@axum-visa-tracker-sse", appointment.id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!(
+            "DTSTART:{}",
+            appointment.starts_at.format(ICS_TIME_FORMAT)
+        ));
+        lines.push(format!(
+            "DTEND:{}",
+            appointment.ends_at.format(ICS_TIME_FORMAT)
+        ));
+        lines.push(format!("SUMMARY:{}", ics_text(appointment.kind.title())));
+        lines.push(format!("LOCATION:{}", ics_text(&appointment.location)));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = lines.join("\r\n");
+    calendar.push_str("\r\n");
+    return calendar;
+}
+
+/// Upcoming appointments as an iCalendar file the applicant can import into a calendar.
+pub async fn ics(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Response, AppError> {
+    authorize(&state, &viewer, id)?;
+    let now = Utc::now();
+    let mut upcoming: Vec<Appointment> = appointments_of(&state, id)?
+        .into_iter()
+        .filter(|appointment| appointment.ends_at >= now)
+        .collect();
+    upcoming.sort_by_key(|appointment| appointment.starts_at);
+
+    let disposition = format!("attachment; filename=\"application-{}.ics\"", id);
+    return Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/calendar; charset=utf-8".to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        render_ics(&upcoming),
+    )
+        .into_response());
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Query, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    auth::{Admin, Principal},
+    client_ip::ClientIp,
+    content::{self, CsvRecord, Negotiated},
+    error::AppError,
+    state::AppState,
+};
+
+/// One `POST /events/send` attempt, whatever its outcome. Entries are only ever appended.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub source_ip: Option<String>,
+    /// The submitted body, or the raw text when it wasn't valid JSON.
+    pub payload: Value,
+    pub status: u16,
+    pub error_code: Option<String>,
+}
+
+impl CsvRecord for AuditEntry {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "at",
+        "subject",
+        "tenant",
+        "source_ip",
+        "status",
+        "error_code",
+        "payload",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![
+            self.id.to_string(),
+            self.at.to_rfc3339(),
+            self.subject.clone().unwrap_or_default(),
+            self.tenant.clone().unwrap_or_default(),
+            self.source_ip.clone().unwrap_or_default(),
+            self.status.to_string(),
+            self.error_code.clone().unwrap_or_default(),
+            self.payload.to_string(),
+        ];
+    }
+}
+
+/// Route middleware for `/events/send`. It buffers the body itself, so it enforces the
+/// same size limit as the `RequestBodyLimitLayer` behind it.
+pub async fn record_send(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let principal = request.extensions().get::<Principal>();
+    let subject = principal.map(|principal| principal.subject.clone());
+    let tenant = principal.and_then(|principal| principal.tenant.clone());
+    let source_ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string());
+
+    let (parts, body) = request.into_parts();
+    let (payload, response) = match to_bytes(body, state.config().limits.send_body_bytes).await {
+        Ok(bytes) => {
+            let payload = serde_json::from_slice(&bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+            let response = next
+                .run(Request::from_parts(parts, Body::from(bytes)))
+                .await;
+            (payload, response)
+        }
+        Err(_) => (Value::Null, AppError::payload_too_large().into_response()),
+    };
+
+    // handler responses are small JSON envelopes, so peeking at the error code is cheap
+    let (response_parts, response_body) = response.into_parts();
+    let response_bytes = to_bytes(response_body, usize::MAX)
+        .await
+        .unwrap_or_default();
+    let error_code = serde_json::from_slice::<Value>(&response_bytes)
+        .ok()
+        .and_then(|body| body.pointer("/error/code")?.as_str().map(str::to_string));
+
+    state.store.write(|data| {
+        let id = data.next_audit_id();
+        data.audit.push(AuditEntry {
+            id,
+            at: Utc::now(),
+            subject,
+            tenant,
+            source_ip,
+            payload,
+            status: response_parts.status.as_u16(),
+            error_code,
+        });
+    });
+
+    return Response::from_parts(response_parts, Body::from(response_bytes));
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuditQuery {
+    since: Option<DateTime<Utc>>,
+    /// Only requests made by this subject.
+    actor: Option<String>,
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    Negotiated(format): Negotiated,
+    WithRejection(Query(query), _): WithRejection<Query<AuditQuery>, AppError>,
+) -> Response {
+    let entries = state.store.read(|data| {
+        data.audit
+            .iter()
+            .filter(|entry| admin.can_access(entry.tenant.as_deref()))
+            .filter(|entry| query.since.is_none_or(|since| entry.at >= since))
+            .filter(|entry| {
+                query
+                    .actor
+                    .as_deref()
+                    .is_none_or(|actor| entry.subject.as_deref() == Some(actor))
+            })
+            .cloned()
+            .collect()
+    });
+    return content::respond(format, entries);
+}
@@ -0,0 +1,369 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    api_key,
+    config::{AuthConfig, AuthMode, ClientCertConfig, TokenConfig},
+    error::{AppError, ErrorCode},
+    event::Visibility,
+    jwks::JwtVerifier,
+    login,
+    oidc::OidcClient,
+    outbound::HttpClient,
+    share,
+    state::AppState,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Publisher,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => return "viewer",
+            Role::Publisher => return "publisher",
+            Role::Admin => return "admin",
+        }
+    }
+}
+
+/// Subject common name of the certificate a caller presented over mutual TLS, attached to
+/// each request of the connection once the handshake verified it.
+#[derive(Debug, Clone)]
+pub struct ClientCert(pub String);
+
+/// The authenticated caller, stored in request extensions by [`authenticate`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Principal {
+    pub subject: String,
+    pub roles: Vec<Role>,
+    /// The agency the caller acts for. `None` marks an operator, who sees every tenant.
+    pub tenant: Option<String>,
+    /// Set for callers without credentials, who get the configured anonymous role.
+    pub anonymous: bool,
+    /// Set for share links: the only application the caller may read.
+    pub application_id: Option<Uuid>,
+    /// When the presented credentials stop being valid; streams are closed then. Login
+    /// sessions keep it next to the principal and end with it.
+    #[serde(skip)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Principal {
+    fn has_any(&self, roles: &[Role]) -> bool {
+        return self.roles.iter().any(|role| roles.contains(role));
+    }
+
+    /// Whether a resource owned by `tenant` is visible to this caller.
+    pub fn can_access(&self, tenant: Option<&str>) -> bool {
+        // a share link of an operator-owned case must not pass for an operator
+        if self.application_id.is_some() {
+            return self.tenant.as_deref() == tenant;
+        }
+        return self.tenant.is_none() || self.tenant.as_deref() == tenant;
+    }
+
+    /// Whether events of this visibility are for the caller. Publishers and admins are
+    /// staff; identified viewers are applicants; anonymous viewers only see public events.
+    pub fn can_see(&self, visibility: Visibility) -> bool {
+        match visibility {
+            Visibility::Public => return true,
+            Visibility::Applicant => {
+                return !self.anonymous || self.has_any(&[Role::Publisher, Role::Admin]);
+            }
+            Visibility::Staff => return self.has_any(&[Role::Publisher, Role::Admin]),
+        }
+    }
+
+    /// Server-wide endpoints would expose every tenant's data, so they are kept to operators.
+    pub fn require_operator(&self) -> Result<(), AppError> {
+        if let Some(application_id) = self.application_id {
+            return Err(AppError::new(
+                ErrorCode::Forbidden,
+                format!(
+                    "This link only grants access to application {}",
+                    application_id
+                ),
+            ));
+        }
+        if let Some(tenant) = &self.tenant {
+            return Err(AppError::new(
+                ErrorCode::TenantForbidden,
+                format!(
+                    "This endpoint spans all tenants and is not available to tenant {}",
+                    tenant
+                ),
+            ));
+        }
+        return Ok(());
+    }
+}
+
+pub struct Authenticator {
+    pub mode: AuthMode,
+    tokens: HashMap<String, TokenConfig>,
+    client_certs: HashMap<String, ClientCertConfig>,
+    anonymous_role: Option<Role>,
+    jwt: Option<JwtVerifier>,
+    pub oidc: Option<OidcClient>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig, http: &HttpClient) -> Self {
+        let tokens = config
+            .tokens
+            .iter()
+            .map(|token| (token.token.clone(), token.clone()))
+            .collect();
+        let client_certs = config
+            .client_certs
+            .iter()
+            .map(|cert| (cert.common_name.clone(), cert.clone()))
+            .collect();
+        let jwt = match config.mode {
+            AuthMode::Jwt => {
+                let jwt_config = config
+                    .jwt
+                    .clone()
+                    .expect("auth.jwt must be configured in jwt mode");
+                Some(JwtVerifier::new(jwt_config, http.clone()))
+            }
+            _ => None,
+        };
+        return Self {
+            mode: config.mode,
+            tokens,
+            client_certs,
+            anonymous_role: config.anonymous_role,
+            jwt,
+            oidc: config
+                .oidc
+                .clone()
+                .map(|oidc| OidcClient::new(oidc, http.clone())),
+        };
+    }
+
+    fn anonymous(&self) -> Option<Principal> {
+        match self.mode {
+            // without auth every caller may do everything, which is what local development wants
+            AuthMode::None => Some(Principal {
+                subject: "anonymous".to_string(),
+                roles: vec![Role::Admin],
+                tenant: None,
+                anonymous: true,
+                application_id: None,
+                expires_at: None,
+            }),
+            AuthMode::Token | AuthMode::Jwt => self.anonymous_role.map(|role| Principal {
+                subject: "anonymous".to_string(),
+                roles: vec![role],
+                tenant: None,
+                anonymous: true,
+                application_id: None,
+                expires_at: None,
+            }),
+        }
+    }
+
+    async fn verify(&self, token: &str) -> Result<Principal, AppError> {
+        match self.mode {
+            AuthMode::None => return Ok(self.anonymous().unwrap()),
+            AuthMode::Token => match self.tokens.get(token) {
+                Some(config) => return Ok(token_caller(config)),
+                None => return Err(unauthorized("Invalid access token")),
+            },
+            AuthMode::Jwt => {
+                let verifier = self.jwt.as_ref().unwrap();
+                return verifier.verify(token).await.map_err(|err| {
+                    tracing::debug!("Rejected JWT: {}", err);
+                    unauthorized("Invalid access token")
+                });
+            }
+        }
+    }
+
+    /// The caller of the configured token with `subject`, while token mode still has one.
+    pub fn token_principal(&self, subject: &str) -> Option<Principal> {
+        if self.mode != AuthMode::Token {
+            return None;
+        }
+        return self
+            .tokens
+            .values()
+            .find(|config| config.subject == subject)
+            .map(token_caller);
+    }
+
+    fn verify_cert(&self, cert: &ClientCert) -> Result<Principal, AppError> {
+        let Some(config) = self.client_certs.get(&cert.0) else {
+            tracing::debug!("Rejected client certificate for unmapped CN {}", cert.0);
+            return Err(unauthorized("Client certificate is not mapped to a caller"));
+        };
+        return Ok(Principal {
+            subject: config.subject.clone(),
+            roles: vec![config.role],
+            tenant: config.tenant.clone(),
+            anonymous: false,
+            application_id: None,
+            expires_at: None,
+        });
+    }
+}
+
+fn token_caller(config: &TokenConfig) -> Principal {
+    return Principal {
+        subject: config.subject.clone(),
+        roles: vec![config.role],
+        tenant: config.tenant.clone(),
+        anonymous: false,
+        application_id: None,
+        expires_at: None,
+    };
+}
+
+fn unauthorized(message: &str) -> AppError {
+    return AppError::new(ErrorCode::Unauthorized, message);
+}
+
+/// Reads the bearer token from the `Authorization` or `X-Api-Key` header, or from the
+/// `access_token` query parameter since browsers' `EventSource` cannot set headers.
+fn bearer_token(parts: &Parts) -> Option<String> {
+    if let Some(value) = parts.headers.get("x-api-key") {
+        return value.to_str().ok().map(str::to_string);
+    }
+    if let Some(value) = parts.headers.get(header::AUTHORIZATION) {
+        let value = value.to_str().ok()?;
+        let (scheme, token) = value.split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return Some(token.trim().to_string());
+        }
+        return None;
+    }
+
+    let query = parts.uri.query()?;
+    return query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if key == "access_token" {
+            return Some(value.to_string());
+        }
+        return None;
+    });
+}
+
+/// Resolves a presented bearer token, API key or share token to its caller.
+pub async fn resolve_token(state: &AppState, token: &str) -> Result<Principal, AppError> {
+    // API keys are honoured in every mode that checks credentials at all
+    if token.starts_with(api_key::KEY_PREFIX) && state.auth.mode != AuthMode::None {
+        return api_key::authenticate(&state.store, token)
+            .ok_or_else(|| unauthorized("Invalid API key"));
+    }
+    if token.starts_with(share::TOKEN_PREFIX) {
+        return share::authenticate(&state.store, token)
+            .ok_or_else(|| unauthorized("Tracking link is unknown or has expired"));
+    }
+    return state.auth.verify(token).await;
+}
+
+/// Resolves the caller once per request. A bearer token takes precedence over a client
+/// certificate, which takes precedence over a login session. Requests without credentials
+/// pass through anonymously; requests with invalid credentials are rejected here.
+pub async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+
+    let principal = match bearer_token(&parts) {
+        Some(token) => match resolve_token(&state, &token).await {
+            Ok(principal) => Some(principal),
+            Err(err) => return err.into_response(),
+        },
+        None => match parts.extensions.get::<ClientCert>() {
+            Some(cert) if state.auth.mode != AuthMode::None => match state.auth.verify_cert(cert) {
+                Ok(principal) => Some(principal),
+                Err(err) => return err.into_response(),
+            },
+            _ => match login::principal(&state, &parts).await {
+                Ok(Some(principal)) => Some(principal),
+                Ok(None) => state.auth.anonymous(),
+                Err(err) => return err.into_response(),
+            },
+        },
+    };
+    if let Some(principal) = principal {
+        parts.extensions.insert(principal);
+    }
+
+    return next.run(Request::from_parts(parts, body)).await;
+}
+
+fn require(parts: &Parts, roles: &[Role]) -> Result<Principal, AppError> {
+    let Some(principal) = parts.extensions.get::<Principal>() else {
+        return Err(unauthorized("Missing access token"));
+    };
+    if !principal.has_any(roles) {
+        return Err(AppError::new(
+            ErrorCode::Forbidden,
+            format!(
+                "This action requires one of the roles: {}",
+                roles
+                    .iter()
+                    .map(Role::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ));
+    }
+    return Ok(principal.clone());
+}
+
+/// Any authenticated caller: may subscribe and read.
+pub struct Viewer(pub Principal);
+
+impl<S: Send + Sync> FromRequestParts<S> for Viewer {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let principal = require(parts, &[Role::Viewer, Role::Publisher, Role::Admin])?;
+        return Ok(Viewer(principal));
+    }
+}
+
+/// May post events and create applications.
+pub struct Publisher(pub Principal);
+
+impl<S: Send + Sync> FromRequestParts<S> for Publisher {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let principal = require(parts, &[Role::Publisher, Role::Admin])?;
+        return Ok(Publisher(principal));
+    }
+}
+
+/// May manage API keys and other server-wide settings.
+pub struct Admin(pub Principal);
+
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let principal = require(parts, &[Role::Admin])?;
+        return Ok(Admin(principal));
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    application::Application,
+    config::Config,
+    event::StoredEvent,
+    pii,
+    schedule::ScheduledEvent,
+    store::{Store, StoreData},
+    webhook::Webhook,
+};
+
+/// Bumped when the layout changes in a way older versions cannot restore.
+const FORMAT_VERSION: u32 = 1;
+
+/// What `visa-tracker backup` writes: the cases with their history and the webhook
+/// registrations, taken from one snapshot so events never refer to a missing case.
+/// Personal fields stay encrypted when the store encrypts them.
+#[derive(Serialize, Deserialize, Debug)]
+struct Backup {
+    format_version: u32,
+    created_at: DateTime<Utc>,
+    applications: BTreeMap<Uuid, Application>,
+    events: Vec<StoredEvent>,
+    #[serde(default)]
+    scheduled: Vec<ScheduledEvent>,
+    webhooks: Vec<Webhook>,
+    sequences: BTreeMap<Uuid, u64>,
+    global_seq: u64,
+}
+
+impl Backup {
+    fn of(data: &StoreData) -> Self {
+        return Self {
+            format_version: FORMAT_VERSION,
+            created_at: Utc::now(),
+            applications: data.applications.clone(),
+            events: data.events.clone(),
+            scheduled: data.scheduled.clone(),
+            webhooks: data.webhooks.clone(),
+            sequences: data.sequences.clone(),
+            global_seq: data.global_seq,
+        };
+    }
+}
+
+/// Opens the configured data file, which both commands need to exist.
+fn open_store(config: Option<PathBuf>, must_exist: bool) -> Result<(Config, Store), String> {
+    let config = Config::read(config)?;
+    let Some(path) = &config.store.path else {
+        return Err("store.path is not set, so nothing is persisted to back up or restore".into());
+    };
+    if must_exist && !path.exists() {
+        return Err(format!("data file {} does not exist", path.display()));
+    }
+    let store = Store::open(&config.store);
+    return Ok((config, store));
+}
+
+fn write_backup(store: &Store, out: &Path) -> Result<Backup, String> {
+    let backup = store.read(Backup::of);
+    let mut snapshot = serde_json::to_value(&backup).map_err(|err| err.to_string())?;
+    if let Some(cipher) = store.cipher() {
+        pii::seal(&mut snapshot, cipher);
+    }
+
+    let failed = |err: std::io::Error| format!("cannot write {}: {}", out.display(), err);
+    let tmp_path = out.with_extension("tmp");
+    let file = File::create(&tmp_path).map_err(failed)?;
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    serde_json::to_writer(&mut encoder, &snapshot).map_err(|err| err.to_string())?;
+    encoder
+        .finish()
+        .and_then(|mut writer| writer.flush())
+        .and_then(|_| fs::rename(&tmp_path, out))
+        .map_err(failed)?;
+    return Ok(backup);
+}
+
+/// `visa-tracker backup`: writes a gzip-compressed JSON backup of the data file. A running
+/// server's last `store.flush_interval_secs` of changes are not in the file yet.
+pub fn create(config: Option<PathBuf>, out: PathBuf) -> ExitCode {
+    let result = open_store(config, true).and_then(|(_, store)| write_backup(&store, &out));
+    match result {
+        Ok(backup) => {
+            println!(
+                "Backed up {} applications, {} events and {} webhooks to {}",
+                backup.applications.len(),
+                backup.events.len(),
+                backup.webhooks.len(),
+                out.display()
+            );
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+}
+
+fn read_backup(store: &Store, input: &Path) -> Result<Backup, String> {
+    let failed = |err: String| format!("cannot read backup {}: {}", input.display(), err);
+    let file = File::open(input).map_err(|err| failed(err.to_string()))?;
+    let mut snapshot: Value = serde_json::from_reader(GzDecoder::new(BufReader::new(file)))
+        .map_err(|err| failed(err.to_string()))?;
+    let version = snapshot.get("format_version").and_then(Value::as_u64);
+    if version != Some(FORMAT_VERSION as u64) {
+        return Err(failed(format!(
+            "unsupported format version {}",
+            version.map_or("none".to_string(), |version| version.to_string())
+        )));
+    }
+    pii::open(&mut snapshot, store.cipher()).map_err(failed)?;
+    return serde_json::from_value(snapshot).map_err(|err| failed(err.to_string()));
+}
+
+/// Replaces the cases, events and webhooks of `data` with the backup's. Cases erased
+/// since the backup was taken stay erased. Share links and room members of cases the
+/// backup lacks are dropped. Returns how many cases were skipped for being erased.
+fn apply(data: &mut StoreData, mut backup: Backup) -> usize {
+    let erased: HashSet<Uuid> = data
+        .erasures
+        .iter()
+        .map(|receipt| receipt.application_id)
+        .collect();
+    let before = backup.applications.len();
+    backup.applications.retain(|id, _| !erased.contains(id));
+    backup.events.retain(|stored| {
+        !stored
+            .event
+            .application_id
+            .is_some_and(|id| erased.contains(&id))
+    });
+    backup.scheduled.retain(|scheduled| {
+        !scheduled
+            .event
+            .application_id
+            .is_some_and(|id| erased.contains(&id))
+    });
+
+    data.applications = backup.applications;
+    data.events = backup.events;
+    data.catch_up_ids();
+    data.reindex_progress();
+    data.scheduled = backup.scheduled;
+    data.webhooks = backup.webhooks;
+    data.sequences = backup.sequences;
+    data.global_seq = backup.global_seq;
+    // pending notifications and delivery logs belong to the replaced events and webhooks
+    data.outbox.clear();
+    data.webhook_deliveries.clear();
+    let applications = &data.applications;
+    data.share_links
+        .retain(|link| applications.contains_key(&link.application_id));
+    for room in &mut data.rooms {
+        room.application_ids
+            .retain(|id| applications.contains_key(id));
+    }
+    return before - data.applications.len();
+}
+
+/// `visa-tracker restore`: loads a backup into the data file. The server must be stopped,
+/// or its next flush overwrites the restored file. API keys, sessions, rooms and the audit
+/// log are kept, as are share links of restored cases. Refuses to replace existing cases
+/// unless `force` is set.
+pub fn restore(config: Option<PathBuf>, input: PathBuf, force: bool) -> ExitCode {
+    let result = open_store(config, false).and_then(|(config, store)| {
+        let backup = read_backup(&store, &input)?;
+        let existing = store.read(|data| data.applications.len());
+        if existing > 0 && !force {
+            return Err(format!(
+                "the data file already holds {} applications; pass --force to replace them",
+                existing
+            ));
+        }
+        let summary = format!(
+            "{} applications, {} events and {} webhooks from {} (taken {})",
+            backup.applications.len(),
+            backup.events.len(),
+            backup.webhooks.len(),
+            input.display(),
+            backup.created_at.to_rfc3339()
+        );
+        let skipped = store.write(|data| apply(data, backup));
+        store.flush().map_err(|err| {
+            format!(
+                "cannot write data file {}: {}",
+                config.store.path.as_ref().unwrap().display(),
+                err
+            )
+        })?;
+        return Ok((summary, skipped));
+    });
+    match result {
+        Ok((summary, skipped)) => {
+            println!("Restored {}", summary);
+            if skipped > 0 {
+                println!("Skipped {} applications erased since the backup", skipped);
+            }
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+}
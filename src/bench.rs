@@ -0,0 +1,191 @@
+use std::{
+    process::ExitCode,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use axum_visa_tracker_sse::client::{ProgressUpdate, TrackerClient};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use futures_util::StreamExt;
+use uuid::Uuid;
+
+#[derive(Args, Debug)]
+pub struct BenchOptions {
+    /// Concurrent SSE connections.
+    #[arg(long, default_value_t = 100)]
+    subscribers: usize,
+    /// Combined publish rate of all publishers.
+    #[arg(long, default_value_t = 10)]
+    events_per_sec: u32,
+    #[arg(long, default_value_t = 1)]
+    publishers: u32,
+    /// How long to publish for.
+    #[arg(long, default_value_t = 10)]
+    duration_secs: u64,
+    /// Time given to subscribers to connect before publishing starts.
+    #[arg(long, default_value_t = 2)]
+    warmup_secs: u64,
+    /// Time allowed for the last events to arrive once publishing stops.
+    #[arg(long, default_value_t = 2)]
+    drain_secs: u64,
+    /// Subscribe and publish for one application instead of the unfiltered stream.
+    #[arg(long)]
+    application_id: Option<Uuid>,
+}
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicU64,
+    send_errors: AtomicU64,
+    received: AtomicU64,
+    /// Events the server reported as dropped through `gap` notices.
+    gap_missed: AtomicU64,
+    too_slow: AtomicU64,
+    /// Delivery latencies in microseconds, from the publisher's `occurred_at`.
+    latencies: Mutex<Vec<u64>>,
+}
+
+/// Runs subscribers and publishers against `client`'s instance and prints delivery
+/// latency percentiles and losses. Only events published by this run are measured.
+pub async fn run(client: TrackerClient, options: BenchOptions) -> ExitCode {
+    if options.subscribers == 0 || options.events_per_sec == 0 || options.publishers == 0 {
+        eprintln!("--subscribers, --events-per-sec and --publishers must be at least 1");
+        return ExitCode::FAILURE;
+    }
+    let started_at = Utc::now();
+    let counters = Arc::new(Counters::default());
+
+    let mut subscribers = Vec::with_capacity(options.subscribers);
+    for _ in 0..options.subscribers {
+        let client = client.clone();
+        let counters = counters.clone();
+        let application_id = options.application_id;
+        subscribers.push(tokio::spawn(async move {
+            let mut events = Box::pin(client.subscribe(application_id));
+            while let Some(event) = events.next().await {
+                match event.kind.as_str() {
+                    "progress" => {
+                        let Some(occurred_at) = event
+                            .data
+                            .get("occurred_at")
+                            .and_then(|value| value.as_str())
+                            .and_then(|value| value.parse::<DateTime<Utc>>().ok())
+                        else {
+                            continue;
+                        };
+                        if occurred_at < started_at {
+                            continue;
+                        }
+                        let latency = (Utc::now() - occurred_at).num_microseconds().unwrap_or(0);
+                        counters.received.fetch_add(1, Ordering::Relaxed);
+                        counters
+                            .latencies
+                            .lock()
+                            .unwrap()
+                            .push(latency.max(0) as u64);
+                    }
+                    "gap" => {
+                        let missed = event.data.get("missed").and_then(|value| value.as_u64());
+                        counters
+                            .gap_missed
+                            .fetch_add(missed.unwrap_or(0), Ordering::Relaxed);
+                    }
+                    "too-slow" => {
+                        counters.too_slow.fetch_add(1, Ordering::Relaxed);
+                    }
+                    _ => {}
+                }
+            }
+        }));
+    }
+    println!(
+        "{} subscribers connecting, publishing starts in {}s",
+        options.subscribers, options.warmup_secs
+    );
+    tokio::time::sleep(Duration::from_secs(options.warmup_secs)).await;
+
+    let per_publisher = options.events_per_sec as f64 / options.publishers as f64;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(options.duration_secs);
+    let mut publishers = Vec::new();
+    for _ in 0..options.publishers {
+        let client = client.clone();
+        let counters = counters.clone();
+        let application_id = options.application_id;
+        publishers.push(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / per_publisher));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut percentage = 0.0;
+            while tokio::time::Instant::now() < deadline {
+                interval.tick().await;
+                let update = ProgressUpdate {
+                    application_id,
+                    percentage,
+                    occurred_at: Some(Utc::now()),
+                    ttl_secs: None,
+                    // wrapping from 99 back to 0 is deliberate
+                    confirm_large_jump: true,
+                };
+                match client.send_event(&update).await {
+                    Ok(_) => counters.sent.fetch_add(1, Ordering::Relaxed),
+                    Err(err) => {
+                        tracing::warn!("Publish failed: {}", err);
+                        counters.send_errors.fetch_add(1, Ordering::Relaxed)
+                    }
+                };
+                percentage = (percentage + 1.0) % 100.0;
+            }
+        }));
+    }
+    for publisher in publishers {
+        let _ = publisher.await;
+    }
+    tokio::time::sleep(Duration::from_secs(options.drain_secs)).await;
+    for subscriber in &subscribers {
+        subscriber.abort();
+    }
+
+    report(&counters, options.subscribers);
+    return ExitCode::SUCCESS;
+}
+
+fn percentile(sorted: &[u64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    return sorted[rank] as f64 / 1000.0;
+}
+
+fn report(counters: &Counters, subscribers: usize) {
+    let sent = counters.sent.load(Ordering::Relaxed);
+    let received = counters.received.load(Ordering::Relaxed);
+    let expected = sent * subscribers as u64;
+    let mut latencies = std::mem::take(&mut *counters.latencies.lock().unwrap());
+    latencies.sort_unstable();
+
+    println!(
+        "published: {} ok, {} failed",
+        sent,
+        counters.send_errors.load(Ordering::Relaxed)
+    );
+    println!(
+        "delivered: {} of {} expected, {} lost ({} reported in gap notices), {} too-slow disconnects",
+        received,
+        expected,
+        expected.saturating_sub(received),
+        counters.gap_missed.load(Ordering::Relaxed),
+        counters.too_slow.load(Ordering::Relaxed)
+    );
+    println!(
+        "latency ms: p50 {:.2}, p90 {:.2}, p99 {:.2}, p99.9 {:.2}, max {:.2}",
+        percentile(&latencies, 50.0),
+        percentile(&latencies, 90.0),
+        percentile(&latencies, 99.0),
+        percentile(&latencies, 99.9),
+        percentile(&latencies, 100.0)
+    );
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{Json, extract::State};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{auth::Admin, config::OutboxConfig, event::EventResponse, state::AppState};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Deliveries go out as usual.
+    Closed,
+    /// The receiver kept failing; its deliveries wait until the cool-down is over.
+    Open,
+    /// The cool-down is over and one trial delivery is on its way; it decides whether the
+    /// breaker closes or opens again.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone)]
+struct Breaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<DateTime<Utc>>,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        return Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+        };
+    }
+}
+
+/// Circuit breakers of the webhook receivers, so a dead one costs a single timeout per
+/// cool-down instead of one per queued delivery. Deliveries held back by an open breaker
+/// keep their attempts. Kept in memory only; every breaker starts closed.
+#[derive(Default)]
+pub struct Breakers {
+    targets: Mutex<HashMap<Uuid, Breaker>>,
+}
+
+fn cooldown(config: &OutboxConfig) -> chrono::Duration {
+    return chrono::Duration::seconds(config.breaker_cooldown_secs as i64);
+}
+
+impl Breakers {
+    /// Whether a delivery to `target` may be attempted now. Turns an open breaker whose
+    /// cool-down passed half-open and lets exactly one delivery through.
+    pub fn allow(&self, config: &OutboxConfig, target: Uuid, now: DateTime<Utc>) -> bool {
+        if config.breaker_failures == 0 {
+            return true;
+        }
+        let mut targets = self.targets.lock().unwrap();
+        let Some(breaker) = targets.get_mut(&target) else {
+            return true;
+        };
+        match breaker.state {
+            BreakerState::Closed => return true,
+            BreakerState::HalfOpen => return false,
+            BreakerState::Open => {
+                if breaker
+                    .opened_at
+                    .is_some_and(|opened_at| now < opened_at + cooldown(config))
+                {
+                    return false;
+                }
+                breaker.state = BreakerState::HalfOpen;
+                return true;
+            }
+        }
+    }
+
+    /// Counts the outcome of a delivery attempt to `target`.
+    pub fn record(&self, config: &OutboxConfig, target: Uuid, success: bool) {
+        let mut targets = self.targets.lock().unwrap();
+        if success {
+            targets.remove(&target);
+            return;
+        }
+        let breaker = targets.entry(target).or_default();
+        breaker.consecutive_failures += 1;
+        let trips = breaker.state == BreakerState::HalfOpen
+            || (config.breaker_failures > 0
+                && breaker.consecutive_failures >= config.breaker_failures);
+        if trips {
+            if breaker.state == BreakerState::Closed {
+                tracing::warn!(
+                    "Pausing deliveries to webhook {} for {}s after {} consecutive failures",
+                    target,
+                    config.breaker_cooldown_secs,
+                    breaker.consecutive_failures
+                );
+                metrics::counter!("outbox_breaker_opened_total").increment(1);
+            }
+            breaker.state = BreakerState::Open;
+            breaker.opened_at = Some(Utc::now());
+        }
+    }
+
+    /// Closes the breaker, e.g. for a deleted webhook or a receiver said to be fixed.
+    pub fn remove(&self, target: Uuid) {
+        self.targets.lock().unwrap().remove(&target);
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct BreakerView {
+    webhook_id: Uuid,
+    url: String,
+    state: BreakerState,
+    consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    opened_at: Option<DateTime<Utc>>,
+    /// When an open breaker lets the next trial delivery through.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_at: Option<DateTime<Utc>>,
+}
+
+/// `GET /admin/breakers`: the breaker of every webhook the caller may see.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Json<EventResponse<Vec<BreakerView>>> {
+    let config = state.config();
+    let targets = state.breakers.targets.lock().unwrap().clone();
+    let views = state.store.read(|data| {
+        data.webhooks
+            .iter()
+            .filter(|webhook| admin.can_access(webhook.tenant.as_deref()))
+            .map(|webhook| {
+                let breaker = targets.get(&webhook.id).cloned().unwrap_or_default();
+                return BreakerView {
+                    webhook_id: webhook.id,
+                    url: webhook.url.clone(),
+                    state: breaker.state,
+                    consecutive_failures: breaker.consecutive_failures,
+                    opened_at: breaker.opened_at,
+                    retry_at: breaker
+                        .opened_at
+                        .filter(|_| breaker.state == BreakerState::Open)
+                        .map(|opened_at| opened_at + cooldown(&config.outbox)),
+                };
+            })
+            .collect()
+    });
+    return Json(EventResponse::ok(views));
+}
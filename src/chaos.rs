@@ -0,0 +1,145 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{get, post},
+};
+use axum_extra::extract::WithRejection;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::Admin, config::Profile, error::AppError, event::EventResponse, state::AppState};
+
+/// Sent as the `data:` of injected frames: an envelope cut off mid-object.
+pub const MALFORMED_DATA: &str = r#"{"v":1,"type":"progress","data":{"percentage":"#;
+
+/// Faults currently applied to every SSE stream.
+#[derive(Debug, Default)]
+pub struct Chaos {
+    latency_ms: AtomicU64,
+    jitter_ms: AtomicU64,
+}
+
+impl Chaos {
+    /// How long to hold the next delivery back, if latency injection is on.
+    pub fn delay(&self) -> Option<Duration> {
+        let latency = self.latency_ms.load(Ordering::Relaxed);
+        let jitter = self.jitter_ms.load(Ordering::Relaxed);
+        if latency == 0 && jitter == 0 {
+            return None;
+        }
+        let jitter = if jitter == 0 {
+            0
+        } else {
+            rand::rng().random_range(0..=jitter)
+        };
+        return Some(Duration::from_millis(latency + jitter));
+    }
+
+    fn settings(&self) -> LatencySettings {
+        return LatencySettings {
+            latency_ms: self.latency_ms.load(Ordering::Relaxed),
+            jitter_ms: self.jitter_ms.load(Ordering::Relaxed),
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LatencySettings {
+    /// Added before every delivery; `0` turns injection off.
+    #[serde(default)]
+    latency_ms: u64,
+    /// Extra random delay of up to this many milliseconds.
+    #[serde(default)]
+    jitter_ms: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TargetRequest {
+    /// How many random connections to hit; all of them when omitted.
+    count: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Affected {
+    connections: usize,
+}
+
+/// The chaos routes, unless the instance runs with the prod profile.
+pub fn routes(profile: Profile) -> Router<Arc<AppState>> {
+    if profile == Profile::Prod {
+        tracing::warn!("Built with the chaos feature, but its endpoints are disabled in prod");
+        return Router::new();
+    }
+    tracing::warn!("Chaos endpoints are enabled under /admin/chaos");
+    return Router::new()
+        .route("/admin/chaos/latency", get(latency).put(set_latency))
+        .route("/admin/chaos/disconnect", post(disconnect))
+        .route("/admin/chaos/malformed", post(malformed));
+}
+
+pub async fn latency(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<LatencySettings>>, AppError> {
+    admin.require_operator()?;
+    return Ok(Json(EventResponse::ok(state.chaos.settings())));
+}
+
+pub async fn set_latency(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<LatencySettings>, AppError>,
+) -> Result<Json<EventResponse<LatencySettings>>, AppError> {
+    admin.require_operator()?;
+    state
+        .chaos
+        .latency_ms
+        .store(payload.latency_ms, Ordering::Relaxed);
+    state
+        .chaos
+        .jitter_ms
+        .store(payload.jitter_ms, Ordering::Relaxed);
+    tracing::warn!(
+        "{} set SSE latency injection to {}ms (+{}ms jitter)",
+        admin.subject,
+        payload.latency_ms,
+        payload.jitter_ms
+    );
+    return Ok(Json(EventResponse::ok(state.chaos.settings())));
+}
+
+/// Force-closes connections so clients have to reconnect.
+pub async fn disconnect(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<TargetRequest>, AppError>,
+) -> Result<Json<EventResponse<Affected>>, AppError> {
+    admin.require_operator()?;
+    let connections = state.hub.close_random(payload.count);
+    tracing::warn!("{} force-closed {} connections", admin.subject, connections);
+    return Ok(Json(EventResponse::ok(Affected { connections })));
+}
+
+/// Sends a frame whose data is not valid JSON.
+pub async fn malformed(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<TargetRequest>, AppError>,
+) -> Result<Json<EventResponse<Affected>>, AppError> {
+    admin.require_operator()?;
+    let connections = state.hub.inject_malformed(payload.count);
+    tracing::warn!(
+        "{} sent malformed events to {} connections",
+        admin.subject,
+        connections
+    );
+    return Ok(Json(EventResponse::ok(Affected { connections })));
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    application::{IfMatch, authorize_active, not_found},
+    auth::Publisher,
+    config::ChecklistConfig,
+    error::{AppError, ErrorCode},
+    event::{self, AppEvent, EventResponse, Priority, Visibility},
+    state::AppState,
+};
+
+#[derive(Serialize, Debug)]
+pub struct StageProgress {
+    pub application_id: Uuid,
+    pub completed_stages: Vec<String>,
+    pub percentage: f64,
+    /// Subscribers the recomputed percentage was sent to; 0 when the stage was already done.
+    pub listeners: usize,
+}
+
+/// Weighted share of the checklist covered by `completed`, in 0-100.
+pub fn percentage(checklist: &ChecklistConfig, completed: &[String]) -> f64 {
+    let total: f64 = checklist.stages.iter().map(|stage| stage.weight).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let done: f64 = checklist
+        .stages
+        .iter()
+        .filter(|stage| completed.contains(&stage.name))
+        .map(|stage| stage.weight)
+        .sum();
+    return (done / total * 100.0).clamp(0.0, 100.0);
+}
+
+/// Marks a stage complete and broadcasts the recomputed percentage, followed by an
+/// `event: stage` naming the stage. Completing a stage twice is a no-op.
+pub async fn complete(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    if_match: IfMatch,
+    WithRejection(Path((id, stage)), _): WithRejection<Path<(Uuid, String)>, AppError>,
+) -> Result<Json<EventResponse<StageProgress>>, AppError> {
+    let tenant = authorize_active(&state, &publisher, id)?;
+    let config = state.config();
+    let checklist = &config.checklist;
+    if checklist.stages.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::ChecklistNotConfigured,
+            "No stage checklist is configured; send percentages to /events/send instead",
+        ));
+    }
+    if !checklist.stages.iter().any(|known| known.name == stage) {
+        return Err(AppError::new(
+            ErrorCode::StageNotFound,
+            format!("Stage {} is not part of the checklist", stage),
+        ));
+    }
+
+    let (completed_stages, newly_completed) =
+        state
+            .store
+            .write(|data| -> Result<(Vec<String>, bool), AppError> {
+                let application = data
+                    .applications
+                    .get_mut(&id)
+                    .ok_or_else(|| not_found(id))?;
+                if_match.check(application)?;
+                let newly_completed = !application.completed_stages.contains(&stage);
+                if newly_completed {
+                    application.completed_stages.push(stage.clone());
+                    application.touch();
+                }
+                return Ok((application.completed_stages.clone(), newly_completed));
+            })?;
+
+    let percentage = percentage(checklist, &completed_stages);
+    let mut listeners = 0;
+    if newly_completed {
+        tracing::debug!("{} completed stage {} of {}", publisher.subject, stage, id);
+        let event = AppEvent {
+            application_id: Some(id),
+            percentage,
+            occurred_at: None,
+            ttl_secs: None,
+            priority: Priority::Normal,
+            visibility: Visibility::Public,
+            stage: None,
+        };
+        listeners = event::record(
+            &state,
+            event,
+            tenant.clone(),
+            &publisher.subject,
+            Utc::now(),
+        );
+        let _ = state.broadcast(
+            Some(id),
+            tenant,
+            "stage",
+            json!({ "application_id": id, "stage": stage, "percentage": percentage }),
+        );
+    }
+
+    return Ok(Json(EventResponse::ok(StageProgress {
+        application_id: id,
+        completed_stages,
+        percentage,
+        listeners,
+    })));
+}
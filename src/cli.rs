@@ -0,0 +1,151 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use axum_visa_tracker_sse::client::{ProgressUpdate, TrackerClient};
+use clap::{Args, Parser, Subcommand};
+use futures_util::StreamExt;
+use uuid::Uuid;
+
+use crate::bench::BenchOptions;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "visa-tracker",
+    version,
+    about = "Live progress tracking for visa applications"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the server (the default without a subcommand).
+    Serve {
+        /// Config file; defaults to $APP_CONFIG, then ./config.toml.
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Watch `assets/` and tell open pages to reload on changes; disables caching.
+        #[arg(long)]
+        dev: bool,
+    },
+    /// Post a progress event to a running instance.
+    Send {
+        #[command(flatten)]
+        target: Target,
+        #[arg(long)]
+        percentage: f64,
+        #[arg(long)]
+        application_id: Option<Uuid>,
+        /// Stop replaying the event after this many seconds.
+        #[arg(long)]
+        ttl_secs: Option<u64>,
+        /// Accept a change bigger than the server's maximum step.
+        #[arg(long)]
+        confirm_large_jump: bool,
+    },
+    /// Load-test an instance with many subscribers and report delivery latency and losses.
+    Bench {
+        #[command(flatten)]
+        target: Target,
+        #[command(flatten)]
+        options: BenchOptions,
+    },
+    /// Write the configured data file's applications, events and webhooks to a
+    /// gzip-compressed JSON backup.
+    Backup {
+        /// Config file; defaults to $APP_CONFIG, then ./config.toml.
+        #[arg(long)]
+        config: Option<PathBuf>,
+        #[arg(long, default_value = "visa-tracker-backup.json.gz")]
+        out: PathBuf,
+    },
+    /// Load a backup into the configured data file. Stop the server first.
+    Restore {
+        /// Config file; defaults to $APP_CONFIG, then ./config.toml.
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Backup written by `visa-tracker backup`.
+        #[arg(long = "from")]
+        input: PathBuf,
+        /// Replace the applications already in the data file.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Follow `/events` and print everything that arrives.
+    Tail {
+        #[command(flatten)]
+        target: Target,
+        #[arg(long)]
+        application_id: Option<Uuid>,
+    },
+}
+
+/// The instance a client subcommand talks to.
+#[derive(Args, Debug)]
+pub struct Target {
+    #[arg(
+        long,
+        env = "VISA_TRACKER_URL",
+        default_value = "http://127.0.0.1:4000"
+    )]
+    url: String,
+    /// Bearer token or API key.
+    #[arg(long, env = "VISA_TRACKER_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+}
+
+impl Target {
+    pub fn client(self) -> TrackerClient {
+        let client = TrackerClient::new(self.url);
+        match self.token {
+            Some(token) => return client.with_token(token),
+            None => return client,
+        }
+    }
+}
+
+pub async fn send(
+    target: Target,
+    percentage: f64,
+    application_id: Option<Uuid>,
+    ttl_secs: Option<u64>,
+    confirm_large_jump: bool,
+) -> ExitCode {
+    let update = ProgressUpdate {
+        application_id,
+        percentage,
+        occurred_at: None,
+        ttl_secs,
+        confirm_large_jump,
+    };
+    match target.client().send_event(&update).await {
+        Ok(message) => {
+            println!("{}", message);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+}
+
+pub async fn tail(target: Target, application_id: Option<Uuid>) -> ExitCode {
+    let client = target.client();
+    let mut events = Box::pin(client.subscribe(application_id));
+    while let Some(event) = events.next().await {
+        let seq = event
+            .seq
+            .map(|seq| format!(" #{}", seq))
+            .unwrap_or_default();
+        println!(
+            "{} {}{} {}",
+            event.timestamp.as_deref().unwrap_or("-"),
+            event.kind,
+            seq,
+            event.data
+        );
+    }
+    return ExitCode::SUCCESS;
+}
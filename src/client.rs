@@ -0,0 +1,295 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A progress update as accepted by `POST /events/send`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProgressUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application_id: Option<Uuid>,
+    pub percentage: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurred_at: Option<DateTime<Utc>>,
+    /// Seconds after which the server stops replaying the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Accepts a change bigger than the server's configured maximum step.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub confirm_large_jump: bool,
+}
+
+/// One event from the stream, unwrapped from the versioned envelope.
+#[derive(Debug, Clone)]
+pub struct TrackerEvent {
+    /// SSE `id:`, when the server sent one.
+    pub id: Option<String>,
+    /// Envelope `type`, e.g. `progress`, `viewers`, `note` or `gap`.
+    pub kind: String,
+    pub seq: Option<u64>,
+    pub timestamp: Option<String>,
+    pub data: Value,
+}
+
+impl TrackerEvent {
+    /// The progress update carried by a `progress` event.
+    pub fn progress(&self) -> Option<ProgressUpdate> {
+        if self.kind != "progress" {
+            return None;
+        }
+        return serde_json::from_value(self.data.clone()).ok();
+    }
+
+    fn from_frame(frame: Frame) -> Option<Self> {
+        if frame.data.is_empty() {
+            return None;
+        }
+        let payload: Value = serde_json::from_str(&frame.data).ok()?;
+        let kind = payload
+            .get("type")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or(frame.event)
+            .unwrap_or_else(|| "progress".to_string());
+        return Some(Self {
+            id: frame.id,
+            kind,
+            seq: payload.get("seq").and_then(Value::as_u64),
+            timestamp: payload
+                .get("timestamp")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            data: payload.get("data").cloned().unwrap_or(payload),
+        });
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    Http(reqwest::Error),
+    /// The server answered with its error envelope.
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(err) => return write!(f, "request failed: {}", err),
+            ClientError::Api {
+                status,
+                code,
+                message,
+            } => return write!(f, "{} {}: {}", status, code, message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(value: reqwest::Error) -> Self {
+        return ClientError::Http(value);
+    }
+}
+
+/// The API version this client speaks; paths below are relative to it.
+const API_PREFIX: &str = "/api/v1";
+
+/// Talks to a running tracker, e.g. `TrackerClient::new("http://127.0.0.1:4000")`.
+#[derive(Clone)]
+pub struct TrackerClient {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl TrackerClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        return Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            // the server requires a User-Agent on `/events`
+            http: reqwest::Client::builder()
+                .user_agent(concat!("visa-tracker-client/", env!("CARGO_PKG_VERSION")))
+                .build()
+                .expect("default HTTP client configuration is valid"),
+        };
+    }
+
+    /// Bearer token or API key sent with every request.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        return self;
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}{}", self.base_url, API_PREFIX, path));
+        match &self.token {
+            Some(token) => return request.bearer_auth(token),
+            None => return request,
+        }
+    }
+
+    /// Publishes a progress update and returns the server's confirmation message.
+    pub async fn send_event(&self, update: &ProgressUpdate) -> Result<String, ClientError> {
+        let response = self
+            .request(reqwest::Method::POST, "/events/send")
+            .json(update)
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+        let body: Value = response.json().await?;
+        if let Some(error) = body.get("error") {
+            let field = |name: &str| {
+                error
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            return Err(ClientError::Api {
+                status,
+                code: field("code"),
+                message: field("message"),
+            });
+        }
+        return Ok(body
+            .pointer("/data/message")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string());
+    }
+
+    /// Follows `/events` forever, reconnecting with exponential backoff. Reconnects resume
+    /// the stream's session with the token from its `session` event, so events published
+    /// in between are replayed; once the token has lapsed a fresh stream is opened.
+    pub fn subscribe(&self, application_id: Option<Uuid>) -> impl Stream<Item = TrackerEvent> {
+        let client = self.clone();
+        return async_stream::stream! {
+            let fresh = match application_id {
+                Some(id) => format!("/events?application_id={}", id),
+                None => "/events".to_string(),
+            };
+            let mut resume_token: Option<String> = None;
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                let path = match &resume_token {
+                    Some(token) => format!("/events?resume={}", token),
+                    None => fresh.clone(),
+                };
+                let request = client
+                    .request(reqwest::Method::GET, &path)
+                    .header(reqwest::header::ACCEPT, "text/event-stream");
+
+                match request.send().await.and_then(|response| response.error_for_status()) {
+                    Ok(response) => {
+                        backoff = INITIAL_BACKOFF;
+                        let mut parser = FrameParser::default();
+                        let mut body = response.bytes_stream();
+                        while let Some(chunk) = body.next().await {
+                            let chunk = match chunk {
+                                Ok(chunk) => chunk,
+                                Err(err) => {
+                                    tracing::warn!("Event stream interrupted: {}", err);
+                                    break;
+                                }
+                            };
+                            for frame in parser.push(&chunk) {
+                                if let Some(retry) = frame.retry {
+                                    backoff = retry;
+                                }
+                                let Some(event) = TrackerEvent::from_frame(frame) else {
+                                    continue;
+                                };
+                                if event.kind == "session"
+                                    && let Some(token) =
+                                        event.data.get("resume_token").and_then(Value::as_str)
+                                {
+                                    resume_token = Some(token.to_string());
+                                }
+                                yield event;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        // a lapsed token is refused; start over rather than retry it forever
+                        if err.status().is_some() {
+                            resume_token = None;
+                        }
+                        tracing::warn!("Cannot connect to event stream: {}", err);
+                    }
+                }
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        };
+    }
+}
+
+/// The fields of one SSE message, collected up to the blank line that ends it.
+#[derive(Debug, Default)]
+struct Frame {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+/// Incremental `text/event-stream` parser; chunks may split lines anywhere.
+#[derive(Default)]
+struct FrameParser {
+    buffer: Vec<u8>,
+    current: Frame,
+}
+
+impl FrameParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<Frame> {
+        self.buffer.extend_from_slice(chunk);
+        let mut frames = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                frames.push(std::mem::take(&mut self.current));
+                continue;
+            }
+            if line.starts_with(':') {
+                // comment, used by the server for keep-alives
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "id" => self.current.id = Some(value.to_string()),
+                "event" => self.current.event = Some(value.to_string()),
+                "data" => {
+                    if !self.current.data.is_empty() {
+                        self.current.data.push('\n');
+                    }
+                    self.current.data.push_str(value);
+                }
+                "retry" => {
+                    self.current.retry = value.parse().ok().map(Duration::from_millis);
+                }
+                _ => {}
+            }
+        }
+        return frames;
+    }
+}
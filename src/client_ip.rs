@@ -0,0 +1,221 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{HeaderMap, Method, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    config::ProxyConfig,
+    error::{AppError, ErrorCode},
+    state::AppState,
+};
+
+/// The address of the client that made the request, as seen through trusted proxies.
+/// Use it wherever requests are attributed to or limited per client. Unspecified
+/// (`0.0.0.0`) only when the server runs without connection info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// An address or CIDR block from `proxy.trusted_proxies` or the `access` lists.
+#[derive(Debug, Clone, Copy)]
+struct Network {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl Network {
+    fn parse(value: &str) -> Option<Self> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let address: IpAddr = address.trim().parse().ok()?;
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().ok().filter(|prefix| *prefix <= max)?,
+            None => max,
+        };
+        return Some(Network { address, prefix });
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                return u32::from(network) & mask == u32::from(ip) & mask;
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                return u128::from(network) & mask == u128::from(ip) & mask;
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn matches_any(entries: &[String], ip: IpAddr) -> bool {
+    return entries
+        .iter()
+        .any(|entry| Network::parse(entry).is_some_and(|network| network.contains(ip)));
+}
+
+fn is_trusted(config: &ProxyConfig, ip: IpAddr) -> bool {
+    return matches_any(&config.trusted_proxies, ip);
+}
+
+/// Fails on the first entry of `entries` that is neither an address nor a CIDR block, so
+/// a typo cannot silently open an access list.
+pub fn check_networks(key: &str, entries: &[String]) -> Result<(), String> {
+    match entries.iter().find(|entry| Network::parse(entry).is_none()) {
+        Some(entry) => {
+            return Err(format!(
+                "invalid address or CIDR block in {}: {}",
+                key, entry
+            ));
+        }
+        None => return Ok(()),
+    }
+}
+
+/// Parses one hop: a bare or bracketed address with an optional port. `None` for
+/// obfuscated identifiers such as `unknown` or `_hidden`.
+fn parse_hop(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    return value
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(ip, _)| ip.parse().ok());
+}
+
+/// The hops recorded by proxies, client first: `Forwarded` (RFC 7239) when present,
+/// otherwise `X-Forwarded-For`.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded: Vec<&str> = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (name, value) = pair.split_once('=')?;
+                    name.trim()
+                        .eq_ignore_ascii_case("for")
+                        .then(|| parse_hop(value))
+                })
+            })
+            .collect();
+    }
+    return headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_hop)
+        .collect();
+}
+
+/// Walks the chain from the nearest hop back towards the client and stops at the first
+/// address that is not a trusted proxy. Headers are ignored unless the peer itself is
+/// trusted, so clients cannot spoof their address by sending them directly.
+pub fn resolve_ip(config: &ProxyConfig, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    let mut client = peer.to_canonical();
+    if !is_trusted(config, client) {
+        return client;
+    }
+    for hop in forwarded_chain(headers).into_iter().rev() {
+        let Some(hop) = hop else {
+            break;
+        };
+        client = hop.to_canonical();
+        if !is_trusted(config, client) {
+            break;
+        }
+    }
+    return client;
+}
+
+/// Attaches [`ClientIp`] to every request. Runs outside the trace layer so request spans
+/// can include it.
+pub async fn resolve(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        let ip = resolve_ip(&state.config().proxy, peer.ip(), request.headers());
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    return next.run(request).await;
+}
+
+/// `POST /events/send` under `/api/v1` and the unversioned alias.
+const SEND_PATHS: &[&str] = &["/api/v1/events/send", "/events/send"];
+
+/// Refuses `POST /events/send` from addresses outside `access.publish_allow`. Layered
+/// outside authentication, so refused addresses never cost a token or API key check.
+pub async fn allow_publishers(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if request.method() != Method::POST || !SEND_PATHS.contains(&request.uri().path()) {
+        return Ok(next.run(request).await);
+    }
+    let allowed = &state.config().access.publish_allow;
+    if !allowed.is_empty() && !matches_any(allowed, ip) {
+        tracing::info!("Refused event from {}: not in access.publish_allow", ip);
+        return Err(AppError::new(
+            ErrorCode::AddressForbidden,
+            format!("Events cannot be published from {}", ip),
+        ));
+    }
+    return Ok(next.run(request).await);
+}
+
+/// Refuses streams from addresses in `access.subscribe_deny`.
+pub async fn deny_subscribers(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if matches_any(&state.config().access.subscribe_deny, ip) {
+        tracing::info!("Refused stream from {}: in access.subscribe_deny", ip);
+        return Err(AppError::new(
+            ErrorCode::AddressForbidden,
+            format!("Streams cannot be opened from {}", ip),
+        ));
+    }
+    return Ok(next.run(request).await);
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ClientIp>()
+            .copied()
+            .unwrap_or(ClientIp(IpAddr::from([0, 0, 0, 0])));
+        return Ok(ip);
+    }
+}
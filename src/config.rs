@@ -0,0 +1,741 @@
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    announcement::Announcement,
+    auth::Role,
+    client_ip, cors,
+    fanout::OverflowPolicy,
+    flags::Flags,
+    outbound,
+    payload_schema::PayloadSchema,
+    quota::Quotas,
+    visa_type::{self, VisaSchema},
+};
+
+const CONFIG_PATH_ENV: &str = "APP_CONFIG";
+const PROFILE_ENV: &str = "APP_PROFILE";
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    #[default]
+    Dev,
+    Prod,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub profile: Profile,
+    pub listen_addr: String,
+    pub server: ServerConfig,
+    pub cors: CorsConfig,
+    pub proxy: ProxyConfig,
+    pub access: AccessConfig,
+    pub drain: DrainConfig,
+    pub limits: LimitsConfig,
+    pub auth: AuthConfig,
+    pub store: StoreConfig,
+    /// When set, `POST /events/send` bodies must carry an HMAC signature.
+    pub signature: Option<SignatureConfig>,
+    pub retention: RetentionConfig,
+    pub sse: SseConfig,
+    pub events: EventsConfig,
+    pub checklist: ChecklistConfig,
+    pub visa_types: VisaTypesConfig,
+    pub quotas: Quotas,
+    pub outbox: OutboxConfig,
+    pub outbound: OutboundConfig,
+    pub frontend: FrontendConfig,
+    pub logging: LoggingConfig,
+    /// When set, panics, 5xx responses and logged errors are reported to Sentry.
+    pub sentry: Option<SentryConfig>,
+    /// Initial values of the runtime feature flags.
+    pub flags: Flags,
+    /// Recurring messages broadcast to every stream.
+    pub announcements: Vec<Announcement>,
+    /// The file this was read from, re-read on reload.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        return Self {
+            profile: Profile::default(),
+            listen_addr: "127.0.0.1:4000".to_string(),
+            server: ServerConfig::default(),
+            cors: CorsConfig::default(),
+            proxy: ProxyConfig::default(),
+            access: AccessConfig::default(),
+            drain: DrainConfig::default(),
+            limits: LimitsConfig::default(),
+            auth: AuthConfig::default(),
+            store: StoreConfig::default(),
+            signature: None,
+            retention: RetentionConfig::default(),
+            sse: SseConfig::default(),
+            events: EventsConfig::default(),
+            checklist: ChecklistConfig::default(),
+            visa_types: VisaTypesConfig::default(),
+            quotas: Quotas::default(),
+            outbox: OutboxConfig::default(),
+            outbound: OutboundConfig::default(),
+            frontend: FrontendConfig::default(),
+            logging: LoggingConfig::default(),
+            sentry: None,
+            flags: Flags::default(),
+            announcements: Vec::new(),
+            source: None,
+        };
+    }
+}
+
+/// Connection settings of the listener, tuned for many long-lived streams.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Accept HTTP/2 next to HTTP/1.1: negotiated through ALPN under TLS, otherwise as
+    /// prior-knowledge h2c from a proxy.
+    pub http2: bool,
+    /// Streams a single HTTP/2 connection may have open at once.
+    pub http2_max_concurrent_streams: u32,
+    /// When set, idle HTTP/2 connections are pinged at this interval.
+    pub http2_keep_alive_interval_secs: Option<u64>,
+    /// Connections whose ping goes unanswered this long are closed.
+    pub http2_keep_alive_timeout_secs: u64,
+    /// Reuse HTTP/1.1 connections for further requests.
+    pub http1_keep_alive: bool,
+    /// HTTP/1.1 connections that do not send a complete request head in time are closed.
+    pub header_read_timeout_secs: u64,
+    /// On shutdown, how long open connections get to finish after every stream was asked
+    /// to reconnect. Whatever is still open then is dropped.
+    pub shutdown_timeout_secs: u64,
+    /// When set, the listener speaks TLS itself.
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        return Self {
+            http2: true,
+            http2_max_concurrent_streams: 1000,
+            http2_keep_alive_interval_secs: None,
+            http2_keep_alive_timeout_secs: 20,
+            http1_keep_alive: true,
+            header_read_timeout_secs: 30,
+            shutdown_timeout_secs: 10,
+            tls: None,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first.
+    pub cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1).
+    pub key_path: PathBuf,
+    /// PEM bundle of the CAs that issue client certificates. Enables mutual TLS: callers
+    /// presenting a certificate are identified through `auth.client_certs`.
+    pub client_ca_path: Option<PathBuf>,
+    /// Refuses handshakes without a client certificate. Leave off when browsers share the
+    /// listener; they keep authenticating with tokens.
+    #[serde(default)]
+    pub require_client_cert: bool,
+    /// Connections that have not finished the handshake by then are closed.
+    #[serde(default = "default_tls_handshake_timeout_secs")]
+    pub handshake_timeout_secs: u64,
+}
+
+fn default_tls_handshake_timeout_secs() -> u64 {
+    return 10;
+}
+
+/// Unset lists fall back to the profile default: everything in `dev`, nothing in `prod`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Exact origins (`https://tracker.example.com`) or wildcard subdomains
+    /// (`https://*.example.com`).
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Option<Vec<String>>,
+    pub allowed_headers: Option<Vec<String>>,
+    pub allow_credentials: bool,
+}
+
+/// Reverse proxies whose `Forwarded` / `X-Forwarded-For` headers are believed.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ProxyConfig {
+    /// Addresses or CIDR blocks, e.g. `127.0.0.1` or `10.0.0.0/8`. Empty trusts nobody,
+    /// so the peer address is always the client.
+    pub trusted_proxies: Vec<String>,
+}
+
+/// Network restrictions for deployments where publishers are only internal systems.
+/// Checked against the client address (see `proxy`) before authentication.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Addresses or CIDR blocks allowed to call `POST /events/send`. Empty allows any.
+    pub publish_allow: Vec<String>,
+    /// Addresses or CIDR blocks refused on the SSE endpoints.
+    pub subscribe_deny: Vec<String>,
+}
+
+/// How subscribers are moved off an instance put into drain mode via `POST /admin/drain`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DrainConfig {
+    /// Where the `reconnect` event sends clients, normally the load balancer's stream URL.
+    /// Without it clients reconnect to the URL they used.
+    pub reconnect_url: Option<String>,
+    /// Sent as `Retry-After` to refused subscribers and as the `retry` delay of the
+    /// `reconnect` event.
+    pub retry_after_secs: u64,
+}
+
+impl Default for DrainConfig {
+    fn default() -> Self {
+        return Self {
+            reconnect_url: None,
+            retry_after_secs: 5,
+        };
+    }
+}
+
+/// Delivery of webhook notifications from the outbox.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OutboxConfig {
+    /// How often the dispatcher looks for due entries.
+    pub poll_interval_ms: u64,
+    pub timeout_ms: u64,
+    /// Failed attempts after which an entry is dead-lettered.
+    pub max_attempts: u32,
+    /// Backoff after the first failure, doubling per attempt up to `max_backoff_secs`.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_secs: u64,
+    /// Consecutive failures after which a receiver's deliveries are held back for
+    /// `breaker_cooldown_secs`; `0` disables the breaker.
+    pub breaker_failures: u32,
+    pub breaker_cooldown_secs: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        return Self {
+            poll_interval_ms: 1000,
+            timeout_ms: 5000,
+            max_attempts: 8,
+            initial_backoff_ms: 1000,
+            max_backoff_secs: 3600,
+            breaker_failures: 5,
+            breaker_cooldown_secs: 60,
+        };
+    }
+}
+
+/// How webhooks, the identity provider and JWKS are reached. Without `proxy`, the
+/// `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables apply.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OutboundConfig {
+    /// e.g. `http://proxy.corp.example:3128`; credentials may be part of the URL.
+    pub proxy: Option<String>,
+    /// Hosts, domains (`.corp.example`) and networks reached directly instead of via `proxy`.
+    pub no_proxy: Vec<String>,
+    pub connect_timeout_ms: u64,
+    /// Whole-request limit; webhook deliveries use `outbox.timeout_ms` instead.
+    pub timeout_ms: u64,
+    /// Idle connections kept open per destination for reuse.
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout_secs: u64,
+    /// Extra attempts for reads (GET) that fail to connect, time out or get a 429 or 5xx.
+    /// Webhooks are retried by the outbox instead.
+    pub retries: u32,
+    /// Delay before the first retry, growing linearly with each further one.
+    pub retry_backoff_ms: u64,
+    /// Defaults to `visa-tracker/<version>`.
+    pub user_agent: Option<String>,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        return Self {
+            proxy: None,
+            no_proxy: Vec::new(),
+            connect_timeout_ms: 3000,
+            timeout_ms: 10_000,
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout_secs: 90,
+            retries: 2,
+            retry_backoff_ms: 200,
+            user_agent: None,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Maximum accepted body size of `POST /events/send`, in bytes.
+    pub send_body_bytes: usize,
+    /// Applies to JSON endpoints only; SSE streams are long-lived by design.
+    pub request_timeout_ms: u64,
+    /// In-flight requests beyond this are shed with 503 instead of queueing.
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        return Self {
+            send_body_bytes: 16 * 1024,
+            request_timeout_ms: 10_000,
+            max_concurrent_requests: 1024,
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// Every caller is treated as an anonymous admin. Refused with the prod profile.
+    #[default]
+    None,
+    /// Callers present one of the configured `tokens`.
+    Token,
+    /// Callers present a JWT signed by a key from `jwt.jwks_url`.
+    Jwt,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub mode: AuthMode,
+    pub tokens: Vec<TokenConfig>,
+    /// Role granted to requests without a token, e.g. `viewer` for a public tracker page.
+    pub anonymous_role: Option<Role>,
+    pub jwt: Option<JwtConfig>,
+    /// Identities of callers authenticated by a TLS client certificate.
+    pub client_certs: Vec<ClientCertConfig>,
+    /// How long a login session lasts without a request.
+    pub session_ttl_secs: u64,
+    /// Lets staff sign in through the agency's identity provider.
+    pub oidc: Option<OidcConfig>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        return Self {
+            mode: AuthMode::default(),
+            tokens: Vec::new(),
+            anonymous_role: None,
+            jwt: None,
+            client_certs: Vec::new(),
+            session_ttl_secs: 8 * 60 * 60,
+            oidc: None,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct JwtConfig {
+    pub jwks_url: String,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Dotted path to the array of role names inside the claims.
+    #[serde(default = "default_roles_claim")]
+    pub roles_claim: String,
+    #[serde(default = "default_jwks_cache_secs")]
+    pub cache_secs: u64,
+    /// Dotted path to the tenant id inside the claims; tokens without it are operators.
+    pub tenant_claim: Option<String>,
+}
+
+/// An OpenID Connect client using the authorization-code flow with PKCE.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OidcConfig {
+    /// Endpoints and keys are discovered from `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// This server's `/api/v1/login/oidc/callback`, as registered with the provider.
+    pub redirect_url: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// Dotted path to the array of group names inside the ID token.
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+    /// Tracker role of each group's members. Staff in none of these groups cannot sign in.
+    pub group_roles: HashMap<String, Role>,
+    /// Dotted path to the tenant id inside the ID token; staff without it are operators.
+    pub tenant_claim: Option<String>,
+    /// Where staff land after signing in.
+    #[serde(default = "default_post_login_url")]
+    pub post_login_url: String,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    return vec!["openid".to_string(), "profile".to_string()];
+}
+
+fn default_groups_claim() -> String {
+    return "groups".to_string();
+}
+
+fn default_post_login_url() -> String {
+    return "/".to_string();
+}
+
+fn default_roles_claim() -> String {
+    return "realm_access.roles".to_string();
+}
+
+fn default_jwks_cache_secs() -> u64 {
+    return 300;
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenConfig {
+    pub token: String,
+    pub subject: String,
+    pub role: Role,
+    /// Confines the token to one tenant's applications; without it the token is an operator.
+    pub tenant: Option<String>,
+}
+
+/// Maps the subject common name of a verified client certificate to a caller, like a
+/// token does for its bearer.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ClientCertConfig {
+    pub common_name: String,
+    pub subject: String,
+    pub role: Role,
+    pub tenant: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct StoreConfig {
+    /// JSON snapshot file; without it everything is kept in memory only.
+    pub path: Option<PathBuf>,
+    pub flush_interval_secs: u64,
+    /// File holding a base64-encoded 256-bit key. When set, applicant names, emails and
+    /// notes are encrypted in the snapshot, as are the outbox and audit payloads.
+    pub encryption_key_path: Option<PathBuf>,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        return Self {
+            path: None,
+            flush_interval_secs: 5,
+            encryption_key_path: None,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SignatureConfig {
+    pub secret: String,
+    /// How far the signed timestamp may drift from the server clock, in seconds.
+    #[serde(default = "default_max_skew_secs")]
+    pub max_skew_secs: u64,
+}
+
+fn default_max_skew_secs() -> u64 {
+    return 300;
+}
+
+/// The event limits are off by default, so history is kept forever unless configured; the
+/// audit log keeps its newest 100 000 entries.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub max_age_days: Option<u64>,
+    pub max_events_per_application: Option<usize>,
+    /// Audit entries older than this are dropped.
+    pub audit_max_age_days: Option<u64>,
+    /// Only the newest this many audit entries are kept.
+    pub max_audit_entries: usize,
+    pub interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        return Self {
+            max_age_days: None,
+            max_events_per_application: None,
+            audit_max_age_days: None,
+            max_audit_entries: 100_000,
+            interval_secs: 3600,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SseConfig {
+    /// Quiet period before a changed viewer count is broadcast.
+    pub viewers_debounce_ms: u64,
+    /// Interval of the keep-alive comments sent on idle streams.
+    pub keep_alive_secs: u64,
+    /// When set, streams also carry an `event: heartbeat` with the current percentage and
+    /// server time at this interval, which keeps keep-alive comments from being needed.
+    pub heartbeat_secs: Option<u64>,
+    /// What happens when a subscriber's queue is full: `drop-oldest`, `drop-newest` or
+    /// `disconnect`.
+    pub overflow_policy: OverflowPolicy,
+    /// Events a single subscriber may have queued; adjustable via `PUT /admin/fanout`.
+    pub queue_capacity: usize,
+    /// How long an application channel without subscribers is kept before being swept.
+    pub channel_idle_secs: u64,
+    /// Disconnect subscribers with more queued events than this.
+    pub slow_consumer_max_lag: Option<usize>,
+    /// Disconnect subscribers whose oldest queued event has waited longer than this.
+    pub slow_consumer_max_lag_secs: Option<u64>,
+    /// When set, streams are asked to reconnect once they have been open this long, so
+    /// load balancers can rebalance long-lived connections.
+    pub max_connection_secs: Option<u64>,
+    /// Upper bound of the random `retry` delay sent with a max-age `reconnect`.
+    pub reconnect_jitter_ms: u64,
+    /// How long a closed stream's resume token stays valid.
+    pub resume_ttl_secs: u64,
+    /// Split the subscribers into this many shards, each filled by a worker task of its
+    /// own, instead of having every publish fill all queues itself. 0 disables it.
+    pub fanout_workers: usize,
+}
+
+impl Default for SseConfig {
+    fn default() -> Self {
+        return Self {
+            viewers_debounce_ms: 1000,
+            keep_alive_secs: 15,
+            heartbeat_secs: None,
+            overflow_policy: OverflowPolicy::default(),
+            queue_capacity: 800,
+            channel_idle_secs: 300,
+            slow_consumer_max_lag: None,
+            slow_consumer_max_lag_secs: None,
+            max_connection_secs: None,
+            reconnect_jitter_ms: 5000,
+            resume_ttl_secs: 300,
+            fanout_workers: 0,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct EventsConfig {
+    /// How far ahead of server time a publisher's `occurred_at` may be.
+    pub max_future_skew_secs: u64,
+    /// Adds `monotonic_ms` (milliseconds since server start) to broadcast payloads.
+    pub include_monotonic: bool,
+    /// Minimum gap between an application's progress events while the `throttle` flag is on.
+    pub throttle_interval_ms: u64,
+    /// Largest change from the previous percentage a single event may make, guarding
+    /// against typos; bigger jumps need `confirm_large_jump`. Unlimited when unset.
+    pub max_step: Option<f64>,
+    /// JSON Schema every `POST /events/send` payload must match, on top of the built-in
+    /// checks. Rules can change with a config reload instead of a new build.
+    pub schema_path: Option<PathBuf>,
+    /// Compiled from `schema_path` along with the rest of the config.
+    #[serde(skip)]
+    pub schema: Option<PayloadSchema>,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        return Self {
+            max_future_skew_secs: 300,
+            include_monotonic: false,
+            throttle_interval_ms: 1000,
+            max_step: None,
+            schema_path: None,
+            schema: None,
+        };
+    }
+}
+
+/// Values injected into `index.html`, so one build can serve several deployments.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct FrontendConfig {
+    /// The stream the page subscribes to; absolute when it lives behind another host.
+    pub events_url: String,
+    /// Pins the page to one application instead of following every event.
+    pub application_id: Option<Uuid>,
+    /// Answer browser navigation to unknown paths with `index.html` so the frontend can do
+    /// client-side routing. API clients always get a JSON 404.
+    pub spa_fallback: bool,
+    /// Origin the server is reached at, e.g. `https://tracker.example.com`, for links that
+    /// leave the browser such as QR codes. Defaults to `http://` and the request's `Host`.
+    pub public_url: Option<String>,
+}
+
+impl Default for FrontendConfig {
+    fn default() -> Self {
+        return Self {
+            events_url: "/api/v1/events".to_string(),
+            application_id: None,
+            spa_fallback: true,
+            public_url: None,
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Also write logs to rotating files; stdout logging stays on either way.
+    pub file: Option<LogFileConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct LogFileConfig {
+    pub directory: PathBuf,
+    /// File names are this prefix followed by the date (and hour) of the period.
+    #[serde(default = "default_log_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Older files beyond this many are deleted on rotation; all are kept when unset.
+    pub max_files: Option<usize>,
+}
+
+fn default_log_prefix() -> String {
+    return "visa-tracker.log".to_string();
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SentryConfig {
+    pub dsn: String,
+    /// Defaults to the profile name.
+    pub environment: Option<String>,
+    /// Share of error events that are sent, between 0 and 1.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f32,
+}
+
+fn default_sample_rate() -> f32 {
+    return 1.0;
+}
+
+/// When any stages are configured, application progress is computed from the stages marked
+/// complete and raw percentages for applications are refused.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ChecklistConfig {
+    pub stages: Vec<StageConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StageConfig {
+    pub name: String,
+    /// Share of the total progress relative to the other stages' weights.
+    #[serde(default = "default_stage_weight")]
+    pub weight: f64,
+}
+
+fn default_stage_weight() -> f64 {
+    return 1.0;
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct VisaTypesConfig {
+    /// JSON file with the stages of each visa type; events naming a `stage` are checked
+    /// against their application's type.
+    pub schemas_path: Option<PathBuf>,
+    /// Read from `schemas_path` along with the rest of the config.
+    #[serde(skip)]
+    pub schemas: HashMap<String, VisaSchema>,
+}
+
+impl Config {
+    /// Reads `path`, else the TOML file pointed to by `APP_CONFIG`, else `config.toml` when
+    /// present, then applies the `APP_PROFILE` override. Panics on an unusable file.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        return Config::read(path).unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Like [`Config::load`], but reports problems instead of panicking.
+    pub fn read(path: Option<PathBuf>) -> Result<Self, String> {
+        let explicit_path = path.or_else(|| env::var(CONFIG_PATH_ENV).ok().map(PathBuf::from));
+        let path = explicit_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str::<Config>(&content)
+                .map_err(|err| format!("invalid config file {}: {}", path.display(), err))?,
+            Err(err) if explicit_path.is_some() => {
+                return Err(format!(
+                    "cannot read config file {}: {}",
+                    path.display(),
+                    err
+                ));
+            }
+            Err(_) => Config::default(),
+        };
+        config.source = explicit_path;
+        for (name, secs) in [
+            (
+                "store.flush_interval_secs",
+                config.store.flush_interval_secs,
+            ),
+            ("retention.interval_secs", config.retention.interval_secs),
+        ] {
+            if secs == 0 {
+                return Err(format!("{} must be at least 1", name));
+            }
+        }
+        if config.sse.queue_capacity == 0 {
+            return Err("Queue capacity must be at least 1".to_string());
+        }
+        // only built here to report bad entries; the state builds its own
+        let _ = cors::layer(&config.cors, config.profile)?;
+        client_ip::check_networks("access.publish_allow", &config.access.publish_allow)?;
+        client_ip::check_networks("access.subscribe_deny", &config.access.subscribe_deny)?;
+        outbound::proxy(&config.outbound)?;
+        if let Some(path) = &config.events.schema_path {
+            config.events.schema = Some(PayloadSchema::load(path)?);
+        }
+        if let Some(path) = &config.visa_types.schemas_path {
+            config.visa_types.schemas = visa_type::load(path)?;
+        }
+
+        if let Ok(profile) = env::var(PROFILE_ENV) {
+            config.profile = match profile.to_lowercase().as_str() {
+                "dev" => Profile::Dev,
+                "prod" => Profile::Prod,
+                other => return Err(format!("unknown {} value: {}", PROFILE_ENV, other)),
+            };
+        }
+        if config.profile == Profile::Prod && config.auth.mode == AuthMode::None {
+            return Err(
+                "auth.mode \"none\" makes every caller an admin and is refused with the prod \
+                 profile; set auth.mode to \"token\" or \"jwt\""
+                    .to_string(),
+            );
+        }
+
+        return Ok(config);
+    }
+}
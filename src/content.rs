@@ -0,0 +1,406 @@
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{HeaderMap, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+};
+
+/// Representations the read endpoints can produce.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+    #[serde(alias = "messagepack")]
+    Msgpack,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => return "application/json",
+            Format::Csv => return "text/csv",
+            Format::Msgpack => return "application/msgpack",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Json => return "json",
+            Format::Csv => return "csv",
+            Format::Msgpack => return "msgpack",
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => return Some(Format::Json),
+            "text/csv" | "text/*" => return Some(Format::Csv),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                return Some(Format::Msgpack);
+            }
+            _ => return None,
+        }
+    }
+
+    /// Picks the producible media type with the highest q-value; JSON when the header is
+    /// missing, and `None` when it only names types we can't produce.
+    pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        let Some(accept) = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Some(Format::Json);
+        };
+        let mut best: Option<(Format, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let Some(format) = params.next().and_then(Format::from_media_type) else {
+                continue;
+            };
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        return best.map(|(format, _)| format);
+    }
+}
+
+/// The representation requested through `Accept`; rejects with 406 when none fits.
+pub struct Negotiated(pub Format);
+
+impl<S: Send + Sync> FromRequestParts<S> for Negotiated {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let format = Format::from_accept(&parts.headers).ok_or_else(|| {
+            AppError::new(
+                ErrorCode::NotAcceptable,
+                "Supported media types are application/json, text/csv and application/msgpack",
+            )
+        })?;
+        return Ok(Negotiated(format));
+    }
+}
+
+/// A flat row for CSV output; nested collections are left to the JSON representations.
+pub trait CsvRecord {
+    const HEADER: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or line break (RFC 4180).
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_string();
+}
+
+pub fn csv_row<T: CsvRecord>(record: &T) -> String {
+    let fields: Vec<String> = record
+        .fields()
+        .iter()
+        .map(|field| csv_field(field))
+        .collect();
+    return format!("{}\n", fields.join(","));
+}
+
+pub fn csv_header<T: CsvRecord>() -> String {
+    return format!("{}\n", T::HEADER.join(","));
+}
+
+/// Excel's "CSV UTF-8" starts files with it.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+/// Longest field and record accepted, so an unclosed quote cannot buffer a whole upload.
+const MAX_FIELD_BYTES: usize = 64 * 1024;
+const MAX_RECORD_BYTES: usize = 256 * 1024;
+
+/// Splits CSV (RFC 4180) into records as chunks of it arrive, so an upload is processed
+/// without holding all of it. Quoted fields may span lines; blank lines are skipped, and
+/// so is a leading byte order mark.
+#[derive(Debug)]
+pub struct CsvReader {
+    record: Vec<String>,
+    /// Bytes in the fields of `record` so far.
+    record_bytes: usize,
+    field: Vec<u8>,
+    /// How much of a leading byte order mark has been seen; `None` once past it.
+    bom: Option<usize>,
+    in_quotes: bool,
+    /// Just saw a quote in a quoted field: either the first of an escaped pair or its end.
+    quote_pending: bool,
+    line: usize,
+    /// Line on which the current record started.
+    record_line: usize,
+}
+
+impl Default for CsvReader {
+    fn default() -> Self {
+        return Self {
+            record: Vec::new(),
+            record_bytes: 0,
+            field: Vec::new(),
+            bom: Some(0),
+            in_quotes: false,
+            quote_pending: false,
+            line: 1,
+            record_line: 1,
+        };
+    }
+}
+
+impl CsvReader {
+    /// Consumes a chunk and returns the records it completed with their line numbers.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<(usize, Vec<String>)>, String> {
+        let mut records = Vec::new();
+        for &byte in chunk {
+            if let Some(matched) = self.bom {
+                if byte == BOM[matched] {
+                    self.bom = Some(matched + 1).filter(|matched| *matched < BOM.len());
+                    continue;
+                }
+                // not a mark after all; what was held back is content
+                self.bom = None;
+                for &held in &BOM[..matched] {
+                    self.push_byte(held)?;
+                }
+            }
+            if self.quote_pending {
+                self.quote_pending = false;
+                if byte == b'"' {
+                    self.push_byte(byte)?;
+                    continue;
+                }
+                self.in_quotes = false;
+            }
+            if self.in_quotes {
+                match byte {
+                    b'"' => self.quote_pending = true,
+                    b'\n' => {
+                        self.line += 1;
+                        self.push_byte(byte)?;
+                    }
+                    _ => self.push_byte(byte)?,
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_quotes = true,
+                b',' => self.end_field()?,
+                b'\n' => {
+                    if let Some(record) = self.end_record()? {
+                        records.push(record);
+                    }
+                    self.line += 1;
+                    self.record_line = self.line;
+                }
+                b'\r' => {}
+                _ => self.push_byte(byte)?,
+            }
+        }
+        return Ok(records);
+    }
+
+    fn push_byte(&mut self, byte: u8) -> Result<(), String> {
+        if self.field.len() >= MAX_FIELD_BYTES {
+            return Err(format!(
+                "line {}: field is longer than {} bytes",
+                self.record_line, MAX_FIELD_BYTES
+            ));
+        }
+        if self.record_bytes + self.field.len() >= MAX_RECORD_BYTES {
+            return Err(format!(
+                "line {}: record is longer than {} bytes",
+                self.record_line, MAX_RECORD_BYTES
+            ));
+        }
+        self.field.push(byte);
+        return Ok(());
+    }
+
+    /// The last record, when the input does not end with a line break.
+    pub fn finish(mut self) -> Result<Option<(usize, Vec<String>)>, String> {
+        if self.in_quotes && !self.quote_pending {
+            return Err(format!(
+                "line {}: quoted field is never closed",
+                self.record_line
+            ));
+        }
+        return self.end_record();
+    }
+
+    fn end_field(&mut self) -> Result<(), String> {
+        let field = String::from_utf8(std::mem::take(&mut self.field))
+            .map_err(|_| format!("line {}: not valid UTF-8", self.line))?;
+        self.record_bytes += field.len();
+        self.record.push(field);
+        return Ok(());
+    }
+
+    fn end_record(&mut self) -> Result<Option<(usize, Vec<String>)>, String> {
+        self.end_field()?;
+        let record = std::mem::take(&mut self.record);
+        self.record_bytes = 0;
+        if record.len() == 1 && record[0].is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some((self.record_line, record)));
+    }
+}
+
+/// Renders records in the negotiated format. JSON and MessagePack keep the usual envelope;
+/// CSV is the bare table.
+pub fn respond<T: Serialize + CsvRecord>(format: Format, records: Vec<T>) -> Response {
+    match format {
+        Format::Json => return Json(EventResponse::ok(records)).into_response(),
+        Format::Csv => {
+            let mut body = csv_header::<T>();
+            for record in &records {
+                body.push_str(&csv_row(record));
+            }
+            return ([(header::CONTENT_TYPE, format.content_type())], body).into_response();
+        }
+        Format::Msgpack => return msgpack(&EventResponse::ok(records)),
+    }
+}
+
+pub fn msgpack<T: Serialize>(value: &T) -> Response {
+    match rmp_serde::to_vec_named(value) {
+        Ok(bytes) => {
+            return (
+                [(header::CONTENT_TYPE, Format::Msgpack.content_type())],
+                bytes,
+            )
+                .into_response();
+        }
+        Err(err) => {
+            tracing::error!("Failed to encode MessagePack response: {}", err);
+            return AppError::from(ErrorCode::UnknownError).into_response();
+        }
+    }
+}
+
+/// One record in the negotiated format; the CSV representation is a single-row table.
+pub fn respond_one<T: Serialize + CsvRecord>(format: Format, record: T) -> Response {
+    match format {
+        Format::Json => return Json(EventResponse::ok(record)).into_response(),
+        Format::Csv => {
+            let body = format!("{}{}", csv_header::<T>(), csv_row(&record));
+            return ([(header::CONTENT_TYPE, format.content_type())], body).into_response();
+        }
+        Format::Msgpack => return msgpack(&EventResponse::ok(record)),
+    }
+}
+
+/// The serialized name of a unit enum variant, e.g. `submitted` for a document status.
+pub fn variant_name<T: Serialize>(value: &T) -> String {
+    return serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(chunks: &[&[u8]]) -> Result<Vec<(usize, Vec<String>)>, String> {
+        let mut reader = CsvReader::default();
+        let mut records = Vec::new();
+        for chunk in chunks {
+            records.extend(reader.push(chunk)?);
+        }
+        records.extend(reader.finish()?);
+        return Ok(records);
+    }
+
+    fn record(line: usize, fields: &[&str]) -> (usize, Vec<String>) {
+        return (line, fields.iter().map(|field| field.to_string()).collect());
+    }
+
+    #[test]
+    fn joins_records_split_across_chunks() {
+        let records = read(&[b"name,visa\nAl", b"ice,tour", b"ist\nBob,work\n"]).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                record(1, &["name", "visa"]),
+                record(2, &["Alice", "tourist"]),
+                record(3, &["Bob", "work"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_doubled_quotes() {
+        let records = read(&[b"\"say \"\"hi\"\"\",x\n"]).unwrap();
+        assert_eq!(records, vec![record(1, &["say \"hi\"", "x"])]);
+    }
+
+    #[test]
+    fn handles_a_quote_at_the_end_of_a_chunk() {
+        let escaped = read(&[b"\"a\"", b"\"b\",c\n"]).unwrap();
+        assert_eq!(escaped, vec![record(1, &["a\"b", "c"])]);
+        let closing = read(&[b"\"a\"", b",c\n"]).unwrap();
+        assert_eq!(closing, vec![record(1, &["a", "c"])]);
+        let last = read(&[b"x,\"a\""]).unwrap();
+        assert_eq!(last, vec![record(1, &["x", "a"])]);
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        let records = read(&[b"a,b\r\n\r\nc,d\r\n"]).unwrap();
+        assert_eq!(
+            records,
+            vec![record(1, &["a", "b"]), record(3, &["c", "d"])]
+        );
+    }
+
+    #[test]
+    fn rejects_an_unterminated_quote() {
+        let err = read(&[b"a,b\nc,\"d\ne\n"]).unwrap_err();
+        assert_eq!(err, "line 2: quoted field is never closed");
+    }
+
+    #[test]
+    fn numbers_records_after_multi_line_fields() {
+        let records = read(&[b"x,\"one\ntwo\nthree\"\ny,z\n"]).unwrap();
+        assert_eq!(
+            records,
+            vec![record(1, &["x", "one\ntwo\nthree"]), record(4, &["y", "z"])]
+        );
+    }
+
+    #[test]
+    fn skips_a_byte_order_mark() {
+        let records = read(&[b"\xEF\xBB", b"\xBFapplicant_name\nA\n"]).unwrap();
+        assert_eq!(
+            records,
+            vec![record(1, &["applicant_name"]), record(2, &["A"])]
+        );
+    }
+
+    #[test]
+    fn caps_field_length() {
+        let mut reader = CsvReader::default();
+        reader.push(b"\"").unwrap();
+        let err = reader.push(&vec![b'a'; MAX_FIELD_BYTES + 1]).unwrap_err();
+        assert_eq!(
+            err,
+            format!("line 1: field is longer than {} bytes", MAX_FIELD_BYTES)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, Method, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use tower::{Layer, ServiceExt};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+use crate::{
+    config::{CorsConfig, Profile},
+    state::AppState,
+};
+
+enum OriginMatcher {
+    Exact(HeaderValue),
+    /// `https://*.example.com` matches `https://a.example.com` but not `https://example.com`.
+    Subdomain {
+        scheme: String,
+        suffix: String,
+    },
+}
+
+impl OriginMatcher {
+    fn parse(origin: &str) -> Result<Self, String> {
+        if let Some((scheme, host)) = origin.split_once("://")
+            && let Some(domain) = host.strip_prefix("*.")
+        {
+            return Ok(OriginMatcher::Subdomain {
+                scheme: scheme.to_lowercase(),
+                suffix: format!(".{}", domain.to_lowercase()),
+            });
+        }
+
+        let value = HeaderValue::from_str(origin)
+            .map_err(|_| format!("invalid CORS origin: {}", origin))?;
+        return Ok(OriginMatcher::Exact(value));
+    }
+
+    fn matches(&self, origin: &HeaderValue) -> bool {
+        match self {
+            OriginMatcher::Exact(value) => return value == origin,
+            OriginMatcher::Subdomain { scheme, suffix } => {
+                let Ok(origin) = origin.to_str() else {
+                    return false;
+                };
+                let Some((origin_scheme, host)) = origin.split_once("://") else {
+                    return false;
+                };
+                let host = host.to_lowercase();
+                return origin_scheme.eq_ignore_ascii_case(scheme)
+                    && host.len() > suffix.len()
+                    && host.ends_with(suffix.as_str());
+            }
+        }
+    }
+}
+
+// ref: https://dev.to/amaendeepm/axum-in-rus-flexibility-cors-control-and-tower-power-4ich
+/// Builds the layer for `[cors]`; fails on origins, methods or headers that are not valid.
+pub fn layer(config: &CorsConfig, profile: Profile) -> Result<CorsLayer, String> {
+    // browsers refuse wildcard responses for credentialed requests, so mirror instead
+    let credentials = config.allow_credentials;
+
+    let allow_origin = match &config.allowed_origins {
+        Some(origins) if origins.iter().any(|origin| origin == "*") => {
+            if credentials {
+                AllowOrigin::mirror_request()
+            } else {
+                AllowOrigin::from(Any)
+            }
+        }
+        Some(origins) => {
+            let matchers = origins
+                .iter()
+                .map(|o| OriginMatcher::parse(o))
+                .collect::<Result<Vec<_>, _>>()?;
+            AllowOrigin::predicate(move |origin: &HeaderValue, _: &Parts| {
+                return matchers.iter().any(|matcher| matcher.matches(origin));
+            })
+        }
+        None if profile == Profile::Dev => {
+            if credentials {
+                AllowOrigin::mirror_request()
+            } else {
+                AllowOrigin::from(Any)
+            }
+        }
+        None => AllowOrigin::list(Vec::<HeaderValue>::new()),
+    };
+
+    let allow_methods = match &config.allowed_methods {
+        Some(methods) => AllowMethods::list(
+            methods
+                .iter()
+                .map(|method| {
+                    return Method::from_bytes(method.to_uppercase().as_bytes())
+                        .map_err(|_| format!("invalid CORS method: {}", method));
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None if profile == Profile::Dev && credentials => AllowMethods::mirror_request(),
+        None if profile == Profile::Dev => AllowMethods::from(Any),
+        None => AllowMethods::list([Method::GET, Method::POST, Method::PATCH]),
+    };
+
+    let allow_headers = match &config.allowed_headers {
+        Some(headers) => AllowHeaders::list(
+            headers
+                .iter()
+                .map(|name| {
+                    return HeaderName::from_bytes(name.to_lowercase().as_bytes())
+                        .map_err(|_| format!("invalid CORS header: {}", name));
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None if profile == Profile::Dev && credentials => AllowHeaders::mirror_request(),
+        None if profile == Profile::Dev => AllowHeaders::from(Any),
+        None => AllowHeaders::list([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::IF_MATCH,
+        ]),
+    };
+
+    return Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        // readable by browser clients: paging totals, back-off hints, versions and
+        // deprecation notices
+        .expose_headers([
+            HeaderName::from_static("x-total-count"),
+            header::RETRY_AFTER,
+            header::ETAG,
+            HeaderName::from_static("deprecation"),
+            header::LINK,
+        ])
+        .allow_credentials(credentials));
+}
+
+/// Applies whichever CORS layer is current, so a config reload takes effect on the next
+/// request.
+pub async fn dynamic(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    // the layer answers every OPTIONS as a preflight; plain ones are for the routes
+    if request.method() == Method::OPTIONS
+        && !request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+    {
+        return next.run(request).await;
+    }
+    let layer = state.cors.read().unwrap().clone();
+    match layer.layer(next).oneshot(request).await {
+        Ok(response) => return response,
+        Err(never) => match never {},
+    }
+}
@@ -0,0 +1,64 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::{frontend, state::AppState};
+
+/// Editors save in several steps; changes this close together trigger a single reload.
+const SETTLE: Duration = Duration::from_millis(150);
+
+/// Runs under `--dev`: recompiles the index template whenever something under `assets/`
+/// changes and sends every open stream an `event: reload`.
+pub async fn watch_assets(app_state: Arc<AppState>) {
+    let (changed_tx, mut changed_rx) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        if let Ok(event) = result
+            && matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            )
+        {
+            let _ = changed_tx.send(());
+        }
+    });
+    // the watcher stops when dropped, so it lives as long as this task
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            tracing::error!("Failed to start the asset watcher: {}", err);
+            return;
+        }
+    };
+    if let Err(err) = watcher.watch(&frontend::assets_dir(), RecursiveMode::Recursive) {
+        tracing::error!("Failed to watch assets: {}", err);
+        return;
+    }
+
+    while changed_rx.recv().await.is_some() {
+        tokio::time::sleep(SETTLE).await;
+        while changed_rx.try_recv().is_ok() {}
+
+        if let Err(err) = app_state.templates.reload() {
+            tracing::error!("Keeping the previous template: {}", err);
+            continue;
+        }
+        let reached = app_state.hub.request_reload();
+        tracing::info!("Assets changed; asked {} subscribers to reload", reached);
+    }
+}
+
+/// Keeps browsers from caching anything while assets are being edited.
+pub async fn no_store(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    return response;
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderName, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{
+    auth::Role,
+    config::{AuthMode, Config},
+    content::Format,
+    event::{EventResponse, SCHEMA_VERSION},
+    signature,
+    state::AppState,
+    version,
+};
+
+/// Methods `/events/send` answers to, as sent in `Allow`.
+const SEND_METHODS: &[&str] = &["OPTIONS", "POST"];
+
+#[derive(Serialize, Debug)]
+pub struct AuthRequirements {
+    mode: AuthMode,
+    /// Where credentials go: `Authorization: Bearer`, `X-Api-Key` or `?access_token=`.
+    credentials: &'static [&'static str],
+    /// Roles allowed to call the endpoint.
+    roles: Vec<Role>,
+    /// Role of callers without credentials; `null` when they are turned away.
+    anonymous_role: Option<Role>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SignatureRequirements {
+    algorithm: &'static str,
+    /// The HMAC covers `"{timestamp}.{body}"`.
+    headers: [&'static str; 2],
+    max_skew_secs: u64,
+}
+
+/// What a publisher must get right for `POST /events/send` to accept an event.
+#[derive(Serialize, Debug)]
+pub struct SendCapabilities {
+    methods: &'static [&'static str],
+    content_types: [&'static str; 1],
+    max_body_bytes: usize,
+    /// Largest change per event without `confirm_large_jump`; `null` when unlimited.
+    max_step: Option<f64>,
+    auth: AuthRequirements,
+    /// Present when bodies must be signed.
+    signature: Option<SignatureRequirements>,
+}
+
+impl SendCapabilities {
+    fn of(config: &Config) -> Self {
+        return Self {
+            methods: SEND_METHODS,
+            content_types: [Format::Json.content_type()],
+            max_body_bytes: config.limits.send_body_bytes,
+            max_step: config.events.max_step,
+            auth: AuthRequirements {
+                mode: config.auth.mode,
+                credentials: &["bearer", "x-api-key", "access_token"],
+                roles: vec![Role::Publisher, Role::Admin],
+                anonymous_role: match config.auth.mode {
+                    AuthMode::None => Some(Role::Admin),
+                    AuthMode::Token | AuthMode::Jwt => config.auth.anonymous_role,
+                },
+            },
+            signature: config
+                .signature
+                .as_ref()
+                .map(|signature| SignatureRequirements {
+                    algorithm: "hmac-sha256",
+                    headers: [signature::SIGNATURE_HEADER, signature::TIMESTAMP_HEADER],
+                    max_skew_secs: signature.max_skew_secs,
+                }),
+        };
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ApiVersion {
+    version: &'static str,
+    base_path: &'static str,
+    /// The `v` of the event envelope this version sends.
+    schema_version: u32,
+}
+
+/// Everything a client needs to configure itself against this instance.
+#[derive(Serialize, Debug)]
+pub struct Capabilities {
+    server_version: &'static str,
+    api_versions: Vec<ApiVersion>,
+    events_url: String,
+    /// Media types the read endpoints can answer with.
+    formats: Vec<&'static str>,
+    send: SendCapabilities,
+}
+
+/// `OPTIONS /events/send`: the content type, size limit and credentials a send needs.
+/// Browser preflights never get here; the CORS layer answers them.
+pub async fn send_options(State(state): State<Arc<AppState>>) -> Response {
+    let config = state.config();
+    return (
+        [
+            (header::ALLOW, SEND_METHODS.join(", ")),
+            (
+                HeaderName::from_static("accept-post"),
+                Format::Json.content_type().to_string(),
+            ),
+        ],
+        Json(EventResponse::ok(SendCapabilities::of(&config))),
+    )
+        .into_response();
+}
+
+/// `GET /.well-known/visa-tracker.json`: open to everyone, so clients can discover where
+/// to connect before they hold credentials.
+pub async fn well_known(State(state): State<Arc<AppState>>) -> Json<Capabilities> {
+    let config = state.config();
+    return Json(Capabilities {
+        server_version: version::build_info().version,
+        api_versions: vec![ApiVersion {
+            version: "v1",
+            base_path: "/api/v1",
+            schema_version: SCHEMA_VERSION,
+        }],
+        events_url: config.frontend.events_url.clone(),
+        formats: [Format::Json, Format::Csv, Format::Msgpack]
+            .iter()
+            .map(Format::content_type)
+            .collect(),
+        send: SendCapabilities::of(&config),
+    });
+}
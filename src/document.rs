@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    application::{authorize, authorize_active, not_found},
+    auth::{Publisher, Viewer},
+    content::{self, CsvRecord, Negotiated},
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentStatus {
+    #[default]
+    Missing,
+    Submitted,
+    Verified,
+    Rejected,
+}
+
+/// One entry of an application's document checklist, identified by its name
+/// (`passport-copy`, `bank-statement`, ...).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Document {
+    pub name: String,
+    pub status: DocumentStatus,
+    /// Why a document was rejected, or anything else the applicant should know.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CsvRecord for Document {
+    const HEADER: &'static [&'static str] = &["name", "status", "comment", "updated_at"];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![
+            self.name.clone(),
+            content::variant_name(&self.status),
+            self.comment.clone().unwrap_or_default(),
+            self.updated_at.to_rfc3339(),
+        ];
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DocumentUpdate {
+    name: String,
+    #[serde(default)]
+    status: DocumentStatus,
+    comment: Option<String>,
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(format): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Response, AppError> {
+    authorize(&state, &viewer, id)?;
+    let documents = state
+        .store
+        .read(|data| {
+            data.applications
+                .get(&id)
+                .map(|application| application.documents.clone())
+        })
+        .ok_or_else(|| not_found(id))?;
+    return Ok(content::respond(format, documents));
+}
+
+/// Adds a document to the checklist, or updates the status of the one with the same name,
+/// and tells the application's subscribers with `event: document-update`.
+pub async fn upsert(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Json(payload), _): WithRejection<Json<DocumentUpdate>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<Document>>), AppError> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::new(
+            ErrorCode::InvalidDocument,
+            "Document name must not be empty",
+        ));
+    }
+
+    let tenant = authorize_active(&state, &publisher, id)?;
+    let document = Document {
+        name: payload.name,
+        status: payload.status,
+        comment: payload.comment,
+        updated_at: Utc::now(),
+    };
+    let created = state
+        .store
+        .write(|data| {
+            let application = data.applications.get_mut(&id)?;
+            application.touch();
+            let existing = application
+                .documents
+                .iter_mut()
+                .find(|known| known.name == document.name);
+            match existing {
+                Some(known) => {
+                    *known = document.clone();
+                    return Some(false);
+                }
+                None => {
+                    application.documents.push(document.clone());
+                    return Some(true);
+                }
+            }
+        })
+        .ok_or_else(|| not_found(id))?;
+
+    tracing::debug!(
+        "{} set document {} of {} to {:?}",
+        publisher.subject,
+        document.name,
+        id,
+        document.status
+    );
+    let _ = state.broadcast(
+        Some(id),
+        tenant,
+        "document-update",
+        json!({ "application_id": id, "document": document }),
+    );
+
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    return Ok((status, Json(EventResponse::ok(document))));
+}
@@ -0,0 +1,100 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{Json, extract::State};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    auth::Admin, error::AppError, event::EventResponse, fanout::Disconnect, state::AppState,
+};
+
+/// Whether this instance is being taken out of rotation. Once draining it stays so until
+/// the process exits.
+#[derive(Default)]
+pub struct Drain {
+    started_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Drain {
+    pub fn is_draining(&self) -> bool {
+        return self.started_at.lock().unwrap().is_some();
+    }
+
+    /// Marks the start of the drain; `false` when it was already under way.
+    fn start(&self) -> bool {
+        let mut started_at = self.started_at.lock().unwrap();
+        if started_at.is_some() {
+            return false;
+        }
+        *started_at = Some(Utc::now());
+        return true;
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DrainStatus {
+    draining: bool,
+    started_at: Option<DateTime<Utc>>,
+    open_streams: usize,
+    /// Draining and every stream has closed; the instance can be stopped.
+    drained: bool,
+}
+
+fn status(state: &AppState) -> DrainStatus {
+    let started_at = *state.drain.started_at.lock().unwrap();
+    let open_streams = state.hub.subscriber_count();
+    return DrainStatus {
+        draining: started_at.is_some(),
+        started_at,
+        open_streams,
+        drained: started_at.is_some() && open_streams == 0,
+    };
+}
+
+/// Logs once the last stream of a draining instance has closed.
+async fn watch(app_state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        ticker.tick().await;
+        if app_state.hub.subscriber_count() == 0 {
+            tracing::info!("Drain complete: all streams have closed");
+            return;
+        }
+    }
+}
+
+/// Refuses new subscriptions from now on and sends every open stream a `reconnect` event
+/// once its queued events are out. Does nothing when the drain is already under way.
+pub fn begin(state: &Arc<AppState>, by: &str) {
+    if state.drain.start() {
+        let closing = state.hub.close_all(Disconnect::Drain);
+        tracing::info!(
+            "{} started draining; asking {} subscribers to reconnect",
+            by,
+            closing
+        );
+        tokio::spawn(watch(state.clone()));
+    }
+}
+
+/// `POST /admin/drain`: starts draining, see [`begin`]. Poll `GET /admin/drain` until
+/// `drained` before stopping the instance.
+pub async fn start(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<DrainStatus>>, AppError> {
+    admin.require_operator()?;
+    begin(&state, &admin.subject);
+    return Ok(Json(EventResponse::ok(status(&state))));
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<DrainStatus>>, AppError> {
+    admin.require_operator()?;
+    return Ok(Json(EventResponse::ok(status(&state))));
+}
@@ -0,0 +1,578 @@
+use axum::{
+    BoxError, Json,
+    extract::{
+        Request,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+use tower::{load_shed::error::Overloaded, timeout::error::Elapsed};
+
+use crate::{
+    event::{EventData, EventResponse},
+    i18n,
+};
+
+/// Every machine-readable error code the API can answer with. Clients match on these, so
+/// variants are only ever added, never renamed.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    AddressForbidden,
+    ApiKeyNotFound,
+    ApplicationArchived,
+    ApplicationNotFound,
+    ApplicationQuotaExceeded,
+    BufferError,
+    ChecklistNotConfigured,
+    ConfigReloadFailed,
+    EmptyScopesError,
+    EventAlreadyRetracted,
+    EventNotFound,
+    EventQuotaExceeded,
+    EventThrottled,
+    Forbidden,
+    IdentityProviderError,
+    InvalidAppointment,
+    InvalidCapacity,
+    InvalidCsrfToken,
+    InvalidDocument,
+    InvalidImport,
+    InvalidLogFilter,
+    InvalidNote,
+    InvalidPathParameter,
+    InvalidQueryParameter,
+    InvalidRoom,
+    InvalidSchedule,
+    InvalidShareLink,
+    InvalidSignature,
+    InvalidWebhook,
+    JsonDeserializationError,
+    JsonValidityError,
+    MissingJsonContentType,
+    MissingSignature,
+    NotAcceptable,
+    OidcNotConfigured,
+    PayloadTooLarge,
+    ProgressIsComputed,
+    RangeExceededError,
+    RequestTimeout,
+    ResumeSessionExpired,
+    RoomNotFound,
+    RouteNotFound,
+    ScheduledEventNotFound,
+    SchemaViolation,
+    ServerDraining,
+    ServiceOverloaded,
+    ShareLinkNotFound,
+    SignatureExpired,
+    SignatureReplayed,
+    StageNotFound,
+    StepTooLarge,
+    SubscriberQuotaExceeded,
+    TenantForbidden,
+    TimestampInFuture,
+    Unauthorized,
+    UnknownError,
+    UnknownField,
+    UnknownStage,
+    VersionConflict,
+    WebhookNotFound,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::AddressForbidden => return "ADDRESS_FORBIDDEN",
+            ErrorCode::ApiKeyNotFound => return "API_KEY_NOT_FOUND",
+            ErrorCode::ApplicationArchived => return "APPLICATION_ARCHIVED",
+            ErrorCode::ApplicationNotFound => return "APPLICATION_NOT_FOUND",
+            ErrorCode::ApplicationQuotaExceeded => return "APPLICATION_QUOTA_EXCEEDED",
+            ErrorCode::BufferError => return "BUFFER_ERROR",
+            ErrorCode::ChecklistNotConfigured => return "CHECKLIST_NOT_CONFIGURED",
+            ErrorCode::ConfigReloadFailed => return "CONFIG_RELOAD_FAILED",
+            ErrorCode::EmptyScopesError => return "EMPTY_SCOPES_ERROR",
+            ErrorCode::EventAlreadyRetracted => return "EVENT_ALREADY_RETRACTED",
+            ErrorCode::EventNotFound => return "EVENT_NOT_FOUND",
+            ErrorCode::EventQuotaExceeded => return "EVENT_QUOTA_EXCEEDED",
+            ErrorCode::EventThrottled => return "EVENT_THROTTLED",
+            ErrorCode::Forbidden => return "FORBIDDEN",
+            ErrorCode::IdentityProviderError => return "IDENTITY_PROVIDER_ERROR",
+            ErrorCode::InvalidAppointment => return "INVALID_APPOINTMENT",
+            ErrorCode::InvalidCapacity => return "INVALID_CAPACITY",
+            ErrorCode::InvalidCsrfToken => return "INVALID_CSRF_TOKEN",
+            ErrorCode::InvalidDocument => return "INVALID_DOCUMENT",
+            ErrorCode::InvalidImport => return "INVALID_IMPORT",
+            ErrorCode::InvalidLogFilter => return "INVALID_LOG_FILTER",
+            ErrorCode::InvalidNote => return "INVALID_NOTE",
+            ErrorCode::InvalidPathParameter => return "INVALID_PATH_PARAMETER",
+            ErrorCode::InvalidQueryParameter => return "INVALID_QUERY_PARAMETER",
+            ErrorCode::InvalidRoom => return "INVALID_ROOM",
+            ErrorCode::InvalidSchedule => return "INVALID_SCHEDULE",
+            ErrorCode::InvalidShareLink => return "INVALID_SHARE_LINK",
+            ErrorCode::InvalidSignature => return "INVALID_SIGNATURE",
+            ErrorCode::InvalidWebhook => return "INVALID_WEBHOOK",
+            ErrorCode::JsonDeserializationError => return "JSON_DESERIALIZATION_ERROR",
+            ErrorCode::JsonValidityError => return "JSON_VALIDITY_ERROR",
+            ErrorCode::MissingJsonContentType => return "MISSING_JSON_CONTENT_TYPE",
+            ErrorCode::MissingSignature => return "MISSING_SIGNATURE",
+            ErrorCode::NotAcceptable => return "NOT_ACCEPTABLE",
+            ErrorCode::OidcNotConfigured => return "OIDC_NOT_CONFIGURED",
+            ErrorCode::PayloadTooLarge => return "PAYLOAD_TOO_LARGE",
+            ErrorCode::ProgressIsComputed => return "PROGRESS_IS_COMPUTED",
+            ErrorCode::RangeExceededError => return "RANGE_EXCEEDED_ERROR",
+            ErrorCode::RequestTimeout => return "REQUEST_TIMEOUT",
+            ErrorCode::ResumeSessionExpired => return "RESUME_SESSION_EXPIRED",
+            ErrorCode::RoomNotFound => return "ROOM_NOT_FOUND",
+            ErrorCode::RouteNotFound => return "ROUTE_NOT_FOUND",
+            ErrorCode::ScheduledEventNotFound => return "SCHEDULED_EVENT_NOT_FOUND",
+            ErrorCode::SchemaViolation => return "SCHEMA_VIOLATION",
+            ErrorCode::ServerDraining => return "SERVER_DRAINING",
+            ErrorCode::ServiceOverloaded => return "SERVICE_OVERLOADED",
+            ErrorCode::ShareLinkNotFound => return "SHARE_LINK_NOT_FOUND",
+            ErrorCode::SignatureExpired => return "SIGNATURE_EXPIRED",
+            ErrorCode::SignatureReplayed => return "SIGNATURE_REPLAYED",
+            ErrorCode::StageNotFound => return "STAGE_NOT_FOUND",
+            ErrorCode::StepTooLarge => return "STEP_TOO_LARGE",
+            ErrorCode::SubscriberQuotaExceeded => return "SUBSCRIBER_QUOTA_EXCEEDED",
+            ErrorCode::TenantForbidden => return "TENANT_FORBIDDEN",
+            ErrorCode::TimestampInFuture => return "TIMESTAMP_IN_FUTURE",
+            ErrorCode::Unauthorized => return "UNAUTHORIZED",
+            ErrorCode::UnknownError => return "UNKNOWN_ERROR",
+            ErrorCode::UnknownField => return "UNKNOWN_FIELD",
+            ErrorCode::UnknownStage => return "UNKNOWN_STAGE",
+            ErrorCode::VersionConflict => return "VERSION_CONFLICT",
+            ErrorCode::WebhookNotFound => return "WEBHOOK_NOT_FOUND",
+        }
+    }
+
+    /// Status used whenever the code is returned.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::AddressForbidden => return StatusCode::FORBIDDEN,
+            ErrorCode::ApiKeyNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::ApplicationArchived => return StatusCode::GONE,
+            ErrorCode::ApplicationNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::ApplicationQuotaExceeded => return StatusCode::FORBIDDEN,
+            ErrorCode::BufferError => return StatusCode::BAD_REQUEST,
+            ErrorCode::ChecklistNotConfigured => return StatusCode::CONFLICT,
+            ErrorCode::ConfigReloadFailed => return StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::EmptyScopesError => return StatusCode::BAD_REQUEST,
+            ErrorCode::EventAlreadyRetracted => return StatusCode::CONFLICT,
+            ErrorCode::EventNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::EventQuotaExceeded => return StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::EventThrottled => return StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Forbidden => return StatusCode::FORBIDDEN,
+            ErrorCode::IdentityProviderError => return StatusCode::BAD_GATEWAY,
+            ErrorCode::InvalidAppointment => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidCapacity => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidCsrfToken => return StatusCode::FORBIDDEN,
+            ErrorCode::InvalidDocument => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidImport => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidLogFilter => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidNote => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidPathParameter => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidQueryParameter => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidRoom => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidSchedule => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidShareLink => return StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidSignature => return StatusCode::UNAUTHORIZED,
+            ErrorCode::InvalidWebhook => return StatusCode::BAD_REQUEST,
+            ErrorCode::JsonDeserializationError => return StatusCode::BAD_REQUEST,
+            ErrorCode::JsonValidityError => return StatusCode::BAD_REQUEST,
+            ErrorCode::MissingJsonContentType => return StatusCode::BAD_REQUEST,
+            ErrorCode::MissingSignature => return StatusCode::UNAUTHORIZED,
+            ErrorCode::NotAcceptable => return StatusCode::NOT_ACCEPTABLE,
+            ErrorCode::OidcNotConfigured => return StatusCode::NOT_FOUND,
+            ErrorCode::PayloadTooLarge => return StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::ProgressIsComputed => return StatusCode::CONFLICT,
+            ErrorCode::RangeExceededError => return StatusCode::BAD_REQUEST,
+            ErrorCode::RequestTimeout => return StatusCode::REQUEST_TIMEOUT,
+            ErrorCode::ResumeSessionExpired => return StatusCode::GONE,
+            ErrorCode::RoomNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::RouteNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::ScheduledEventNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::SchemaViolation => return StatusCode::BAD_REQUEST,
+            ErrorCode::ServerDraining => return StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::ServiceOverloaded => return StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::ShareLinkNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::SignatureExpired => return StatusCode::UNAUTHORIZED,
+            ErrorCode::SignatureReplayed => return StatusCode::UNAUTHORIZED,
+            ErrorCode::StageNotFound => return StatusCode::NOT_FOUND,
+            ErrorCode::StepTooLarge => return StatusCode::BAD_REQUEST,
+            ErrorCode::SubscriberQuotaExceeded => return StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::TenantForbidden => return StatusCode::FORBIDDEN,
+            ErrorCode::TimestampInFuture => return StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => return StatusCode::UNAUTHORIZED,
+            ErrorCode::UnknownError => return StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::UnknownField => return StatusCode::BAD_REQUEST,
+            ErrorCode::UnknownStage => return StatusCode::BAD_REQUEST,
+            ErrorCode::VersionConflict => return StatusCode::CONFLICT,
+            ErrorCode::WebhookNotFound => return StatusCode::NOT_FOUND,
+        }
+    }
+
+    /// English message for when nothing more specific is known.
+    pub fn default_message(&self) -> &'static str {
+        match self {
+            ErrorCode::AddressForbidden => return "Requests from this address are not allowed",
+            ErrorCode::ApiKeyNotFound => return "API key does not exist",
+            ErrorCode::ApplicationArchived => {
+                return "Application is archived and no longer accepts events";
+            }
+            ErrorCode::ApplicationNotFound => return "Application does not exist",
+            ErrorCode::ApplicationQuotaExceeded => {
+                return "Tenant has reached its limit of active applications";
+            }
+            ErrorCode::BufferError => return "Request body could not be read",
+            ErrorCode::ChecklistNotConfigured => return "No stage checklist is configured",
+            ErrorCode::ConfigReloadFailed => return "The configuration file could not be reloaded",
+            ErrorCode::EmptyScopesError => return "An API key needs at least one scope",
+            ErrorCode::EventAlreadyRetracted => return "Event was already retracted",
+            ErrorCode::EventNotFound => return "Event not found",
+            ErrorCode::EventQuotaExceeded => {
+                return "Application has reached its daily event limit";
+            }
+            ErrorCode::EventThrottled => {
+                return "Events for this application are arriving too quickly";
+            }
+            ErrorCode::Forbidden => return "This action is not allowed for your role",
+            ErrorCode::IdentityProviderError => {
+                return "The identity provider could not be reached, please retry later";
+            }
+            ErrorCode::InvalidAppointment => return "Invalid appointment times",
+            ErrorCode::InvalidCapacity => return "Queue capacity must be at least 1",
+            ErrorCode::InvalidCsrfToken => return "CSRF token is missing or invalid",
+            ErrorCode::InvalidDocument => return "Document name must not be empty",
+            ErrorCode::InvalidImport => return "Invalid import file",
+            ErrorCode::InvalidLogFilter => return "Invalid log filter",
+            ErrorCode::InvalidNote => return "Invalid note length",
+            ErrorCode::InvalidPathParameter => return "Invalid path parameter",
+            ErrorCode::InvalidQueryParameter => return "Invalid query parameter",
+            ErrorCode::InvalidRoom => return "Invalid room",
+            ErrorCode::InvalidSchedule => return "publish_at must be in the future",
+            ErrorCode::InvalidShareLink => return "Invalid share link lifetime",
+            ErrorCode::InvalidSignature => return "Signature does not match the body",
+            ErrorCode::InvalidWebhook => return "Webhook registration is invalid",
+            ErrorCode::JsonDeserializationError => {
+                return "JSON body does not have the expected shape";
+            }
+            ErrorCode::JsonValidityError => return "Request body is not valid JSON",
+            ErrorCode::MissingJsonContentType => return "Content-Type must be application/json",
+            ErrorCode::MissingSignature => return "Missing signature",
+            ErrorCode::NotAcceptable => {
+                return "None of the requested media types can be produced";
+            }
+            ErrorCode::OidcNotConfigured => {
+                return "Sign-in with an identity provider is not set up";
+            }
+            ErrorCode::PayloadTooLarge => return "Request body exceeds the configured size limit",
+            ErrorCode::ProgressIsComputed => {
+                return "Application progress is derived from its stage checklist";
+            }
+            ErrorCode::RangeExceededError => return "Percentage must be within 0-100",
+            ErrorCode::RequestTimeout => return "Request took too long to process",
+            ErrorCode::ResumeSessionExpired => {
+                return "Resume token is unknown or has expired; subscribe afresh";
+            }
+            ErrorCode::RoomNotFound => return "Room not found",
+            ErrorCode::RouteNotFound => return "No such endpoint",
+            ErrorCode::ScheduledEventNotFound => return "Scheduled event not found",
+            ErrorCode::SchemaViolation => {
+                return "Event payload does not match the configured schema";
+            }
+            ErrorCode::ServerDraining => {
+                return "Server is draining connections; reconnect through the load balancer";
+            }
+            ErrorCode::ServiceOverloaded => return "Server is overloaded, please retry later",
+            ErrorCode::ShareLinkNotFound => return "Tracking link is unknown or has expired",
+            ErrorCode::SignatureExpired => {
+                return "Signature timestamp is outside the allowed window";
+            }
+            ErrorCode::SignatureReplayed => return "This signature was already used",
+            ErrorCode::StageNotFound => return "Stage is not part of the checklist",
+            ErrorCode::StepTooLarge => {
+                return "Progress changed by more than the allowed step; confirm the jump to accept it";
+            }
+            ErrorCode::SubscriberQuotaExceeded => {
+                return "Application has reached its limit of subscribers";
+            }
+            ErrorCode::TenantForbidden => return "This action is not available to your tenant",
+            ErrorCode::TimestampInFuture => return "Timestamp is too far in the future",
+            ErrorCode::Unauthorized => return "Missing or invalid access token",
+            ErrorCode::UnknownError => return "An unexpected error occured",
+            ErrorCode::UnknownField => return "Request body contains unknown fields",
+            ErrorCode::UnknownStage => return "The stage does not exist for this visa type",
+            ErrorCode::VersionConflict => {
+                return "The resource was changed by someone else; reload it and retry";
+            }
+            ErrorCode::WebhookNotFound => return "Webhook not found",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ErrorDetail {
+    code: ErrorCode,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    violations: Vec<Violation>,
+}
+
+/// One failed rule of a payload, located by its JSON Pointer (RFC 6901).
+#[derive(Serialize, Debug)]
+pub struct Violation {
+    pub pointer: String,
+    pub message: String,
+}
+
+/// The fields of the JSON object `value` (found at the pointer `at`) that are not among
+/// `known`, each with the closest known name when one is near enough to be a typo.
+pub fn unknown_fields(value: &Value, at: &str, known: &[&str]) -> Vec<Violation> {
+    let Value::Object(fields) = value else {
+        return Vec::new();
+    };
+    return fields
+        .keys()
+        .filter(|field| !known.contains(&field.as_str()))
+        .map(|field| {
+            let message = match closest(field, known) {
+                Some(suggestion) => {
+                    format!("unknown field `{}`, did you mean `{}`?", field, suggestion)
+                }
+                None => format!(
+                    "unknown field `{}`, expected one of {}",
+                    field,
+                    known.join(", ")
+                ),
+            };
+            return Violation {
+                pointer: format!("{}/{}", at, field.replace('~', "~0").replace('/', "~1")),
+                message,
+            };
+        })
+        .collect();
+}
+
+/// The known name fewest edits away from `field`, if within a third of its length.
+fn closest<'a>(field: &str, known: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (field.chars().count() / 3).max(1);
+    return known
+        .iter()
+        .map(|candidate| (edit_distance(field, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate);
+}
+
+/// Levenshtein distance, counting a transposition of neighbours as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    return rows[a.len()][b.len()];
+}
+
+impl ErrorDetail {
+    /// `message` is the English text; other languages use the catalog entry for `code`.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        let message = match i18n::lookup(i18n::current(), code.as_str()) {
+            Some(localized) => localized.to_string(),
+            None => message.into(),
+        };
+        return Self {
+            code,
+            message,
+            violations: Vec::new(),
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct AppError {
+    error: ErrorDetail,
+    status_code: StatusCode,
+    /// Sent as `Retry-After` when the client may try again later.
+    retry_after: Option<u64>,
+    /// Sent as `ETag`, e.g. the current version after a failed `If-Match`.
+    etag: Option<String>,
+}
+
+impl AppError {
+    /// An error with the code's default status and a message specific to this occurrence.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        return Self {
+            error: ErrorDetail::new(code, message),
+            status_code: code.status(),
+            retry_after: None,
+            etag: None,
+        };
+    }
+
+    pub fn retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        return self;
+    }
+
+    pub fn etag(mut self, etag: String) -> Self {
+        self.etag = Some(etag);
+        return self;
+    }
+
+    /// Lists what exactly was wrong with the payload.
+    pub fn violations(mut self, violations: Vec<Violation>) -> Self {
+        self.error.violations = violations;
+        return self;
+    }
+
+    /// Refuses a body with the fields found by [`unknown_fields`]; `Ok` when there are none.
+    pub fn check_fields(violations: Vec<Violation>) -> Result<(), AppError> {
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let fields: Vec<&str> = violations
+            .iter()
+            .map(|violation| violation.pointer.as_str())
+            .collect();
+        let message = format!("Unknown fields: {}", fields.join(", "));
+        return Err(AppError::new(ErrorCode::UnknownField, message).violations(violations));
+    }
+
+    /// A body that is valid JSON but not of the expected shape, worded like the rejection
+    /// of axum's `Json` extractor.
+    pub fn invalid_body(err: impl std::fmt::Display) -> Self {
+        return AppError::new(
+            ErrorCode::JsonDeserializationError,
+            format!(
+                "Failed to deserialize the JSON body into the target type: {}",
+                err
+            ),
+        );
+    }
+
+    /// The code and message, for reporting one failed item among several.
+    pub fn into_detail(self) -> ErrorDetail {
+        return self.error;
+    }
+
+    pub fn payload_too_large() -> Self {
+        return AppError::from(ErrorCode::PayloadTooLarge);
+    }
+}
+
+impl From<ErrorCode> for AppError {
+    fn from(code: ErrorCode) -> Self {
+        return AppError::new(code, code.default_message());
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(value: JsonRejection) -> Self {
+        match value {
+            JsonRejection::MissingJsonContentType(missing_json_content_type) => AppError::new(
+                ErrorCode::MissingJsonContentType,
+                missing_json_content_type.to_string(),
+            ),
+            JsonRejection::JsonDataError(json_data_error) => AppError::new(
+                ErrorCode::JsonDeserializationError,
+                json_data_error.body_text(),
+            ),
+            JsonRejection::JsonSyntaxError(json_syntax_error) => {
+                AppError::new(ErrorCode::JsonValidityError, json_syntax_error.body_text())
+            }
+            JsonRejection::BytesRejection(bytes_rejection)
+                if bytes_rejection.status() == StatusCode::PAYLOAD_TOO_LARGE =>
+            {
+                AppError::payload_too_large()
+            }
+            JsonRejection::BytesRejection(bytes_rejection) => {
+                AppError::new(ErrorCode::BufferError, bytes_rejection.body_text())
+            }
+            _ => AppError::new(ErrorCode::UnknownError, "An unexpected error occured"),
+        }
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(value: PathRejection) -> Self {
+        return AppError::new(ErrorCode::InvalidPathParameter, value.body_text());
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(value: QueryRejection) -> Self {
+        return AppError::new(ErrorCode::InvalidQueryParameter, value.body_text());
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let response = EventResponse::<EventData> {
+            data: None,
+            error: Some(self.error),
+        };
+        let mut response = (self.status_code, Json(response)).into_response();
+        if let Some(secs) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        if let Some(etag) = self.etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        return response;
+    }
+}
+
+/// `RequestBodyLimitLayer` answers oversized bodies with a plain-text 413 before any
+/// extractor runs; rewrite those into the standard error envelope.
+pub async fn payload_too_large_envelope(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        return AppError::payload_too_large().into_response();
+    }
+    return response;
+}
+
+/// Turns errors raised by tower middleware (timeouts, load shedding) into the envelope.
+pub async fn handle_middleware_error(err: BoxError) -> AppError {
+    if err.is::<Elapsed>() {
+        return AppError::new(
+            ErrorCode::RequestTimeout,
+            "Request took too long to process",
+        );
+    }
+    if err.is::<Overloaded>() {
+        return AppError::new(
+            ErrorCode::ServiceOverloaded,
+            "Server is overloaded, please retry later",
+        );
+    }
+
+    tracing::error!("Unhandled middleware error: {}", err);
+    return AppError::new(ErrorCode::UnknownError, "An unexpected error occured");
+}
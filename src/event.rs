@@ -1,183 +1,1362 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    panic::AssertUnwindSafe,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use axum::{
     Json,
-    extract::{State, rejection::JsonRejection},
+    body::Body,
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Sse, sse::Event},
+    response::{
+        IntoResponse, Response, Sse,
+        sse::{Event, KeepAlive},
+    },
 };
 use axum_extra::{TypedHeader, extract::WithRejection};
-use futures_util::stream::Stream;
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, stream::Stream};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
+use serde_json::{Value, json};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    application,
+    auth::{Admin, Principal, Publisher, Viewer},
+    client_ip::ClientIp,
+    error::{AppError, ErrorCode, ErrorDetail, unknown_fields},
+    fanout::{Delivery, Disconnect},
+    history, i18n, outbox, quota,
+    resume::Session,
+    retraction::Retraction,
+    room,
+    state::AppState,
+    stats::{DisconnectReason, SubscriberStats},
+    tasks, telemetry, visa_type,
+};
+
+/// Version of the `{ "v", "type", "data" }` envelope events are wrapped in.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Most stored events a stream replays through `?backfill=` or `?since=`.
+const MAX_BACKFILL: usize = 1000;
+
+/// Envelope and SSE `event:` type of numeric progress events.
+const PROGRESS_TYPE: &str = "progress";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    Normal,
+    /// Decisions and other events that must not wait: exempt from send throttling and
+    /// subscriber overflow policies, and delivered ahead of anything already queued.
+    Critical,
+}
+
+impl Priority {
+    pub fn is_normal(&self) -> bool {
+        return *self == Priority::Normal;
+    }
+
+    /// Metric label value.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Priority::Normal => return "normal",
+            Priority::Critical => return "critical",
+        }
+    }
+}
+
+/// Who an event is for. Streams know their subscriber, so one broadcast serves every
+/// audience and each subscriber is only sent what it may see.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Everyone, including anonymous viewers such as public status pages.
+    #[default]
+    Public,
+    /// Identified viewers (the applicant) and staff.
+    Applicant,
+    /// Publishers and admins only, e.g. internal notes and provisional progress.
+    Staff,
+}
+
+impl Visibility {
+    pub fn is_public(&self) -> bool {
+        return *self == Visibility::Public;
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppEvent {
-    percentage: f64,
+    /// Events without an application are only seen by unfiltered subscribers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application_id: Option<Uuid>,
+    pub percentage: f64,
+    /// When the publisher says the change happened. Informational only; ordering always
+    /// follows the server's `timestamp`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurred_at: Option<DateTime<Utc>>,
+    /// For transient notices: seconds after acceptance at which the event stops being
+    /// replayed. Live subscribers still receive it when it is published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Priority::is_normal")]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "Visibility::is_public")]
+    pub visibility: Visibility,
+    /// The stage the case reached, e.g. `biometrics`. Must be one of the stages of the
+    /// application's visa type when a schema exists for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+}
+
+impl AppEvent {
+    /// Every field a publisher may send, for pointing out typos.
+    pub const FIELDS: &[&str] = &[
+        "application_id",
+        "percentage",
+        "occurred_at",
+        "ttl_secs",
+        "priority",
+        "visibility",
+        "stage",
+    ];
+}
+
+/// An accepted event as kept in the store's history.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredEvent {
+    pub id: u64,
+    /// Position in the application's sequence, shared with everything else broadcast for it.
+    #[serde(default)]
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    /// Subject of the principal that submitted the event, e.g. the officer who completed
+    /// a stage. Missing on events stored before attribution existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(flatten)]
+    pub event: AppEvent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retracted: Option<Retraction>,
+}
+
+/// What heartbeats need of the newest event of one visibility; see [`StoreData::progress`].
+///
+/// [`StoreData::progress`]: crate::store::StoreData::progress
+#[derive(Debug, Clone)]
+pub struct LatestProgress {
+    pub id: u64,
+    pub percentage: f64,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl LatestProgress {
+    pub fn of(stored: &StoredEvent) -> Self {
+        return Self {
+            id: stored.id,
+            percentage: stored.event.percentage,
+            expires_at: stored.expires_at(),
+        };
+    }
+}
+
+impl StoredEvent {
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        return self
+            .event
+            .ttl_secs
+            .map(|ttl| self.at + chrono::Duration::seconds(ttl as i64));
+    }
+
+    /// Expired events stay in the store but are left out of history and catch-up.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        return self
+            .expires_at()
+            .is_some_and(|expires_at| expires_at <= now);
+    }
+
+    /// Whether the event still counts towards its application's progress: neither expired
+    /// nor retracted.
+    pub fn is_current(&self, now: DateTime<Utc>) -> bool {
+        return !self.is_expired(now) && self.retracted.is_none();
+    }
+
+    /// The event as it was fanned out, for replaying it onto a stream.
+    fn to_broadcast(&self) -> Broadcast {
+        return Broadcast {
+            application_id: self.event.application_id,
+            tenant: None,
+            seq: self.seq,
+            at: self.at,
+            monotonic_ms: None,
+            event: None,
+            priority: self.event.priority,
+            visibility: self.event.visibility,
+            data: serde_json::to_value(&self.event).unwrap(),
+            ingested_at: Instant::now(),
+            frames: Frames::default(),
+        };
+    }
+}
+
+/// What the hub fans out to every interested subscriber.
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    pub application_id: Option<Uuid>,
+    /// Unfiltered subscribers of other tenants never see the event; `None` reaches operators only.
+    pub tenant: Option<String>,
+    /// Monotonic per application (events without one share a global sequence). Clients
+    /// that see a jump, or an `event: gap`, should backfill from the history API.
+    pub seq: u64,
+    /// Server time at which the event was accepted, sent as RFC 3339 `timestamp`.
+    pub at: DateTime<Utc>,
+    /// Milliseconds since server start, sent as `monotonic_ms` when enabled. Unlike
+    /// `timestamp` it never jumps with wall-clock adjustments.
+    pub monotonic_ms: Option<u64>,
+    /// SSE `event:` name; `None` marks a progress event.
+    pub event: Option<&'static str>,
+    pub priority: Priority,
+    pub visibility: Visibility,
+    pub data: Value,
+    /// When the server took the event in, for measuring how long it takes to reach each
+    /// stream. Restamped when a paused application resumes, so the pause is not counted.
+    pub ingested_at: Instant,
+    pub frames: Frames,
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    tx: broadcast::Sender<AppEvent>,
+/// A broadcast's SSE frames, encoded by the first subscriber that needs each shape and
+/// copied for every other one, so fanning out to thousands of streams serializes once.
+#[derive(Debug, Clone, Default)]
+pub struct Frames {
+    /// Indexed by `legacy` and `tagged`.
+    shapes: [OnceLock<Event>; 4],
 }
 
-impl AppState {
-    pub fn new() -> Self {
-        let (tx, _rx) = broadcast::channel(800);
-        return Self { tx: tx };
+impl Broadcast {
+    /// The event type, as matched by `?types=` and sent in the envelope.
+    pub fn kind(&self) -> &'static str {
+        return self.event.unwrap_or(PROGRESS_TYPE);
+    }
+
+    /// The versioned `{ "v", "type", "seq", "timestamp", "data" }` envelope. `tagged` adds
+    /// the `application_id`.
+    pub fn envelope(&self, tagged: bool) -> Value {
+        let mut envelope = json!({
+            "v": SCHEMA_VERSION,
+            "type": self.kind(),
+            "seq": self.seq,
+            "timestamp": self.at.to_rfc3339(),
+            "data": self.data,
+        });
+        if let Some(monotonic_ms) = self.monotonic_ms {
+            envelope["monotonic_ms"] = Value::from(monotonic_ms);
+        }
+        if tagged {
+            envelope["application_id"] = json!(self.application_id);
+        }
+        return envelope;
+    }
+
+    /// The frame for a subscriber, encoded only if no other subscriber needed it before.
+    fn to_sse(&self, legacy: bool, tagged: bool) -> Result<Event, axum::Error> {
+        let frame = &self.frames.shapes[usize::from(legacy) * 2 + usize::from(tagged)];
+        if let Some(event) = frame.get() {
+            return Ok(event.clone());
+        }
+        let event = self.encode(legacy, tagged)?;
+        return Ok(frame.get_or_init(|| event).clone());
+    }
+
+    /// Wraps the data in the envelope, or for `legacy` subscribers merges the metadata
+    /// into the bare data object as before.
+    fn encode(&self, legacy: bool, tagged: bool) -> Result<Event, axum::Error> {
+        let payload = if legacy {
+            let mut data = self.data.clone();
+            if let Value::Object(fields) = &mut data {
+                if tagged {
+                    fields.insert("application_id".to_string(), json!(self.application_id));
+                }
+                fields.insert("seq".to_string(), Value::from(self.seq));
+                fields.insert("timestamp".to_string(), Value::from(self.at.to_rfc3339()));
+                if let Some(monotonic_ms) = self.monotonic_ms {
+                    fields.insert("monotonic_ms".to_string(), Value::from(monotonic_ms));
+                }
+            }
+            data
+        } else {
+            self.envelope(tagged)
+        };
+        let event = Event::default().json_data(&payload)?;
+        // legacy frontends only listen for progress as the default `message`
+        match self.event {
+            None if legacy => return Ok(event),
+            _ => return Ok(event.event(self.kind())),
+        }
+    }
+}
+
+/// The versioned wrapper of a notice, with its fields in the order `json!` would write them.
+#[derive(Serialize)]
+struct NoticeEnvelope<T> {
+    data: T,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    v: u32,
+}
+
+/// Stream-level notices (`gap`, `too-slow`, `heartbeat`, `reconnect`, `error`) that are not part of any application's sequence.
+fn notice(name: &'static str, data: impl Serialize, legacy: bool) -> Result<Event, axum::Error> {
+    let event = Event::default().event(name);
+    if legacy {
+        return event.json_data(&data);
+    }
+    return event.json_data(NoticeEnvelope {
+        data,
+        kind: name,
+        v: SCHEMA_VERSION,
+    });
+}
+
+/// The subscription's last-known state: the latest unexpired percentage (`null` before
+/// any) and sequence number, so clients can tell a quiet stream from a stale one.
+fn heartbeat(
+    state: &AppState,
+    principal: &Principal,
+    filter: Option<Uuid>,
+    legacy: bool,
+) -> Result<Event, axum::Error> {
+    let now = Utc::now();
+    let (percentage, seq) = state.store.read(|data| {
+        let percentage =
+            data.latest_percentage(filter, now, |visibility| principal.can_see(visibility));
+        return (percentage, data.current_seq(filter));
+    });
+    return notice(
+        "heartbeat",
+        json!({
+            "application_id": filter,
+            "percentage": percentage,
+            "seq": seq,
+            "server_time": now.to_rfc3339(),
+        }),
+        legacy,
+    );
+}
+
+/// What woke a stream up.
+enum Wake {
+    Delivery(Result<Delivery, Disconnect>),
+    Heartbeat,
+    /// The stream reached `sse.max_connection_secs`.
+    Expired,
+    /// The subscriber's credentials expired.
+    AuthExpired,
+}
+
+/// Resolves at `deadline`, or never without one.
+async fn expiry(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending::<()>().await,
+    }
+}
+
+/// Completes on the next heartbeat tick, or never when heartbeats are disabled.
+async fn next_heartbeat(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending::<()>().await,
+    }
+}
+
+/// Body of `POST /events/send`: either `{ "v": 1, "type": "progress", "data": {...} }` or,
+/// while publishers migrate, the bare legacy event.
+#[derive(Debug)]
+pub struct SendRequest {
+    event: AppEvent,
+    /// Accepts a change beyond `events.max_step`. Not stored with the event.
+    confirm_large_jump: bool,
+    /// The event object as sent, for checking against `events.schema_path`.
+    raw: Value,
+}
+
+impl SendRequest {
+    /// Unknown fields are refused before anything else, so a typo is reported as such
+    /// rather than as the field it was meant to be going missing.
+    pub fn parse(value: Value) -> Result<Self, AppError> {
+        let known: Vec<&str> = AppEvent::FIELDS
+            .iter()
+            .copied()
+            .chain(["confirm_large_jump"])
+            .collect();
+        let Some(version) = value.get("v") else {
+            AppError::check_fields(unknown_fields(&value, "", &known))?;
+            return SendRequest::from_data(value);
+        };
+        let mut violations = unknown_fields(&value, "", &["v", "type", "data"]);
+        if let Some(data) = value.get("data") {
+            violations.extend(unknown_fields(data, "/data", &known));
+        }
+        AppError::check_fields(violations)?;
+        if *version != SCHEMA_VERSION {
+            return Err(AppError::invalid_body(format!(
+                "unsupported envelope version {}, expected {}",
+                version, SCHEMA_VERSION
+            )));
+        }
+        let kind = value
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or(PROGRESS_TYPE);
+        if kind != PROGRESS_TYPE {
+            return Err(AppError::invalid_body(format!(
+                "unsupported event type {}, only {} events can be sent",
+                kind, PROGRESS_TYPE
+            )));
+        }
+        let Some(data) = value.get("data").cloned() else {
+            return Err(AppError::invalid_body("missing field `data` in envelope"));
+        };
+        return SendRequest::from_data(data);
+    }
+
+    fn from_data(data: Value) -> Result<Self, AppError> {
+        let confirm_large_jump = match data.get("confirm_large_jump") {
+            None => false,
+            Some(confirm) => confirm.as_bool().ok_or_else(|| {
+                AppError::invalid_body("field `confirm_large_jump` must be a boolean")
+            })?,
+        };
+        let event = serde_json::from_value(data.clone()).map_err(AppError::invalid_body)?;
+        return Ok(SendRequest {
+            event,
+            confirm_large_jump,
+            raw: data,
+        });
     }
 }
 
 #[derive(Serialize, Debug)]
-pub struct EventResponse {
+pub struct EventResponse<T = EventData> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    data: Option<EventData>,
+    pub(crate) data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<ErrorDetail>,
+    pub(crate) error: Option<ErrorDetail>,
 }
 
-#[derive(Serialize, Debug)]
-pub struct EventData {
-    message: String,
+impl<T> EventResponse<T> {
+    pub fn ok(data: T) -> Self {
+        return Self {
+            data: Some(data),
+            error: None,
+        };
+    }
 }
 
 #[derive(Serialize, Debug)]
-pub struct ErrorDetail {
-    code: String,
+pub struct EventData {
     message: String,
 }
 
-#[derive(Debug)]
-pub struct AppError {
-    error: ErrorDetail,
-    status_code: StatusCode,
-}
-
-impl From<JsonRejection> for AppError {
-    fn from(value: JsonRejection) -> Self {
-        match value {
-            JsonRejection::MissingJsonContentType(missing_json_content_type) => AppError {
-                error: ErrorDetail {
-                    code: "MISSING_JSON_CONTENT_TYPE".to_string(),
-                    message: missing_json_content_type.to_string(),
-                },
-                status_code: StatusCode::BAD_REQUEST,
-            },
-            JsonRejection::JsonDataError(json_data_error) => AppError {
-                error: ErrorDetail {
-                    code: "JSON_DESERIALIZATION_ERROR".to_string(),
-                    message: json_data_error.body_text(),
-                },
-                status_code: StatusCode::BAD_REQUEST,
-            },
-            JsonRejection::JsonSyntaxError(json_syntax_error) => AppError {
-                error: ErrorDetail {
-                    code: "JSON_VALIDITY_ERROR".to_string(),
-                    message: json_syntax_error.body_text(),
-                },
-                status_code: StatusCode::BAD_REQUEST,
-            },
-            JsonRejection::BytesRejection(bytes_rejection) => AppError {
-                error: ErrorDetail {
-                    code: "BUFFER_ERROR".to_string(),
-                    message: bytes_rejection.body_text(),
-                },
-                status_code: StatusCode::BAD_REQUEST,
-            },
-            _ => AppError {
-                error: ErrorDetail {
-                    code: "UNKNOWN_ERROR".to_string(),
-                    message: "An unexpected error occured".to_string(),
-                },
-                status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            },
-        }
-    }
-}
-
-impl IntoResponse for AppError {
-    fn into_response(self) -> axum::response::Response {
-        let response = EventResponse {
+/// An error envelope with the status that belongs to `code`.
+fn rejected(code: ErrorCode, message: impl Into<String>) -> Response {
+    return (
+        code.status(),
+        Json(EventResponse::<EventData> {
             data: None,
-            error: Some(self.error),
-        };
-        return (self.status_code, Json(response)).into_response();
-    }
+            error: Some(ErrorDetail::new(code, message)),
+        }),
+    )
+        .into_response();
+}
+
+fn acknowledged(status: StatusCode, message: String) -> Response {
+    return (
+        status,
+        Json(EventResponse {
+            data: Some(EventData { message }),
+            error: None,
+        }),
+    )
+        .into_response();
 }
 
 #[axum::debug_handler]
 pub async fn send(
     State(state): State<Arc<AppState>>,
-    WithRejection(Json(payload), _): WithRejection<Json<AppEvent>, AppError>,
-) -> (StatusCode, Json<EventResponse>) {
+    Publisher(publisher): Publisher,
+    WithRejection(Json(body), _): WithRejection<Json<Value>, AppError>,
+) -> Response {
+    let request = match SendRequest::parse(body) {
+        Ok(request) => request,
+        Err(err) => return err.into_response(),
+    };
+    let SendRequest {
+        event: payload,
+        confirm_large_jump,
+        raw,
+    } = request;
+    tracing::debug!("Event submitted by {}", publisher.subject);
+    if let Some(schema) = &state.config().events.schema
+        && let Err(err) = schema.check(&raw)
+    {
+        return err.into_response();
+    }
     let percentage = payload.percentage;
-    if percentage < 0.0 || percentage > 100.0 {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(EventResponse {
-                data: None,
-                error: Some(ErrorDetail {
-                    code: "RANGE_EXCEEDED_ERROR".to_string(),
-                    message: format!(
-                        "Percentage range is exceeded. It should be within 0-100, but got {}",
-                        percentage
-                    )
-                    .to_string(),
-                }),
-            }),
+    let tenant = match admit(&state, &publisher, &payload) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+
+    let now = Utc::now();
+    let max_skew = chrono::Duration::seconds(state.config().events.max_future_skew_secs as i64);
+    if let Some(occurred_at) = payload.occurred_at
+        && occurred_at > now + max_skew
+    {
+        return rejected(
+            ErrorCode::TimestampInFuture,
+            format!(
+                "occurred_at {} is too far ahead of server time {}",
+                occurred_at.to_rfc3339(),
+                now.to_rfc3339()
+            ),
         );
     }
 
-    match state.tx.send(payload.clone()) {
-        Ok(num_receivers) => {
-            let response_msg = format!("Event sent to {} listeners!", num_receivers);
-            return (
-                StatusCode::OK,
-                Json(EventResponse {
-                    data: Some(EventData {
-                        message: response_msg,
-                    }),
-                    error: None,
-                }),
+    let previous = state.store.read(|data| {
+        data.events
+            .iter()
+            .rev()
+            .find(|stored| {
+                stored.event.application_id == payload.application_id && stored.retracted.is_none()
+            })
+            .map(|stored| (stored.at, stored.event.percentage))
+    });
+    if let Some(max_step) = state.config().events.max_step
+        && !confirm_large_jump
+    {
+        // a first event is measured from 0
+        let previous_percentage = previous.map(|(_, percentage)| percentage).unwrap_or(0.0);
+        if (percentage - previous_percentage).abs() > max_step {
+            return rejected(
+                ErrorCode::StepTooLarge,
+                format!(
+                    "Progress would move from {} to {}, more than the allowed {} points. Resend with confirm_large_jump set to accept it",
+                    previous_percentage, percentage, max_step
+                ),
             );
         }
-        Err(_) => {
-            let response_msg = "Event accepted, but no listeners".to_string();
-            return (
-                StatusCode::ACCEPTED,
-                Json(EventResponse {
-                    data: Some(EventData {
-                        message: response_msg,
-                    }),
-                    error: None,
-                }),
+    }
+
+    let flags = state.flags();
+    if let Some((previous_at, previous_percentage)) = previous {
+        if flags.dedup && previous_percentage == percentage {
+            return acknowledged(StatusCode::OK, i18n::text("event-duplicate", &[]));
+        }
+        let interval =
+            chrono::Duration::milliseconds(state.config().events.throttle_interval_ms as i64);
+        if flags.throttle && payload.priority != Priority::Critical && now - previous_at < interval
+        {
+            return rejected(
+                ErrorCode::EventThrottled,
+                format!(
+                    "The previous event was accepted {}ms ago; wait at least {}ms between events",
+                    (now - previous_at).num_milliseconds(),
+                    interval.num_milliseconds()
+                ),
             );
         }
     }
+    if flags.simulation {
+        return acknowledged(StatusCode::OK, i18n::text("event-simulated", &[]));
+    }
+
+    let application_id = payload.application_id;
+    match record(&state, payload, tenant, &publisher.subject, now) {
+        0 if application_id.is_some_and(|id| state.is_paused(id)) => {
+            return acknowledged(StatusCode::ACCEPTED, i18n::text("event-held", &[]));
+        }
+        0 => return acknowledged(StatusCode::ACCEPTED, i18n::text("event-accepted", &[])),
+        num_receivers => {
+            let response_msg = i18n::text("event-sent", &[("count", num_receivers.to_string())]);
+            return acknowledged(StatusCode::OK, response_msg);
+        }
+    }
+}
+
+/// Checks every event must pass however it is submitted: the percentage range, the
+/// checklist and the publisher's access to the application. Returns the event's tenant.
+pub fn admit(
+    state: &AppState,
+    publisher: &Principal,
+    event: &AppEvent,
+) -> Result<Option<String>, AppError> {
+    if !(0.0..=100.0).contains(&event.percentage) {
+        return Err(AppError::new(
+            ErrorCode::RangeExceededError,
+            format!(
+                "Percentage range is exceeded. It should be within 0-100, but got {}",
+                event.percentage
+            ),
+        ));
+    }
+    let Some(application_id) = event.application_id else {
+        return Ok(publisher.tenant.clone());
+    };
+    if !state.config().checklist.stages.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::ProgressIsComputed,
+            "Application progress is derived from its stage checklist. Complete stages via /applications/{id}/stages/{stage}/complete",
+        ));
+    }
+    let tenant = application::authorize_active(state, publisher, application_id)?;
+    quota::check_events(state, application_id)?;
+    if let Some(stage) = &event.stage {
+        visa_type::check_stage(state, application_id, stage)?;
+    }
+    return Ok(tenant);
+}
+
+/// Appends an accepted event to the history, attributed to `actor`, queues its webhook
+/// notifications and fans it out, returning how many subscribers it reached.
+pub fn record(
+    state: &AppState,
+    event: AppEvent,
+    tenant: Option<String>,
+    actor: &str,
+    at: DateTime<Utc>,
+) -> usize {
+    let monotonic_ms = state.monotonic_ms();
+    let broadcast = state.store.write(|data| {
+        let id = data.next_event_id();
+        let seq = data.next_seq(event.application_id);
+        let stored = StoredEvent {
+            id,
+            seq,
+            at,
+            actor: Some(actor.to_string()),
+            event: event.clone(),
+            retracted: None,
+        };
+        data.track_progress(&stored);
+        data.events.push(stored);
+        let broadcast = Broadcast {
+            application_id: event.application_id,
+            tenant,
+            seq,
+            at,
+            monotonic_ms,
+            event: None,
+            priority: event.priority,
+            visibility: event.visibility,
+            data: serde_json::to_value(&event).unwrap(),
+            ingested_at: Instant::now(),
+            frames: Frames::default(),
+        };
+        outbox::enqueue(data, &broadcast);
+        return broadcast;
+    });
+    return state.publish(broadcast);
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SubscribeQuery {
+    /// Only receive events of this application; everything is delivered when omitted.
+    application_id: Option<Uuid>,
+    /// `0` keeps the pre-envelope payload shape for frontends that have not migrated yet.
+    v: Option<u32>,
+    /// Comma-separated event types to receive, e.g. `progress,stage`; all types when
+    /// omitted. Stream notices such as `gap` and `heartbeat` are always delivered.
+    types: Option<String>,
+    /// Token from an earlier stream's `event: session`: restores its application, types and
+    /// payload shape, overriding the parameters above, and first replays the stored events
+    /// it missed.
+    resume: Option<String>,
+    /// Replay up to this many of the latest stored events before going live.
+    backfill: Option<usize>,
+    /// Replay the stored events accepted at or after this time before going live; with
+    /// `backfill`, only the latest of them.
+    since: Option<DateTime<Utc>>,
+}
+
+/// Parses a `types=progress,stage` list; `None` when the parameter is absent.
+fn parse_types(types: Option<&str>) -> Result<Option<HashSet<String>>, AppError> {
+    let Some(types) = types else {
+        return Ok(None);
+    };
+    let types: HashSet<String> = types
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(str::to_string)
+        .collect();
+    if types.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::InvalidQueryParameter,
+            "types must name at least one event type",
+        ));
+    }
+    return Ok(Some(types));
 }
 
 pub async fn subscribe(
     State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    TypedHeader(user_agent): TypedHeader<headers::UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    WithRejection(Query(query), _): WithRejection<Query<SubscribeQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let ttl = Duration::from_secs(state.config().sse.resume_ttl_secs);
+    let resumed = match query.resume.as_deref() {
+        Some(token) => match state.sessions.resume(token, ttl) {
+            Some(session) => Some((token.to_string(), session)),
+            None => return Err(AppError::from(ErrorCode::ResumeSessionExpired)),
+        },
+        None => None,
+    };
+    let filter = match &resumed {
+        Some((_, session)) => session.filter,
+        None => query.application_id,
+    };
+    if let Some(scoped) = viewer.application_id
+        && filter != Some(scoped)
+    {
+        return Err(AppError::new(
+            ErrorCode::Forbidden,
+            format!("This link only follows application {}", scoped),
+        ));
+    }
+    if let Some(application_id) = filter {
+        application::authorize_active(&state, &viewer, application_id)?;
+        quota::check_subscribers(&state, application_id)?;
+    }
+    let backfill = Backfill::from_query(query.backfill, query.since)?;
+    let (resumable, types, legacy) = match resumed {
+        Some((token, session)) => {
+            let (types, legacy) = (session.types.clone(), session.legacy);
+            let resumable = Resumable {
+                token,
+                session,
+                replay: true,
+            };
+            (resumable, types, legacy)
+        }
+        None => {
+            let types = parse_types(query.types.as_deref())?;
+            let legacy = query.v == Some(0);
+            let position = state.store.read(|data| data.current_seq(filter));
+            let (token, session) =
+                state
+                    .sessions
+                    .open(filter, types.clone(), legacy, position, ttl);
+            let resumable = Resumable {
+                token,
+                session,
+                replay: false,
+            };
+            (resumable, types, legacy)
+        }
+    };
+    return open_stream(
+        state,
+        Connection {
+            user_agent: user_agent.as_str().to_string(),
+            client_ip,
+            filter,
+            principal: viewer,
+            types,
+            legacy,
+            tagged: false,
+            route: "/events",
+            room: None,
+            resumable: Some(resumable),
+            backfill,
+        },
+    );
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DashboardQuery {
+    v: Option<u32>,
+    types: Option<String>,
+}
+
+/// `GET /events/all`: every application's events in one stream for staff dashboards, each
+/// tagged with its `application_id`. Tenant admins only see their own tenant's cases.
+pub async fn subscribe_all(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
     TypedHeader(user_agent): TypedHeader<headers::UserAgent>,
-) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
-    tracing::debug!("{} connected", user_agent.as_str());
+    ClientIp(client_ip): ClientIp,
+    WithRejection(Query(query), _): WithRejection<Query<DashboardQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let types = parse_types(query.types.as_deref())?;
+    return open_stream(
+        state,
+        Connection {
+            user_agent: user_agent.as_str().to_string(),
+            client_ip,
+            filter: None,
+            principal: admin,
+            types,
+            legacy: query.v == Some(0),
+            tagged: true,
+            route: "/events/all",
+            room: None,
+            resumable: None,
+            backfill: None,
+        },
+    );
+}
+
+/// `GET /rooms/{name}/events`: the events of every application in the room, each tagged
+/// with its `application_id`. Applications added to or removed from the room take effect
+/// on open streams right away.
+pub async fn subscribe_room(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    TypedHeader(user_agent): TypedHeader<headers::UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    WithRejection(Path(name), _): WithRejection<Path<String>, AppError>,
+    WithRejection(Query(query), _): WithRejection<Query<DashboardQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let room = room::find(&state, &viewer, &name)?;
+    let types = parse_types(query.types.as_deref())?;
+    return open_stream(
+        state,
+        Connection {
+            user_agent: user_agent.as_str().to_string(),
+            client_ip,
+            filter: None,
+            principal: viewer,
+            types,
+            legacy: query.v == Some(0),
+            tagged: true,
+            route: "/rooms/{name}/events",
+            room: Some(room.name),
+            resumable: None,
+            backfill: None,
+        },
+    );
+}
+
+/// Who is subscribing and how their stream is shaped.
+struct Connection {
+    user_agent: String,
+    client_ip: IpAddr,
+    filter: Option<Uuid>,
+    /// Decides which tenants' events, and which visibility levels, the stream receives.
+    principal: Principal,
+    types: Option<HashSet<String>>,
+    legacy: bool,
+    /// Add the `application_id` to every event, for streams spanning applications.
+    tagged: bool,
+    /// The stream's route, as labelled in the delivery latency histogram.
+    route: &'static str,
+    /// Narrows an unfiltered stream to the applications in this room.
+    room: Option<String>,
+    resumable: Option<Resumable>,
+    /// Ignored for resumed sessions, which replay what they missed instead.
+    backfill: Option<Backfill>,
+}
+
+/// The resume session a stream announces in its `event: session` and keeps current.
+struct Resumable {
+    token: String,
+    session: Session,
+    /// Replay the stored events after the session's position before going live.
+    replay: bool,
+}
+
+/// Stored events of the session's application after its position, as they were broadcast.
+/// Only progress events are kept in the history; clients backfill anything else from the
+/// sequence jump.
+fn missed_events(
+    state: &AppState,
+    principal: &Principal,
+    session: &Session,
+) -> Result<Vec<Broadcast>, AppError> {
+    let Some(application_id) = session.filter else {
+        return Ok(Vec::new());
+    };
+    if session
+        .types
+        .as_ref()
+        .is_some_and(|types| !types.contains(PROGRESS_TYPE))
+    {
+        return Ok(Vec::new());
+    }
+    let position = session.position();
+    let now = Utc::now();
+    let events = history::events_of(state, principal, application_id, None)?;
+    return Ok(events
+        .into_iter()
+        .filter(|stored| stored.seq > position && stored.is_current(now))
+        .map(|stored| stored.to_broadcast())
+        .collect());
+}
+
+/// Stored events a new stream starts with, from `?backfill=` and `?since=`.
+#[derive(Debug, Clone, Copy)]
+struct Backfill {
+    /// Only the latest this many.
+    limit: Option<usize>,
+    since: Option<DateTime<Utc>>,
+}
+
+impl Backfill {
+    fn from_query(
+        limit: Option<usize>,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Option<Self>, AppError> {
+        if limit.is_none() && since.is_none() {
+            return Ok(None);
+        }
+        if limit.is_some_and(|limit| limit > MAX_BACKFILL) {
+            return Err(AppError::new(
+                ErrorCode::InvalidQueryParameter,
+                format!("backfill must be at most {}", MAX_BACKFILL),
+            ));
+        }
+        return Ok(Some(Self { limit, since }));
+    }
+}
+
+/// The current stored events a subscriber may see, oldest first: those of `filter`, or of
+/// every application the principal has access to. At most [`MAX_BACKFILL`].
+fn backfill_events(
+    state: &AppState,
+    principal: &Principal,
+    filter: Option<Uuid>,
+    types: Option<&HashSet<String>>,
+    backfill: Backfill,
+) -> Vec<Broadcast> {
+    if types.is_some_and(|types| !types.contains(PROGRESS_TYPE)) {
+        return Vec::new();
+    }
+    let now = Utc::now();
+    let mut events: Vec<Broadcast> = state.store.read(|data| {
+        return data
+            .events
+            .iter()
+            .filter(|stored| filter.is_none() || stored.event.application_id == filter)
+            .filter(|stored| backfill.since.is_none_or(|since| stored.at >= since))
+            .filter(|stored| stored.is_current(now) && principal.can_see(stored.event.visibility))
+            .filter(|stored| {
+                // events without an application are the operators' own
+                let tenant = match stored.event.application_id {
+                    Some(id) => match data.applications.get(&id) {
+                        Some(application) => application.tenant.as_deref(),
+                        None => return false,
+                    },
+                    None => None,
+                };
+                return principal.can_access(tenant);
+            })
+            .map(StoredEvent::to_broadcast)
+            .collect();
+    });
+    let limit = backfill.limit.unwrap_or(MAX_BACKFILL).min(MAX_BACKFILL);
+    let skip = events.len().saturating_sub(limit);
+    events.drain(..skip);
+    return events;
+}
 
-    let mut rx = state.tx.subscribe();
+/// How many bytes a stream sends between updates of the process-wide bandwidth stats.
+const REPORT_BYTES: u64 = 16 * 1024;
+
+/// A subscriber stream's tracing span, which everything the stream logs is recorded in,
+/// and the totals it reports when the stream ends.
+struct ConnectionLog {
+    id: Uuid,
+    span: tracing::Span,
+    delivered: AtomicU64,
+    bytes: AtomicU64,
+    /// Bytes not yet added to the process-wide stats.
+    unreported: AtomicU64,
+    reason: Mutex<Option<DisconnectReason>>,
+    stats: Arc<SubscriberStats>,
+    application_id: Option<Uuid>,
+    /// Who the bytes are billed to: the followed application's tenant, or the subscriber's.
+    tenant: Option<String>,
+}
+
+impl ConnectionLog {
+    fn open(state: &AppState, connection: &Connection) -> Arc<Self> {
+        let id = Uuid::new_v4();
+        let tenant = match connection.filter {
+            Some(application_id) => state.store.read(|data| {
+                data.applications
+                    .get(&application_id)
+                    .and_then(|application| application.tenant.clone())
+            }),
+            None => connection.principal.tenant.clone(),
+        };
+        let span = tracing::info_span!(
+            "connection",
+            connection_id = %id,
+            user_agent = %connection.user_agent,
+            client_ip = %connection.client_ip,
+            route = connection.route,
+            application_id = connection.filter.map(tracing::field::display),
+            room = connection.room.as_deref(),
+            types = connection.types.as_ref().map(tracing::field::debug),
+            delivered = tracing::field::Empty,
+            bytes = tracing::field::Empty,
+            reason = tracing::field::Empty,
+        );
+        return Arc::new(Self {
+            id,
+            span,
+            delivered: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            unreported: AtomicU64::new(0),
+            reason: Mutex::new(None),
+            stats: state.stats.clone(),
+            application_id: connection.filter,
+            tenant,
+        });
+    }
+
+    fn record_bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        let unreported = self.unreported.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if unreported >= REPORT_BYTES {
+            self.report_bytes();
+        }
+    }
+
+    fn report_bytes(&self) {
+        let bytes = self.unreported.swap(0, Ordering::Relaxed);
+        if bytes > 0 {
+            self.stats
+                .record_bytes(self.application_id, self.tenant.as_deref(), bytes);
+        }
+    }
+
+    fn record_delivery(&self) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Why the server ends the stream; without one it was the client that went away.
+    fn close(&self, reason: DisconnectReason) {
+        self.reason.lock().unwrap().get_or_insert(reason);
+    }
+}
+
+impl Drop for ConnectionLog {
+    fn drop(&mut self) {
+        let reason = self
+            .reason
+            .get_mut()
+            .unwrap()
+            .unwrap_or(DisconnectReason::ClientClosed);
+        self.span
+            .record("delivered", self.delivered.load(Ordering::Relaxed))
+            .record("bytes", self.bytes.load(Ordering::Relaxed))
+            .record("reason", reason.label());
+        self.span.in_scope(|| tracing::debug!("Disconnected"));
+        self.report_bytes();
+        self.stats.record_disconnect(reason);
+    }
+}
+
+/// Counts the bytes of the encoded stream as they are handed to the connection.
+fn counted(response: Response, log: Arc<ConnectionLog>) -> Response {
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            log.record_bytes(chunk.len() as u64);
+        }
+    });
+    return Response::from_parts(parts, Body::from_stream(body));
+}
+
+/// Closes a subscriber stream whose body panicked or failed to encode an event with a
+/// final `error` notice, so the client learns why it was dropped and can quote the
+/// connection ID that the server logged. Runs the body in the connection's span.
+fn guarded(
+    stream: impl Stream<Item = Result<Event, axum::Error>> + Send,
+    log: Arc<ConnectionLog>,
+    legacy: bool,
+    locale: i18n::Locale,
+) -> impl Stream<Item = Result<Event, axum::Error>> + Send {
+    return async_stream::stream! {
+        let connection_id = log.id;
+        let mut inner = std::pin::pin!(AssertUnwindSafe(stream).catch_unwind());
+        while let Some(item) = inner.next().instrument(log.span.clone()).await {
+            let failure = match item {
+                Ok(Ok(event)) => {
+                    yield Ok(event);
+                    continue;
+                }
+                Ok(Err(err)) => err.to_string(),
+                Err(payload) => format!("panicked: {}", tasks::panic_message(payload)),
+            };
+            log.close(DisconnectReason::Error);
+            // surfaces in Sentry through the tracing integration
+            log.span.in_scope(|| tracing::error!("Subscriber stream failed: {}", failure));
+            metrics::counter!("sse_stream_errors_total").increment(1);
+            yield notice("error", json!({
+                "code": ErrorCode::UnknownError,
+                "message": i18n::text_in(locale, "stream-error", &[("connection_id", connection_id.to_string())]),
+                "connection_id": connection_id,
+            }), legacy);
+            break;
+        }
+    };
+}
+
+fn open_stream(state: Arc<AppState>, connection: Connection) -> Result<Response, AppError> {
+    if state.drain.is_draining() {
+        return Err(AppError::from(ErrorCode::ServerDraining)
+            .retry_after(state.config().drain.retry_after_secs));
+    }
+    let filter = connection.filter;
+    let mut subscription = state.hub.subscribe(
+        filter,
+        connection.room.clone(),
+        connection.principal.clone(),
+        connection.types.clone(),
+    );
+    // read once subscribed, so nothing published meanwhile falls between replay and live
+    let replay = match (&connection.resumable, connection.backfill) {
+        (Some(resumable), _) if resumable.replay => {
+            missed_events(&state, &connection.principal, &resumable.session)?
+        }
+        (_, Some(backfill)) => backfill_events(
+            &state,
+            &connection.principal,
+            filter,
+            connection.types.as_ref(),
+            backfill,
+        ),
+        _ => Vec::new(),
+    };
+
+    // only admitted streams are logged and counted
+    let log = ConnectionLog::open(&state, &connection);
+    let Connection {
+        user_agent,
+        client_ip,
+        filter,
+        principal,
+        types: _,
+        legacy,
+        tagged,
+        route,
+        room: _,
+        resumable,
+        backfill: _,
+    } = connection;
+    log.span.in_scope(|| tracing::debug!("Connected"));
+    sentry::configure_scope(|scope| {
+        scope.set_tag("user_agent", &user_agent);
+        if let Some(application_id) = filter {
+            scope.set_tag("application_id", application_id);
+        }
+    });
+    let stats = state.stats.clone();
+    #[cfg(feature = "chaos")]
+    let chaos = state.chaos.clone();
+    let connection = stats.connect(&user_agent, client_ip, filter, log.tenant.clone());
+
+    // the stream is polled after the request scope has ended
+    let locale = i18n::current();
+    let mut heartbeat_interval = state.config().sse.heartbeat_secs.map(|secs| {
+        let period = Duration::from_secs(secs.max(1));
+        return tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    });
+    let expires_at = state
+        .config()
+        .sse
+        .max_connection_secs
+        .map(|secs| tokio::time::Instant::now() + Duration::from_secs(secs.max(1)));
+    let auth_expires_at = principal
+        .expires_at
+        .map(|at| tokio::time::Instant::now() + (at - Utc::now()).to_std().unwrap_or_default());
+    let missed = if resumable.as_ref().is_some_and(|resumable| resumable.replay) {
+        replay.len()
+    } else {
+        0
+    };
+    let app_state = state.clone();
+    let stream_log = log.clone();
 
     let stream = async_stream::stream! {
+        let log = stream_log;
+        let _connection = connection;
+        let session = resumable.as_ref().map(|resumable| resumable.session.clone());
+        let _attached = session.as_ref().map(Session::attach);
+        if let Some(resumable) = &resumable {
+            yield Ok(notice("session", json!({
+                "resume_token": resumable.token,
+                "resumed": resumable.replay,
+                "replayed": missed,
+            }), legacy)?);
+        }
+        // live events the replay already covered are skipped
+        let mut replayed_up_to: HashMap<Option<Uuid>, u64> = HashMap::new();
+        for msg in replay {
+            yield Ok(msg.to_sse(legacy, tagged)?);
+            log.record_delivery();
+            replayed_up_to.insert(msg.application_id, msg.seq);
+            if let Some(session) = session.as_ref().filter(|_| msg.application_id == filter) {
+                session.advance(msg.seq);
+            }
+        }
         loop {
-            match rx.recv().await {
-                Ok(msg) => {
-                    let event = Event::default().json_data(msg)?;
-                    yield Ok(event);
+            let next = tokio::select! {
+                delivery = subscription.recv() => Wake::Delivery(delivery),
+                _ = next_heartbeat(&mut heartbeat_interval) => Wake::Heartbeat,
+                _ = expiry(expires_at) => Wake::Expired,
+                _ = expiry(auth_expires_at) => Wake::AuthExpired,
+            };
+            let delivery = match next {
+                Wake::Delivery(delivery) => delivery,
+                Wake::Heartbeat => {
+                    yield Ok(heartbeat(&app_state, &principal, filter, legacy)?);
+                    continue;
+                }
+                Wake::Expired => {
+                    // spread the reconnects of streams opened together over the jitter window
+                    let jitter = app_state.config().sse.reconnect_jitter_ms;
+                    let retry = Duration::from_millis(rand::rng().random_range(0..=jitter));
+                    log.close(DisconnectReason::MaxAge);
+                    yield Ok(notice("reconnect", json!({
+                        "reason": "max-age",
+                        "url": None::<String>,
+                        "retry_ms": retry.as_millis() as u64,
+                        "message": i18n::text_in(locale, "stream-expired", &[]),
+                    }), legacy)?.retry(retry));
+                    break;
+                }
+                Wake::AuthExpired => {
+                    log.close(DisconnectReason::AuthExpired);
+                    yield Ok(notice("reconnect", json!({
+                        "reason": "auth-expired",
+                        "url": None::<String>,
+                        "retry_ms": 0,
+                        "message": i18n::text_in(locale, "stream-auth-expired", &[]),
+                    }), legacy)?);
+                    break;
+                }
+            };
+            #[cfg(feature = "chaos")]
+            if let Some(delay) = chaos.delay() {
+                tokio::time::sleep(delay).await;
+            }
+            match delivery {
+                Ok(Delivery::Event(msg)) => {
+                    if replayed_up_to
+                        .get(&msg.application_id)
+                        .is_some_and(|seq| msg.seq <= *seq)
+                    {
+                        continue;
+                    }
+                    let positioned = filter.is_some() && msg.application_id == filter;
+                    stats.record_delivery();
+                    match msg.to_sse(legacy, tagged) {
+                        Ok(event) => {
+                            metrics::histogram!(
+                                telemetry::DELIVERY_LATENCY,
+                                "route" => route,
+                                "priority" => msg.priority.label()
+                            )
+                            .record(msg.ingested_at.elapsed().as_secs_f64());
+                            yield Ok(event);
+                            log.record_delivery();
+                            if let Some(session) = session.as_ref().filter(|_| positioned) {
+                                session.advance(msg.seq);
+                            }
+                        }
+                        Err(err) => {
+                            // surfaces in Sentry through the tracing integration
+                            tracing::error!(
+                                application_id = ?msg.application_id,
+                                seq = msg.seq,
+                                "Failed to encode broadcast: {}",
+                                err
+                            );
+                            log.close(DisconnectReason::Error);
+                            break;
+                        }
+                    }
+                }
+                Ok(Delivery::Gap { missed }) => {
+                    yield Ok(notice("gap", json!({
+                        "missed": missed,
+                        "message": i18n::text_in(locale, "stream-gap", &[]),
+                    }), legacy)?);
+                }
+                Err(Disconnect::TooSlow { lag }) => {
+                    tracing::debug!("Fell {} events behind", lag);
+                    log.close(DisconnectReason::Lag);
+                    let history_url = filter
+                        .map(|id| format!("/api/v1/applications/{}/history/export?format=json", id));
+                    yield Ok(notice("too-slow", json!({
+                        "lag": lag,
+                        "message": i18n::text_in(locale, "stream-too-slow", &[]),
+                        "history_url": history_url,
+                    }), legacy)?);
+                    break;
+                }
+                Ok(Delivery::Reload) => {
+                    yield Ok(notice("reload", json!({}), legacy)?);
+                }
+                Ok(Delivery::Announcement(data)) => {
+                    yield Ok(notice("announcement", &*data, legacy)?);
+                }
+                #[cfg(feature = "chaos")]
+                Ok(Delivery::Malformed) => {
+                    yield Ok(Event::default().data(crate::chaos::MALFORMED_DATA));
+                }
+                #[cfg(feature = "chaos")]
+                Err(Disconnect::Chaos) => {
+                    log.close(DisconnectReason::Chaos);
+                    break;
+                }
+                Err(Disconnect::Drain) => {
+                    log.close(DisconnectReason::Drain);
+                    let drain = &app_state.config().drain;
+                    let retry = Duration::from_secs(drain.retry_after_secs);
+                    yield Ok(notice("reconnect", json!({
+                        "reason": "drain",
+                        "url": drain.reconnect_url,
+                        "retry_ms": retry.as_millis() as u64,
+                        "message": i18n::text_in(locale, "stream-reconnect", &[]),
+                    }), legacy)?.retry(retry));
+                    break;
+                }
+                Err(Disconnect::Archived) => {
+                    log.close(DisconnectReason::Archived);
+                    break;
                 }
-                Err(err) => {
-                    tracing::error!("Error: {}", err);
+                Err(Disconnect::Overflow) => {
+                    log.close(DisconnectReason::Lag);
                     break;
                 }
             }
         }
     };
 
-    return Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default());
+    let stream = guarded(stream, log.clone(), legacy, locale);
+    let keep_alive = Duration::from_secs(state.config().sse.keep_alive_secs);
+    let response = Sse::new(stream)
+        .keep_alive(KeepAlive::new().interval(keep_alive))
+        .into_response();
+    return Ok(counted(response, log));
 }
@@ -0,0 +1,784 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{Json, extract::State};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::{
+    auth::{Admin, Principal},
+    config::SseConfig,
+    error::{AppError, ErrorCode},
+    event::{Broadcast, EventResponse, Priority},
+    state::AppState,
+};
+
+/// What to do when a subscriber's queue is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued event to make room; the client sees the latest state.
+    #[default]
+    DropOldest,
+    /// Discard the incoming event; the client sees an older but contiguous prefix.
+    DropNewest,
+    /// Close the subscriber's stream so it reconnects and starts fresh.
+    Disconnect,
+}
+
+impl OverflowPolicy {
+    fn label(&self) -> &'static str {
+        match self {
+            OverflowPolicy::DropOldest => return "drop_oldest",
+            OverflowPolicy::DropNewest => return "drop_newest",
+            OverflowPolicy::Disconnect => return "disconnect",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Utilization {
+    pub capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+    pub subscribers: usize,
+    /// Application channels currently held, including empty ones awaiting the sweeper.
+    pub channels: usize,
+    /// Tasks filling the subscriber queues; 0 when publishers do it themselves.
+    pub workers: usize,
+    /// Events waiting for those tasks.
+    pub backlog: usize,
+    pub queued_events: usize,
+    pub max_queue_length: usize,
+    /// Queued events relative to the combined capacity of all queues.
+    pub fill_ratio: f64,
+}
+
+/// Why the hub stopped feeding a subscriber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disconnect {
+    /// The queue filled up under the `disconnect` overflow policy.
+    Overflow,
+    /// The subscriber fell further behind than the slow-consumer thresholds allow.
+    TooSlow { lag: usize },
+    /// The instance is draining and asked every subscriber to reconnect elsewhere.
+    Drain,
+    /// The application was archived and its channel torn down, or the room was deleted.
+    Archived,
+    /// Closed on purpose through the chaos endpoints.
+    #[cfg(feature = "chaos")]
+    Chaos,
+}
+
+/// Thresholds beyond which a subscriber is considered too slow to keep.
+#[derive(Debug, Clone, Copy)]
+struct LagLimits {
+    max_events: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+/// What a subscriber receives next.
+#[derive(Debug)]
+pub enum Delivery {
+    /// Shared with every other subscriber of the event.
+    Event(Arc<Broadcast>),
+    /// The overflow policy discarded this many events since the last delivery.
+    Gap { missed: u64 },
+    /// Assets changed while running with `--dev`; the page should reload itself.
+    Reload,
+    /// An operator announcement from the recurring schedule, for every subscriber.
+    Announcement(Arc<Value>),
+    /// A deliberately broken frame requested through the chaos endpoints.
+    #[cfg(feature = "chaos")]
+    Malformed,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Events with the instant they were queued, oldest first.
+    events: VecDeque<(Instant, Arc<Broadcast>)>,
+    /// Critical events, which are never dropped and are delivered before `events`.
+    urgent: VecDeque<Arc<Broadcast>>,
+    missed: u64,
+    closed: Option<Disconnect>,
+    /// Like `closed`, but takes effect once everything already queued is delivered.
+    closing: Option<Disconnect>,
+    /// Set by [`Hub::request_reload`]; several changes before the next poll collapse into one.
+    reload: bool,
+    announcements: VecDeque<Arc<Value>>,
+    #[cfg(feature = "chaos")]
+    malformed: usize,
+}
+
+struct SubscriberQueue {
+    /// Who is listening: tenant subscribers only see their own tenant's events, and each
+    /// subscriber only the visibility levels meant for it.
+    principal: Principal,
+    /// Event types the subscriber asked for; `None` receives every type.
+    types: Option<HashSet<String>>,
+    /// The room an unfiltered subscriber follows, limiting it to the room's applications.
+    room: Option<String>,
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+impl SubscriberQueue {
+    fn wants(&self, event: &Broadcast) -> bool {
+        return self.principal.can_see(event.visibility)
+            && self
+                .types
+                .as_ref()
+                .is_none_or(|types| types.contains(event.kind()));
+    }
+
+    fn push(
+        &self,
+        event: Arc<Broadcast>,
+        capacity: usize,
+        policy: OverflowPolicy,
+        limits: LagLimits,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed.is_some() || state.closing.is_some() {
+            return;
+        }
+
+        let lag = state.events.len();
+        let oldest_age = state
+            .events
+            .front()
+            .map(|(queued_at, _)| queued_at.elapsed());
+        let too_many = limits.max_events.is_some_and(|max| lag >= max);
+        let too_old = limits
+            .max_age
+            .is_some_and(|max| oldest_age.is_some_and(|age| age > max));
+        if too_many || too_old {
+            metrics::counter!("slow_consumer_disconnects_total").increment(1);
+            state.closed = Some(Disconnect::TooSlow { lag });
+            self.notify.notify_one();
+            return;
+        }
+
+        if event.priority == Priority::Critical {
+            metrics::counter!("priority_deliveries_total").increment(1);
+            state.urgent.push_back(event);
+            self.notify.notify_one();
+            return;
+        }
+
+        if lag >= capacity {
+            metrics::counter!("fanout_overflow_total", "policy" => policy.label()).increment(1);
+            match policy {
+                OverflowPolicy::DropOldest => {
+                    state.events.pop_front();
+                    state.missed += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.missed += 1;
+                    return;
+                }
+                OverflowPolicy::Disconnect => {
+                    state.closed = Some(Disconnect::Overflow);
+                    self.notify.notify_one();
+                    return;
+                }
+            }
+        }
+        // a capacity shrunk at runtime may leave more than one event to discard
+        if policy == OverflowPolicy::DropOldest {
+            while state.events.len() >= capacity.max(1) {
+                state.events.pop_front();
+                state.missed += 1;
+            }
+        }
+        state.events.push_back((Instant::now(), event));
+        metrics::counter!("fanout_enqueued_total").increment(1);
+        self.notify.notify_one();
+    }
+}
+
+/// Subscribers of one application (or of everything), keyed by subscription id.
+struct Channel {
+    queues: HashMap<u64, Arc<SubscriberQueue>>,
+    /// Last subscribe, unsubscribe or publish. Empty channels stay around so reconnecting
+    /// clients reuse them, until the sweeper finds them idle.
+    last_activity: Instant,
+}
+
+impl Channel {
+    fn new() -> Self {
+        return Self {
+            queues: HashMap::new(),
+            last_activity: Instant::now(),
+        };
+    }
+}
+
+/// Events waiting for a shard's worker, bounded like a subscriber queue.
+#[derive(Default)]
+struct Backlog {
+    events: Mutex<VecDeque<Arc<Broadcast>>>,
+    notify: Notify,
+}
+
+/// One slice of the subscribers, picked by subscription id. With `sse.fanout_workers` set,
+/// each shard's queues are filled by a worker task of its own.
+struct Shard {
+    /// Subscribers keyed by the application they follow; `None` receives everything.
+    subscribers: Mutex<HashMap<Option<Uuid>, Channel>>,
+    /// `None` when publishers fill the queues themselves.
+    backlog: Option<Backlog>,
+}
+
+impl Shard {
+    fn new(worker: bool) -> Self {
+        return Self {
+            subscribers: Mutex::new(HashMap::new()),
+            backlog: worker.then(Backlog::default),
+        };
+    }
+}
+
+/// Fans events out to subscribers, each of which owns a bounded queue. Unlike a shared
+/// broadcast ring, a slow client only ever loses its own events.
+pub struct Hub {
+    /// Events a single subscriber may have waiting before the overflow policy kicks in.
+    capacity: AtomicUsize,
+    policy: OverflowPolicy,
+    lag_limits: LagLimits,
+    next_id: AtomicU64,
+    /// A single one unless `sse.fanout_workers` asks for more.
+    shards: Vec<Shard>,
+    /// The applications of each room, mirrored from the store.
+    rooms: RwLock<HashMap<String, HashSet<Uuid>>>,
+}
+
+impl Hub {
+    pub fn new(config: &SseConfig) -> Self {
+        let shards = match config.fanout_workers {
+            0 => vec![Shard::new(false)],
+            workers => (0..workers).map(|_| Shard::new(true)).collect(),
+        };
+        return Self {
+            capacity: AtomicUsize::new(config.queue_capacity),
+            policy: config.overflow_policy,
+            lag_limits: LagLimits {
+                max_events: config.slow_consumer_max_lag,
+                max_age: config.slow_consumer_max_lag_secs.map(Duration::from_secs),
+            },
+            next_id: AtomicU64::new(1),
+            shards,
+            rooms: RwLock::new(HashMap::new()),
+        };
+    }
+
+    fn shard(&self, id: u64) -> &Shard {
+        return &self.shards[id as usize % self.shards.len()];
+    }
+
+    /// Calls `f` with every subscriber queue, one shard locked at a time.
+    fn each_queue(&self, mut f: impl FnMut(&Arc<SubscriberQueue>)) {
+        for shard in &self.shards {
+            let subscribers = shard.subscribers.lock().unwrap();
+            subscribers
+                .values()
+                .flat_map(|channel| channel.queues.values())
+                .for_each(&mut f);
+        }
+    }
+
+    /// Registers a subscriber. Callers must have checked that a tenant may see `application_id`.
+    pub fn subscribe(
+        self: &Arc<Self>,
+        application_id: Option<Uuid>,
+        room: Option<String>,
+        principal: Principal,
+        types: Option<HashSet<String>>,
+    ) -> Subscription {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let queue = Arc::new(SubscriberQueue {
+            principal,
+            types,
+            room,
+            state: Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+        });
+        {
+            let mut subscribers = self.shard(id).subscribers.lock().unwrap();
+            let channel = subscribers
+                .entry(application_id)
+                .or_insert_with(Channel::new);
+            channel.queues.insert(id, queue.clone());
+            channel.last_activity = Instant::now();
+        }
+
+        return Subscription {
+            id,
+            application_id,
+            queue,
+            hub: self.clone(),
+        };
+    }
+
+    /// Queues the event for every interested subscriber and returns how many there were.
+    /// Shards with a worker are handed the event instead, counting the subscribers it
+    /// will be queued for.
+    pub fn publish(&self, event: Broadcast) -> usize {
+        let event = Arc::new(event);
+        let mut reached = 0;
+        for shard in &self.shards {
+            match &shard.backlog {
+                Some(backlog) => {
+                    reached += self
+                        .targets(&shard.subscribers.lock().unwrap(), &event)
+                        .len();
+                    self.enqueue(shard, backlog, event.clone());
+                }
+                None => reached += self.deliver(shard, &event),
+            }
+        }
+        return reached;
+    }
+
+    /// Hands the event to the shard's worker. A full backlog sheds according to the
+    /// overflow policy, critical events excepted.
+    fn enqueue(&self, shard: &Shard, backlog: &Backlog, event: Arc<Broadcast>) {
+        let capacity = self.capacity.load(Ordering::Relaxed).max(1);
+        let mut events = backlog.events.lock().unwrap();
+        let mut shed = None;
+        if events.len() < capacity || event.priority == Priority::Critical {
+            events.push_back(event);
+        } else {
+            metrics::counter!("fanout_backlog_overflow_total", "policy" => self.policy.label())
+                .increment(1);
+            let oldest = events
+                .iter()
+                .position(|queued| queued.priority != Priority::Critical)
+                .filter(|_| self.policy == OverflowPolicy::DropOldest);
+            match oldest {
+                Some(position) => {
+                    shed = events.remove(position);
+                    events.push_back(event);
+                }
+                None => shed = Some(event),
+            }
+        }
+        drop(events);
+        backlog.notify.notify_one();
+        if let Some(shed) = shed {
+            self.shed(shard, &shed);
+        }
+    }
+
+    /// Tells the subscribers an event was meant for that they will not get it: as a gap,
+    /// or by closing their stream under the `disconnect` policy.
+    fn shed(&self, shard: &Shard, event: &Broadcast) {
+        let subscribers = shard.subscribers.lock().unwrap();
+        for queue in self.targets(&subscribers, event) {
+            let mut state = queue.state.lock().unwrap();
+            if self.policy == OverflowPolicy::Disconnect {
+                state.closed = Some(Disconnect::Overflow);
+            } else {
+                state.missed += 1;
+            }
+            queue.notify.notify_one();
+        }
+    }
+
+    /// Queues the event for the shard's interested subscribers and returns how many there were.
+    fn deliver(&self, shard: &Shard, event: &Arc<Broadcast>) -> usize {
+        let mut subscribers = shard.subscribers.lock().unwrap();
+        let now = Instant::now();
+        if let Some(unfiltered) = subscribers.get_mut(&None) {
+            unfiltered.last_activity = now;
+        }
+        if event.application_id.is_some()
+            && let Some(filtered) = subscribers.get_mut(&event.application_id)
+        {
+            filtered.last_activity = now;
+        }
+
+        let targets = self.targets(&subscribers, event);
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        for queue in &targets {
+            queue.push(event.clone(), capacity, self.policy, self.lag_limits);
+        }
+        return targets.len();
+    }
+
+    /// The subscribers of a shard that want the event, after tenant, room, visibility and
+    /// type filters.
+    fn targets<'a>(
+        &self,
+        subscribers: &'a HashMap<Option<Uuid>, Channel>,
+        event: &Broadcast,
+    ) -> Vec<&'a Arc<SubscriberQueue>> {
+        let mut targets: Vec<&Arc<SubscriberQueue>> = Vec::new();
+        if let Some(unfiltered) = subscribers.get(&None) {
+            let rooms = self.rooms.read().unwrap();
+            let in_room = |room: &String| {
+                return rooms.get(room).is_some_and(|applications| {
+                    event
+                        .application_id
+                        .is_some_and(|id| applications.contains(&id))
+                });
+            };
+            targets.extend(unfiltered.queues.values().filter(|queue| {
+                queue.principal.can_access(event.tenant.as_deref())
+                    && queue.room.as_ref().is_none_or(in_room)
+            }));
+        }
+        if event.application_id.is_some()
+            && let Some(filtered) = subscribers.get(&event.application_id)
+        {
+            targets.extend(filtered.queues.values());
+        }
+
+        targets.retain(|queue| queue.wants(event));
+        return targets;
+    }
+
+    /// Applies a new per-subscriber capacity to existing and future queues; queues that
+    /// are now over capacity shed events according to the overflow policy on their next push.
+    pub fn resize(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    pub fn utilization(&self) -> Utilization {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let mut lengths: Vec<usize> = Vec::new();
+        self.each_queue(|queue| lengths.push(queue.state.lock().unwrap().events.len()));
+
+        let queued_events: usize = lengths.iter().sum();
+        let max_queue_length = lengths.iter().copied().max().unwrap_or(0);
+        let fill_ratio = if lengths.is_empty() || capacity == 0 {
+            0.0
+        } else {
+            queued_events as f64 / (lengths.len() * capacity) as f64
+        };
+        return Utilization {
+            capacity,
+            overflow_policy: self.policy,
+            subscribers: lengths.len(),
+            channels: self.channel_count(),
+            workers: self
+                .shards
+                .iter()
+                .filter(|shard| shard.backlog.is_some())
+                .count(),
+            backlog: self
+                .shards
+                .iter()
+                .filter_map(|shard| shard.backlog.as_ref())
+                .map(|backlog| backlog.events.lock().unwrap().len())
+                .sum(),
+            queued_events,
+            max_queue_length,
+            fill_ratio,
+        };
+    }
+
+    /// Applications with a channel in any shard, plus the unfiltered one.
+    fn channel_count(&self) -> usize {
+        let mut channels: HashSet<Option<Uuid>> = HashSet::new();
+        for shard in &self.shards {
+            channels.extend(shard.subscribers.lock().unwrap().keys().copied());
+        }
+        return channels.len();
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        let mut count = 0;
+        self.each_queue(|_| count += 1);
+        return count;
+    }
+
+    /// Ends every subscription with `reason` once its already queued events are delivered,
+    /// returning how many there were.
+    pub fn close_all(&self, reason: Disconnect) -> usize {
+        let mut closed = 0;
+        self.each_queue(|queue| {
+            queue.state.lock().unwrap().closing = Some(reason);
+            queue.notify.notify_one();
+            closed += 1;
+        });
+        return closed;
+    }
+
+    /// Asks every subscriber, whatever it follows, to reload the page. Returns how many
+    /// there were.
+    pub fn request_reload(&self) -> usize {
+        let mut reached = 0;
+        self.each_queue(|queue| {
+            queue.state.lock().unwrap().reload = true;
+            queue.notify.notify_one();
+            reached += 1;
+        });
+        return reached;
+    }
+
+    /// Queues an announcement for every subscriber, regardless of application, tenant or
+    /// event-type filter. Returns how many there were.
+    pub fn announce(&self, data: Value) -> usize {
+        let data = Arc::new(data);
+        let mut reached = 0;
+        self.each_queue(|queue| {
+            queue
+                .state
+                .lock()
+                .unwrap()
+                .announcements
+                .push_back(data.clone());
+            queue.notify.notify_one();
+            reached += 1;
+        });
+        return reached;
+    }
+
+    /// Up to `count` random subscriber queues, or all of them.
+    #[cfg(feature = "chaos")]
+    fn sample(&self, count: Option<usize>) -> Vec<Arc<SubscriberQueue>> {
+        use rand::seq::SliceRandom;
+
+        let mut queues: Vec<Arc<SubscriberQueue>> = Vec::new();
+        self.each_queue(|queue| queues.push(queue.clone()));
+        queues.shuffle(&mut rand::rng());
+        queues.truncate(count.unwrap_or(usize::MAX));
+        return queues;
+    }
+
+    /// Force-closes random subscribers and returns how many were hit.
+    #[cfg(feature = "chaos")]
+    pub fn close_random(&self, count: Option<usize>) -> usize {
+        let queues = self.sample(count);
+        for queue in &queues {
+            queue.state.lock().unwrap().closed = Some(Disconnect::Chaos);
+            queue.notify.notify_one();
+        }
+        return queues.len();
+    }
+
+    /// Queues a malformed frame for random subscribers and returns how many were hit.
+    #[cfg(feature = "chaos")]
+    pub fn inject_malformed(&self, count: Option<usize>) -> usize {
+        let queues = self.sample(count);
+        for queue in &queues {
+            queue.state.lock().unwrap().malformed += 1;
+            queue.notify.notify_one();
+        }
+        return queues.len();
+    }
+
+    fn unsubscribe(&self, application_id: Option<Uuid>, id: u64) {
+        let mut subscribers = self.shard(id).subscribers.lock().unwrap();
+        if let Some(channel) = subscribers.get_mut(&application_id) {
+            channel.queues.remove(&id);
+            channel.last_activity = Instant::now();
+        }
+    }
+
+    /// Sets which applications' events the room's subscribers receive.
+    pub fn set_room(&self, name: &str, applications: &BTreeSet<Uuid>) {
+        self.rooms
+            .write()
+            .unwrap()
+            .insert(name.to_string(), applications.iter().copied().collect());
+    }
+
+    /// Forgets a room, ending its subscriptions with `reason` once their queued events are
+    /// delivered. Returns how many subscribers there were.
+    pub fn remove_room(&self, name: &str, reason: Disconnect) -> usize {
+        self.rooms.write().unwrap().remove(name);
+        let mut closed = 0;
+        self.each_queue(|queue| {
+            if queue.room.as_deref() == Some(name) {
+                queue.state.lock().unwrap().closing = Some(reason);
+                queue.notify.notify_one();
+                closed += 1;
+            }
+        });
+        return closed;
+    }
+
+    /// Removes an application's channel, ending its subscriptions with `reason` once their
+    /// queued events are delivered. Returns how many subscribers there were.
+    pub fn close_channel(&self, application_id: Uuid, reason: Disconnect) -> usize {
+        let mut closed = 0;
+        for shard in &self.shards {
+            let Some(channel) = shard
+                .subscribers
+                .lock()
+                .unwrap()
+                .remove(&Some(application_id))
+            else {
+                continue;
+            };
+            for queue in channel.queues.values() {
+                queue.state.lock().unwrap().closing = Some(reason);
+                queue.notify.notify_one();
+            }
+            closed += channel.queues.len();
+        }
+        return closed;
+    }
+
+    /// Drops channels that have had no subscribers and no events for `idle`, returning
+    /// how many were removed.
+    pub fn sweep(&self, idle: Duration) -> usize {
+        let mut removed = 0;
+        for shard in &self.shards {
+            let mut subscribers = shard.subscribers.lock().unwrap();
+            let before = subscribers.len();
+            subscribers.retain(|_, channel| {
+                !channel.queues.is_empty() || channel.last_activity.elapsed() < idle
+            });
+            removed += before - subscribers.len();
+        }
+        metrics::gauge!("fanout_channels").set(self.channel_count() as f64);
+        return removed;
+    }
+}
+
+/// Runs the fanout workers of `sse.fanout_workers`, each filling its own shard's queues,
+/// so publishing to an application with tens of thousands of subscribers is spread over
+/// that many tasks instead of one.
+pub async fn run_workers(app_state: Arc<AppState>) {
+    let mut workers = tokio::task::JoinSet::new();
+    for index in 0..app_state.hub.shards.len() {
+        let app_state = app_state.clone();
+        workers.spawn(async move {
+            let shard = &app_state.hub.shards[index];
+            let Some(backlog) = &shard.backlog else {
+                return;
+            };
+            loop {
+                let next = backlog.events.lock().unwrap().pop_front();
+                match next {
+                    Some(event) => {
+                        app_state.hub.deliver(shard, &event);
+                    }
+                    None => backlog.notify.notified().await,
+                }
+            }
+        });
+    }
+    while let Some(result) = workers.join_next().await {
+        if let Err(err) = result
+            && err.is_panic()
+        {
+            // the supervisor restarts every worker, which picks up where the backlogs are
+            std::panic::resume_unwind(err.into_panic());
+        }
+    }
+}
+
+/// Periodically removes idle channels so applications nobody follows anymore don't keep
+/// an entry forever.
+pub async fn sweep_channels(app_state: Arc<AppState>) {
+    loop {
+        let idle = Duration::from_secs(app_state.config().sse.channel_idle_secs);
+        tokio::time::sleep(idle.max(Duration::from_secs(1))).await;
+        let removed = app_state.hub.sweep(idle);
+        if removed > 0 {
+            tracing::debug!("Swept {} idle fanout channels", removed);
+        }
+    }
+}
+
+/// A subscriber's end of the hub; dropping it unregisters the queue.
+pub struct Subscription {
+    id: u64,
+    application_id: Option<Uuid>,
+    queue: Arc<SubscriberQueue>,
+    hub: Arc<Hub>,
+}
+
+impl Subscription {
+    /// Waits for the next delivery, or tells why the hub gave up on this subscriber. Dropped
+    /// events are reported as a [`Delivery::Gap`] ahead of the events that survived.
+    /// Cancel-safe: nothing is taken from the queue until the future completes.
+    pub async fn recv(&mut self) -> Result<Delivery, Disconnect> {
+        loop {
+            {
+                let mut state = self.queue.state.lock().unwrap();
+                if let Some(reason) = state.closed {
+                    return Err(reason);
+                }
+                #[cfg(feature = "chaos")]
+                if state.malformed > 0 {
+                    state.malformed -= 1;
+                    return Ok(Delivery::Malformed);
+                }
+                if let Some(event) = state.urgent.pop_front() {
+                    return Ok(Delivery::Event(event));
+                }
+                if std::mem::take(&mut state.reload) {
+                    return Ok(Delivery::Reload);
+                }
+                if let Some(data) = state.announcements.pop_front() {
+                    return Ok(Delivery::Announcement(data));
+                }
+                if state.missed > 0 {
+                    let missed = std::mem::take(&mut state.missed);
+                    return Ok(Delivery::Gap { missed });
+                }
+                if let Some((_, event)) = state.events.pop_front() {
+                    return Ok(Delivery::Event(event));
+                }
+                if let Some(reason) = state.closing {
+                    return Err(reason);
+                }
+            }
+            self.queue.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.hub.unsubscribe(self.application_id, self.id);
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResizeRequest {
+    capacity: usize,
+}
+
+pub async fn utilization(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<Utilization>>, AppError> {
+    admin.require_operator()?;
+    return Ok(Json(EventResponse::ok(state.hub.utilization())));
+}
+
+pub async fn resize(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<ResizeRequest>, AppError>,
+) -> Result<Json<EventResponse<Utilization>>, AppError> {
+    admin.require_operator()?;
+    if payload.capacity == 0 {
+        return Err(AppError::new(
+            ErrorCode::InvalidCapacity,
+            "Queue capacity must be at least 1",
+        ));
+    }
+
+    state.hub.resize(payload.capacity);
+    tracing::info!(
+        "{} resized subscriber queues to {}",
+        admin.subject,
+        payload.capacity
+    );
+    return Ok(Json(EventResponse::ok(state.hub.utilization())));
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::Admin, error::AppError, event::EventResponse, state::AppState};
+
+/// Behaviours operators can switch on and off while the server runs. `[flags]` in the
+/// config sets the values at startup; `PUT /admin/flags` changes them until the next restart.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Flags {
+    /// Skip progress events that repeat an application's last percentage.
+    pub dedup: bool,
+    /// Refuse progress events that follow the application's previous one sooner than
+    /// `events.throttle_interval_ms`.
+    pub throttle: bool,
+    /// Validate and acknowledge sends without storing or broadcasting them.
+    pub simulation: bool,
+}
+
+/// Body of `PUT /admin/flags`; omitted flags keep their value.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FlagsUpdate {
+    dedup: Option<bool>,
+    throttle: Option<bool>,
+    simulation: Option<bool>,
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<Flags>>, AppError> {
+    admin.require_operator()?;
+    return Ok(Json(EventResponse::ok(state.flags())));
+}
+
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<FlagsUpdate>, AppError>,
+) -> Result<Json<EventResponse<Flags>>, AppError> {
+    admin.require_operator()?;
+    let flags = {
+        let mut flags = state.flags.write().unwrap();
+        flags.dedup = payload.dedup.unwrap_or(flags.dedup);
+        flags.throttle = payload.throttle.unwrap_or(flags.throttle);
+        flags.simulation = payload.simulation.unwrap_or(flags.simulation);
+        *flags
+    };
+    tracing::info!("{} set feature flags to {:?}", admin.subject, flags);
+    return Ok(Json(EventResponse::ok(flags)));
+}
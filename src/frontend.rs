@@ -0,0 +1,119 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method, Uri, header},
+    response::{Html, IntoResponse, Response},
+};
+use minijinja::{Environment, context, value::Serde};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    state::AppState,
+    version,
+};
+
+const INDEX_TEMPLATE: &str = "index.html";
+
+pub fn assets_dir() -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
+}
+
+/// `index.html` compiled at startup, and again on changes under `--dev`. Everything else
+/// under `assets/` is served as is.
+pub struct Templates {
+    env: RwLock<Environment<'static>>,
+}
+
+impl Templates {
+    pub fn load() -> Self {
+        let env = compile().unwrap_or_else(|err| panic!("{}", err));
+        return Self {
+            env: RwLock::new(env),
+        };
+    }
+
+    /// Recompiles from disk. A broken template is reported and the previous one kept.
+    pub fn reload(&self) -> Result<(), String> {
+        *self.env.write().unwrap() = compile()?;
+        return Ok(());
+    }
+}
+
+fn compile() -> Result<Environment<'static>, String> {
+    let path = assets_dir().join(INDEX_TEMPLATE);
+    let source = fs::read_to_string(&path)
+        .map_err(|err| format!("cannot read template {}: {}", path.display(), err))?;
+    let mut env = Environment::new();
+    env.add_template_owned(INDEX_TEMPLATE, source)
+        .map_err(|err| format!("invalid template {}: {}", path.display(), err))?;
+    return Ok(env);
+}
+
+/// Renders `index.html` with the `[frontend]` settings and the current runtime flags.
+pub async fn index(State(state): State<Arc<AppState>>) -> Response {
+    let config = state.config();
+    return render(
+        &state,
+        &config.frontend.events_url,
+        config.frontend.application_id,
+    );
+}
+
+/// Renders `index.html` wired to `events_url`, following `application_id` when set.
+pub fn render(state: &AppState, events_url: &str, application_id: Option<Uuid>) -> Response {
+    let env = state.templates.env.read().unwrap();
+    let rendered = env.get_template(INDEX_TEMPLATE).and_then(|template| {
+        template.render(context! {
+            events_url => events_url,
+            application_id => Serde(application_id),
+            flags => Serde(state.flags()),
+            version => version::build_info().version,
+            dev => state.dev,
+        })
+    });
+    match rendered {
+        Ok(html) => return Html(html).into_response(),
+        Err(err) => {
+            tracing::error!("Failed to render {}: {}", INDEX_TEMPLATE, err);
+            return AppError::from(ErrorCode::UnknownError).into_response();
+        }
+    }
+}
+
+/// Whether the request looks like a browser loading a page rather than an API call.
+fn is_navigation(method: &Method, uri: &Uri, headers: &HeaderMap) -> bool {
+    if method != Method::GET && method != Method::HEAD {
+        return false;
+    }
+    if uri.path() == "/api" || uri.path().starts_with("/api/") {
+        return false;
+    }
+    return headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+}
+
+/// Answers paths no route or asset matches: browsers navigating get `index.html` when
+/// `spa_fallback` is on, everything else the standard 404 envelope.
+pub async fn fallback(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    if is_navigation(&method, &uri, &headers) && state.config().frontend.spa_fallback {
+        return index(State(state)).await;
+    }
+    return AppError::new(
+        ErrorCode::RouteNotFound,
+        format!("No endpoint matches {} {}", method, uri.path()),
+    )
+    .into_response();
+}
@@ -0,0 +1,239 @@
+use std::{convert::Infallible, iter, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    application,
+    auth::{Principal, Viewer},
+    content::{self, CsvRecord, Format, Negotiated},
+    error::{AppError, ErrorCode},
+    event::StoredEvent,
+    state::AppState,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct HistoryQuery {
+    /// Only events submitted by this subject.
+    actor: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeriesQuery {
+    /// Upper bound on the number of points returned.
+    points: Option<usize>,
+}
+
+const DEFAULT_SERIES_POINTS: usize = 200;
+const MAX_SERIES_POINTS: usize = 5000;
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    /// Overrides the `Accept` header, for download links that cannot set headers.
+    format: Option<Format>,
+    actor: Option<String>,
+}
+
+/// Unexpired stored events of one application that `viewer` may see, oldest first,
+/// optionally only those submitted by `actor`.
+pub fn events_of(
+    state: &AppState,
+    viewer: &Principal,
+    id: Uuid,
+    actor: Option<&str>,
+) -> Result<Vec<StoredEvent>, AppError> {
+    let now = Utc::now();
+    return state.store.read(|data| {
+        if !data.applications.contains_key(&id) {
+            return Err(application::not_found(id));
+        }
+        return Ok(data
+            .events
+            .iter()
+            .filter(|stored| stored.event.application_id == Some(id) && !stored.is_expired(now))
+            .filter(|stored| viewer.can_see(stored.event.visibility))
+            .filter(|stored| actor.is_none_or(|actor| stored.actor.as_deref() == Some(actor)))
+            .cloned()
+            .collect());
+    });
+}
+
+impl CsvRecord for StoredEvent {
+    const HEADER: &'static [&'static str] = &["id", "at", "actor", "percentage", "retracted_at"];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![
+            self.id.to_string(),
+            self.at.to_rfc3339(),
+            self.actor.clone().unwrap_or_default(),
+            self.event.percentage.to_string(),
+            self.retracted
+                .as_ref()
+                .map(|retraction| retraction.at.to_rfc3339())
+                .unwrap_or_default(),
+        ];
+    }
+}
+
+/// `GET /applications/{id}/history`: who moved the case forward and when.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(format): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Query(query), _): WithRejection<Query<HistoryQuery>, AppError>,
+) -> Result<Response, AppError> {
+    application::authorize(&state, &viewer, id)?;
+    let events = events_of(&state, &viewer, id, query.actor.as_deref())?;
+    return Ok(content::respond(format, events));
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct SeriesPoint {
+    at: DateTime<Utc>,
+    percentage: f64,
+}
+
+impl CsvRecord for SeriesPoint {
+    const HEADER: &'static [&'static str] = &["at", "percentage"];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![self.at.to_rfc3339(), self.percentage.to_string()];
+    }
+}
+
+/// Largest-Triangle-Three-Buckets: keeps the first and last point and, from each bucket
+/// in between, the point spanning the largest triangle with its neighbours, so steps and
+/// plateaus survive downsampling. `points` must be at least 3.
+fn lttb(series: &[SeriesPoint], points: usize) -> Vec<SeriesPoint> {
+    if series.len() <= points {
+        return series.to_vec();
+    }
+    let x = |point: &SeriesPoint| point.at.timestamp_millis() as f64;
+
+    let bucket_size = (series.len() - 2) as f64 / (points - 2) as f64;
+    let mut sampled = Vec::with_capacity(points);
+    sampled.push(series[0]);
+    let mut selected = 0;
+    for bucket in 0..points - 2 {
+        let start = (bucket as f64 * bucket_size) as usize + 1;
+        let end = ((bucket + 1) as f64 * bucket_size) as usize + 1;
+
+        // the next bucket is represented by its average, the last one by the final point
+        let next_end = (((bucket + 2) as f64 * bucket_size) as usize + 1).min(series.len());
+        let next = if end < series.len() - 1 {
+            &series[end..next_end]
+        } else {
+            &series[series.len() - 1..]
+        };
+        let average_x = next.iter().map(x).sum::<f64>() / next.len() as f64;
+        let average_y = next.iter().map(|point| point.percentage).sum::<f64>() / next.len() as f64;
+
+        let anchor = &series[selected];
+        let mut largest = -1.0;
+        for (index, point) in series.iter().enumerate().take(end).skip(start) {
+            let area = ((x(anchor) - average_x) * (point.percentage - anchor.percentage)
+                - (x(anchor) - x(point)) * (average_y - anchor.percentage))
+                .abs();
+            if area > largest {
+                largest = area;
+                selected = index;
+            }
+        }
+        sampled.push(series[selected]);
+    }
+    sampled.push(series[series.len() - 1]);
+    return sampled;
+}
+
+/// `GET /applications/{id}/history/series`: (timestamp, percentage) pairs for charting,
+/// downsampled to at most `points` entries.
+pub async fn series(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(format): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Query(query), _): WithRejection<Query<SeriesQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let points = query.points.unwrap_or(DEFAULT_SERIES_POINTS);
+    if !(3..=MAX_SERIES_POINTS).contains(&points) {
+        return Err(AppError::new(
+            ErrorCode::InvalidQueryParameter,
+            format!("points must be between 3 and {}", MAX_SERIES_POINTS),
+        ));
+    }
+    application::authorize(&state, &viewer, id)?;
+    let series: Vec<SeriesPoint> = events_of(&state, &viewer, id, None)?
+        .into_iter()
+        .filter(|stored| stored.retracted.is_none())
+        .map(|stored| SeriesPoint {
+            at: stored.at,
+            percentage: stored.event.percentage,
+        })
+        .collect();
+    return Ok(content::respond(format, lttb(&series, points)));
+}
+
+/// Full history as a downloadable file, streamed so a long history never sits in memory
+/// as a single serialized blob.
+pub async fn export(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(accepted): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Query(query), _): WithRejection<Query<ExportQuery>, AppError>,
+) -> Result<Response, AppError> {
+    application::authorize(&state, &viewer, id)?;
+    let events = events_of(&state, &viewer, id, query.actor.as_deref())?;
+
+    // rows are formatted lazily as the body is polled
+    let format = query.format.unwrap_or(accepted);
+    let body = match format {
+        Format::Csv => {
+            let rows = events.into_iter().map(|stored| content::csv_row(&stored));
+            let chunks = iter::once(content::csv_header::<StoredEvent>()).chain(rows);
+            Body::from_stream(stream::iter(chunks.map(Ok::<_, Infallible>)))
+        }
+        // MessagePack has no cheap way to stream an array of unknown length
+        Format::Msgpack => match rmp_serde::to_vec_named(&events) {
+            Ok(bytes) => Body::from(bytes),
+            Err(err) => {
+                tracing::error!("Failed to encode history of {}: {}", id, err);
+                return Err(AppError::from(ErrorCode::UnknownError));
+            }
+        },
+        Format::Json => {
+            let rows = events.into_iter().enumerate().map(|(index, stored)| {
+                let separator = if index == 0 { "" } else { "," };
+                return format!("{}{}", separator, serde_json::to_string(&stored).unwrap());
+            });
+            let chunks = iter::once("[".to_string())
+                .chain(rows)
+                .chain(iter::once("]".to_string()));
+            Body::from_stream(stream::iter(chunks.map(Ok::<_, Infallible>)))
+        }
+    };
+
+    let disposition = format!(
+        "attachment; filename=\"application-{}-history.{}\"",
+        id,
+        format.extension()
+    );
+    return Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response());
+}
@@ -0,0 +1,559 @@
+use axum::{
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Id,
+    De,
+}
+
+impl Locale {
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => return Some(Locale::En),
+            "id" | "in" => return Some(Locale::Id),
+            "de" => return Some(Locale::De),
+            _ => return None,
+        }
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Locale::En => return "en",
+            Locale::Id => return "id",
+            Locale::De => return "de",
+        }
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => return EN,
+            Locale::Id => return ID,
+            Locale::De => return DE,
+        }
+    }
+}
+
+/// Server-generated texts. English error messages are written at the call site, where
+/// they can mention specifics, so only the other catalogs translate error codes.
+const EN: &[(&str, &str)] = &[
+    ("event-accepted", "Event accepted, but no listeners"),
+    ("event-sent", "Event sent to {count} listeners!"),
+    (
+        "event-duplicate",
+        "Duplicate event ignored; the progress is unchanged",
+    ),
+    (
+        "event-simulated",
+        "Simulation mode: event validated but not recorded",
+    ),
+    (
+        "event-held",
+        "Event recorded; broadcasting is paused for this application",
+    ),
+    (
+        "stream-gap",
+        "Some events were dropped for this connection. Backfill from the history API.",
+    ),
+    (
+        "stream-too-slow",
+        "Connection fell too far behind and is being closed. Reconnect, and fetch the history to catch up on missed events.",
+    ),
+    (
+        "stream-reconnect",
+        "This server is shutting down. Reconnect to continue receiving events.",
+    ),
+    (
+        "stream-expired",
+        "This connection has reached its maximum age. Reconnect to continue receiving events.",
+    ),
+    (
+        "stream-auth-expired",
+        "Your credentials have expired. Sign in again and reconnect to continue receiving events.",
+    ),
+    (
+        "stream-error",
+        "Something went wrong on this connection and it is being closed. Reconnect, and quote {connection_id} if it keeps happening.",
+    ),
+];
+
+const ID: &[(&str, &str)] = &[
+    (
+        "event-accepted",
+        "Event diterima, tetapi tidak ada pendengar",
+    ),
+    ("event-sent", "Event dikirim ke {count} pendengar!"),
+    (
+        "event-duplicate",
+        "Event duplikat diabaikan; progres tidak berubah",
+    ),
+    (
+        "event-simulated",
+        "Mode simulasi: event divalidasi tetapi tidak dicatat",
+    ),
+    (
+        "event-held",
+        "Event dicatat; siaran untuk aplikasi ini sedang dijeda",
+    ),
+    (
+        "stream-gap",
+        "Beberapa event terlewat pada koneksi ini. Lengkapi dari API riwayat.",
+    ),
+    (
+        "stream-too-slow",
+        "Koneksi terlalu tertinggal dan akan ditutup. Sambungkan ulang dan ambil riwayat untuk event yang terlewat.",
+    ),
+    (
+        "stream-reconnect",
+        "Server ini akan dimatikan. Sambungkan ulang untuk terus menerima event.",
+    ),
+    (
+        "stream-expired",
+        "Koneksi ini telah mencapai batas umurnya. Sambungkan ulang untuk terus menerima event.",
+    ),
+    (
+        "stream-auth-expired",
+        "Kredensial Anda telah kedaluwarsa. Masuk kembali dan sambungkan ulang untuk terus menerima event.",
+    ),
+    (
+        "stream-error",
+        "Terjadi kesalahan pada koneksi ini dan koneksi akan ditutup. Sambungkan ulang, dan sebutkan {connection_id} jika terus terjadi.",
+    ),
+    (
+        "ADDRESS_FORBIDDEN",
+        "Permintaan dari alamat ini tidak diizinkan",
+    ),
+    ("API_KEY_NOT_FOUND", "API key tidak ditemukan"),
+    (
+        "APPLICATION_ARCHIVED",
+        "Aplikasi telah diarsipkan dan tidak lagi menerima event",
+    ),
+    ("APPLICATION_NOT_FOUND", "Aplikasi tidak ditemukan"),
+    (
+        "APPLICATION_QUOTA_EXCEEDED",
+        "Tenant telah mencapai batas aplikasi aktif",
+    ),
+    ("BUFFER_ERROR", "Body permintaan tidak dapat dibaca"),
+    (
+        "CHECKLIST_NOT_CONFIGURED",
+        "Checklist tahapan belum dikonfigurasi",
+    ),
+    (
+        "CONFIG_RELOAD_FAILED",
+        "Konfigurasi tidak dapat dimuat ulang",
+    ),
+    (
+        "EMPTY_SCOPES_ERROR",
+        "API key memerlukan minimal satu scope",
+    ),
+    ("EVENT_ALREADY_RETRACTED", "Event sudah ditarik"),
+    ("EVENT_NOT_FOUND", "Event tidak ditemukan"),
+    (
+        "EVENT_QUOTA_EXCEEDED",
+        "Aplikasi telah mencapai batas event harian",
+    ),
+    (
+        "EVENT_THROTTLED",
+        "Event untuk aplikasi ini dikirim terlalu cepat",
+    ),
+    ("FORBIDDEN", "Anda tidak memiliki izin untuk tindakan ini"),
+    (
+        "IDENTITY_PROVIDER_ERROR",
+        "Penyedia identitas tidak dapat dihubungi, silakan coba lagi nanti",
+    ),
+    ("INVALID_APPOINTMENT", "Waktu janji temu tidak valid"),
+    ("INVALID_CAPACITY", "Kapasitas antrean minimal 1"),
+    (
+        "INVALID_CSRF_TOKEN",
+        "Token CSRF tidak ada atau tidak valid",
+    ),
+    ("INVALID_DOCUMENT", "Nama dokumen tidak boleh kosong"),
+    ("INVALID_IMPORT", "Berkas impor tidak valid"),
+    ("INVALID_LOG_FILTER", "Filter log tidak valid"),
+    ("INVALID_NOTE", "Panjang catatan tidak valid"),
+    ("INVALID_PATH_PARAMETER", "Parameter path tidak valid"),
+    ("INVALID_QUERY_PARAMETER", "Parameter query tidak valid"),
+    ("INVALID_ROOM", "Ruang tidak valid"),
+    ("INVALID_SCHEDULE", "publish_at harus di masa depan"),
+    (
+        "INVALID_SHARE_LINK",
+        "Masa berlaku tautan berbagi tidak valid",
+    ),
+    ("INVALID_SIGNATURE", "Tanda tangan tidak valid"),
+    ("INVALID_WEBHOOK", "Pendaftaran webhook tidak valid"),
+    (
+        "JSON_DESERIALIZATION_ERROR",
+        "Isi JSON tidak sesuai format yang diharapkan",
+    ),
+    (
+        "JSON_VALIDITY_ERROR",
+        "Body permintaan bukan JSON yang valid",
+    ),
+    (
+        "MISSING_JSON_CONTENT_TYPE",
+        "Header Content-Type harus application/json",
+    ),
+    ("MISSING_SIGNATURE", "Tanda tangan tidak ditemukan"),
+    (
+        "NOT_ACCEPTABLE",
+        "Format respons yang diminta tidak didukung",
+    ),
+    (
+        "OIDC_NOT_CONFIGURED",
+        "Masuk melalui penyedia identitas belum dikonfigurasi",
+    ),
+    ("PAYLOAD_TOO_LARGE", "Body permintaan melebihi batas ukuran"),
+    (
+        "PROGRESS_IS_COMPUTED",
+        "Progres aplikasi dihitung dari checklist tahapan",
+    ),
+    (
+        "RANGE_EXCEEDED_ERROR",
+        "Persentase harus berada di antara 0-100",
+    ),
+    ("REQUEST_TIMEOUT", "Permintaan terlalu lama diproses"),
+    (
+        "RESUME_SESSION_EXPIRED",
+        "Token lanjutan tidak dikenal atau sudah kedaluwarsa; berlangganan ulang dari awal",
+    ),
+    ("ROOM_NOT_FOUND", "Ruang tidak ditemukan"),
+    ("ROUTE_NOT_FOUND", "Endpoint tidak ditemukan"),
+    (
+        "SCHEDULED_EVENT_NOT_FOUND",
+        "Event terjadwal tidak ditemukan",
+    ),
+    (
+        "SCHEMA_VIOLATION",
+        "Payload event tidak sesuai dengan skema yang dikonfigurasi",
+    ),
+    (
+        "SERVER_DRAINING",
+        "Server sedang mengosongkan koneksi; sambungkan ulang melalui load balancer",
+    ),
+    (
+        "SERVICE_OVERLOADED",
+        "Server sedang sibuk, silakan coba lagi nanti",
+    ),
+    (
+        "SHARE_LINK_NOT_FOUND",
+        "Tautan pelacakan tidak dikenal atau sudah kedaluwarsa",
+    ),
+    ("SIGNATURE_EXPIRED", "Tanda tangan sudah kedaluwarsa"),
+    ("SIGNATURE_REPLAYED", "Tanda tangan sudah pernah digunakan"),
+    ("STAGE_NOT_FOUND", "Tahapan tidak ada dalam checklist"),
+    (
+        "STEP_TOO_LARGE",
+        "Perubahan progres melebihi batas; konfirmasi lompatan untuk menerimanya",
+    ),
+    (
+        "SUBSCRIBER_QUOTA_EXCEEDED",
+        "Aplikasi telah mencapai batas pelanggan",
+    ),
+    (
+        "TENANT_FORBIDDEN",
+        "Tindakan ini tidak tersedia untuk tenant Anda",
+    ),
+    (
+        "TIMESTAMP_IN_FUTURE",
+        "Waktu kejadian terlalu jauh di masa depan",
+    ),
+    ("UNAUTHORIZED", "Token akses tidak ada atau tidak valid"),
+    ("UNKNOWN_ERROR", "Terjadi kesalahan yang tidak terduga"),
+    (
+        "UNKNOWN_FIELD",
+        "Isi permintaan berisi field yang tidak dikenal",
+    ),
+    (
+        "UNKNOWN_STAGE",
+        "Tahap ini tidak ada untuk jenis visa tersebut",
+    ),
+    (
+        "VERSION_CONFLICT",
+        "Data telah diubah oleh orang lain; muat ulang lalu coba lagi",
+    ),
+    ("WEBHOOK_NOT_FOUND", "Webhook tidak ditemukan"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("event-accepted", "Ereignis angenommen, aber keine Zuhörer"),
+    ("event-sent", "Ereignis an {count} Zuhörer gesendet!"),
+    (
+        "event-duplicate",
+        "Doppeltes Ereignis ignoriert; der Fortschritt ist unverändert",
+    ),
+    (
+        "event-simulated",
+        "Simulationsmodus: Ereignis geprüft, aber nicht gespeichert",
+    ),
+    (
+        "event-held",
+        "Ereignis gespeichert; die Übertragung für diesen Antrag ist pausiert",
+    ),
+    (
+        "stream-gap",
+        "Für diese Verbindung wurden Ereignisse verworfen. Bitte über die Verlaufs-API nachladen.",
+    ),
+    (
+        "stream-too-slow",
+        "Die Verbindung ist zu weit zurückgefallen und wird geschlossen. Bitte neu verbinden und den Verlauf abrufen.",
+    ),
+    (
+        "stream-reconnect",
+        "Dieser Server wird heruntergefahren. Bitte neu verbinden, um weiter Ereignisse zu erhalten.",
+    ),
+    (
+        "stream-expired",
+        "Diese Verbindung hat ihre maximale Dauer erreicht. Bitte neu verbinden, um weiter Ereignisse zu erhalten.",
+    ),
+    (
+        "stream-auth-expired",
+        "Ihre Anmeldedaten sind abgelaufen. Bitte erneut anmelden und neu verbinden, um weiter Ereignisse zu erhalten.",
+    ),
+    (
+        "stream-error",
+        "Bei dieser Verbindung ist ein Fehler aufgetreten; sie wird geschlossen. Bitte neu verbinden und bei Wiederholung {connection_id} angeben.",
+    ),
+    (
+        "ADDRESS_FORBIDDEN",
+        "Anfragen von dieser Adresse sind nicht erlaubt",
+    ),
+    ("API_KEY_NOT_FOUND", "API-Schlüssel nicht gefunden"),
+    (
+        "APPLICATION_ARCHIVED",
+        "Der Antrag ist archiviert und nimmt keine Ereignisse mehr an",
+    ),
+    ("APPLICATION_NOT_FOUND", "Antrag nicht gefunden"),
+    (
+        "APPLICATION_QUOTA_EXCEEDED",
+        "Der Mandant hat die Höchstzahl aktiver Anträge erreicht",
+    ),
+    (
+        "BUFFER_ERROR",
+        "Der Anfrageinhalt konnte nicht gelesen werden",
+    ),
+    (
+        "CHECKLIST_NOT_CONFIGURED",
+        "Es ist keine Stufen-Checkliste konfiguriert",
+    ),
+    (
+        "CONFIG_RELOAD_FAILED",
+        "Die Konfiguration konnte nicht neu geladen werden",
+    ),
+    (
+        "EMPTY_SCOPES_ERROR",
+        "Ein API-Schlüssel braucht mindestens einen Scope",
+    ),
+    (
+        "EVENT_ALREADY_RETRACTED",
+        "Ereignis wurde bereits zurückgezogen",
+    ),
+    ("EVENT_NOT_FOUND", "Ereignis nicht gefunden"),
+    (
+        "EVENT_QUOTA_EXCEEDED",
+        "Der Antrag hat sein tägliches Ereignislimit erreicht",
+    ),
+    (
+        "EVENT_THROTTLED",
+        "Ereignisse für diesen Antrag kommen zu schnell",
+    ),
+    ("FORBIDDEN", "Für diese Aktion fehlt die Berechtigung"),
+    (
+        "IDENTITY_PROVIDER_ERROR",
+        "Der Identitätsanbieter ist nicht erreichbar, bitte später erneut versuchen",
+    ),
+    ("INVALID_APPOINTMENT", "Ungültige Terminzeiten"),
+    (
+        "INVALID_CAPACITY",
+        "Die Warteschlangenkapazität muss mindestens 1 sein",
+    ),
+    ("INVALID_CSRF_TOKEN", "CSRF-Token fehlt oder ist ungültig"),
+    ("INVALID_DOCUMENT", "Der Dokumentname darf nicht leer sein"),
+    ("INVALID_IMPORT", "Ungültige Importdatei"),
+    ("INVALID_LOG_FILTER", "Ungültiger Logfilter"),
+    ("INVALID_NOTE", "Ungültige Notizlänge"),
+    ("INVALID_PATH_PARAMETER", "Ungültiger Pfadparameter"),
+    ("INVALID_QUERY_PARAMETER", "Ungültiger Query-Parameter"),
+    ("INVALID_ROOM", "Ungültiger Raum"),
+    ("INVALID_SCHEDULE", "publish_at muss in der Zukunft liegen"),
+    ("INVALID_SHARE_LINK", "Ungültige Gültigkeitsdauer des Links"),
+    ("INVALID_SIGNATURE", "Ungültige Signatur"),
+    ("INVALID_WEBHOOK", "Die Webhook-Registrierung ist ungültig"),
+    (
+        "JSON_DESERIALIZATION_ERROR",
+        "Der JSON-Inhalt hat nicht das erwartete Format",
+    ),
+    (
+        "JSON_VALIDITY_ERROR",
+        "Der Anfrageinhalt ist kein gültiges JSON",
+    ),
+    (
+        "MISSING_JSON_CONTENT_TYPE",
+        "Content-Type muss application/json sein",
+    ),
+    ("MISSING_SIGNATURE", "Signatur fehlt"),
+    (
+        "NOT_ACCEPTABLE",
+        "Das angeforderte Antwortformat wird nicht unterstützt",
+    ),
+    (
+        "OIDC_NOT_CONFIGURED",
+        "Die Anmeldung über einen Identitätsanbieter ist nicht eingerichtet",
+    ),
+    (
+        "PAYLOAD_TOO_LARGE",
+        "Der Anfrageinhalt überschreitet die Größenbeschränkung",
+    ),
+    (
+        "PROGRESS_IS_COMPUTED",
+        "Der Fortschritt wird aus der Stufen-Checkliste berechnet",
+    ),
+    (
+        "RANGE_EXCEEDED_ERROR",
+        "Der Prozentsatz muss zwischen 0 und 100 liegen",
+    ),
+    (
+        "REQUEST_TIMEOUT",
+        "Die Verarbeitung der Anfrage hat zu lange gedauert",
+    ),
+    (
+        "RESUME_SESSION_EXPIRED",
+        "Das Fortsetzungstoken ist unbekannt oder abgelaufen; bitte neu abonnieren",
+    ),
+    ("ROOM_NOT_FOUND", "Raum nicht gefunden"),
+    ("ROUTE_NOT_FOUND", "Endpunkt nicht gefunden"),
+    (
+        "SCHEDULED_EVENT_NOT_FOUND",
+        "Geplantes Event nicht gefunden",
+    ),
+    (
+        "SCHEMA_VIOLATION",
+        "Die Nutzdaten des Ereignisses entsprechen nicht dem konfigurierten Schema",
+    ),
+    (
+        "SERVER_DRAINING",
+        "Der Server baut Verbindungen ab; bitte über den Load Balancer neu verbinden",
+    ),
+    (
+        "SERVICE_OVERLOADED",
+        "Der Server ist überlastet, bitte später erneut versuchen",
+    ),
+    (
+        "SHARE_LINK_NOT_FOUND",
+        "Der Tracking-Link ist unbekannt oder abgelaufen",
+    ),
+    ("SIGNATURE_EXPIRED", "Die Signatur ist abgelaufen"),
+    ("SIGNATURE_REPLAYED", "Die Signatur wurde bereits verwendet"),
+    ("STAGE_NOT_FOUND", "Die Stufe ist nicht Teil der Checkliste"),
+    (
+        "STEP_TOO_LARGE",
+        "Der Fortschritt hat sich stärker als erlaubt geändert; bestätigen Sie den Sprung",
+    ),
+    (
+        "SUBSCRIBER_QUOTA_EXCEEDED",
+        "Der Antrag hat die Höchstzahl an Abonnenten erreicht",
+    ),
+    (
+        "TENANT_FORBIDDEN",
+        "Diese Aktion ist für Ihren Mandanten nicht verfügbar",
+    ),
+    (
+        "TIMESTAMP_IN_FUTURE",
+        "Der Zeitpunkt liegt zu weit in der Zukunft",
+    ),
+    ("UNAUTHORIZED", "Zugriffstoken fehlt oder ist ungültig"),
+    ("UNKNOWN_ERROR", "Ein unerwarteter Fehler ist aufgetreten"),
+    ("UNKNOWN_FIELD", "Der Anfragetext enthält unbekannte Felder"),
+    (
+        "UNKNOWN_STAGE",
+        "Diese Phase gibt es für diesen Visumtyp nicht",
+    ),
+    (
+        "VERSION_CONFLICT",
+        "Die Daten wurden zwischenzeitlich geändert; bitte neu laden und erneut versuchen",
+    ),
+    ("WEBHOOK_NOT_FOUND", "Webhook nicht gefunden"),
+];
+
+/// Picks the supported language with the highest q-value, English when none matches.
+pub fn negotiate(accept_language: Option<&str>) -> Locale {
+    let Some(accept_language) = accept_language else {
+        return Locale::default();
+    };
+    let mut best: Option<(Locale, f32)> = None;
+    for range in accept_language.split(',') {
+        let mut params = range.split(';');
+        let Some(locale) = params.next().and_then(Locale::from_tag) else {
+            continue;
+        };
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((locale, quality));
+        }
+    }
+    return best.map(|(locale, _)| locale).unwrap_or_default();
+}
+
+/// The language negotiated for the request being handled; English outside a request.
+pub fn current() -> Locale {
+    return LOCALE.try_with(|locale| *locale).unwrap_or_default();
+}
+
+/// Looks up `key` in the locale's catalog, without falling back to English.
+pub fn lookup(locale: Locale, key: &str) -> Option<&'static str> {
+    return locale
+        .catalog()
+        .iter()
+        .find(|(id, _)| *id == key)
+        .map(|(_, text)| *text);
+}
+
+/// Catalog text with `{name}` placeholders filled in, falling back to English and then
+/// to the key itself.
+pub fn text_in(locale: Locale, key: &str, args: &[(&str, String)]) -> String {
+    let template = lookup(locale, key)
+        .or_else(|| lookup(Locale::En, key))
+        .unwrap_or(key);
+    let mut text = template.to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    return text;
+}
+
+pub fn text(key: &str, args: &[(&str, String)]) -> String {
+    return text_in(current(), key, args);
+}
+
+/// Makes the `Accept-Language` choice available to everything the request runs, so
+/// error envelopes and messages can be localized without threading it through handlers.
+pub async fn localize(request: Request, next: Next) -> Response {
+    let locale = negotiate(
+        request
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok()),
+    );
+    let mut response = LOCALE.scope(locale, next.run(request)).await;
+
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_LANGUAGE,
+        HeaderValue::from_static(locale.tag()),
+    );
+    headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    return response;
+}
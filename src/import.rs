@@ -0,0 +1,305 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{Json, body::Body, extract::State};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    application::Application,
+    auth::{Admin, Principal},
+    content::CsvReader,
+    error::{self, AppError, ErrorCode, ErrorDetail},
+    event::{AppEvent, EventResponse, Priority, StoredEvent, Visibility},
+    quota,
+    state::AppState,
+    visa_type,
+};
+
+/// Columns the header row may name, in any order. Only `applicant_name` is required.
+const COLUMNS: &[&str] = &[
+    "applicant_name",
+    "applicant_email",
+    "visa_type",
+    "tenant",
+    "created_at",
+    "percentage",
+    "stage",
+    "updated_at",
+];
+
+fn invalid(message: impl Into<String>) -> AppError {
+    return AppError::new(ErrorCode::InvalidImport, message);
+}
+
+/// Where each column sits in a record, read from the header row.
+struct Header {
+    positions: HashMap<String, usize>,
+    width: usize,
+}
+
+impl Header {
+    fn parse(record: Vec<String>) -> Result<Self, AppError> {
+        let names: Vec<String> = record
+            .iter()
+            .map(|name| name.trim().to_lowercase())
+            .collect();
+        let fields: Map<String, Value> = names
+            .iter()
+            .map(|name| (name.clone(), Value::Null))
+            .collect();
+        AppError::check_fields(error::unknown_fields(&Value::Object(fields), "", COLUMNS))?;
+        let mut positions = HashMap::new();
+        for (position, name) in names.iter().enumerate() {
+            if positions.insert(name.clone(), position).is_some() {
+                return Err(invalid(format!("Column {} appears twice", name)));
+            }
+        }
+        if !positions.contains_key("applicant_name") {
+            return Err(invalid("The header row has no applicant_name column"));
+        }
+        return Ok(Self {
+            positions,
+            width: names.len(),
+        });
+    }
+}
+
+/// A row's value of `column`, `None` when the column is missing or the cell blank.
+fn cell<'a>(header: &Header, record: &'a [String], column: &str) -> Option<&'a str> {
+    return header
+        .positions
+        .get(column)
+        .map(|position| record[*position].trim())
+        .filter(|value| !value.is_empty());
+}
+
+/// Accepts RFC 3339 timestamps and, as spreadsheets tend to hold, plain dates.
+fn parse_time(value: Option<&str>, column: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(at.with_timezone(&Utc)));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(Some(date.and_hms_opt(0, 0, 0).unwrap().and_utc()));
+    }
+    return Err(invalid(format!(
+        "{} must be a date (YYYY-MM-DD) or an RFC 3339 timestamp, got {}",
+        column, value
+    )));
+}
+
+/// The event seeding the case's latest status, when the row has one.
+fn initial_event(
+    state: &AppState,
+    header: &Header,
+    record: &[String],
+    application: &Application,
+) -> Result<Option<AppEvent>, AppError> {
+    let stage = cell(header, record, "stage");
+    let Some(percentage) = cell(header, record, "percentage") else {
+        if stage.is_some() {
+            return Err(invalid("A stage needs a percentage"));
+        }
+        return Ok(None);
+    };
+    let percentage: f64 = percentage
+        .parse()
+        .map_err(|_| invalid(format!("percentage must be a number, got {}", percentage)))?;
+    if !(0.0..=100.0).contains(&percentage) {
+        return Err(AppError::new(
+            ErrorCode::RangeExceededError,
+            format!(
+                "Percentage range is exceeded. It should be within 0-100, but got {}",
+                percentage
+            ),
+        ));
+    }
+    if !state.config().checklist.stages.is_empty() {
+        return Err(AppError::from(ErrorCode::ProgressIsComputed));
+    }
+    if let (Some(visa_type), Some(stage)) = (&application.visa_type, stage) {
+        visa_type::check_type_stage(state, visa_type, stage)?;
+    }
+    return Ok(Some(AppEvent {
+        application_id: Some(application.id),
+        percentage,
+        occurred_at: parse_time(cell(header, record, "updated_at"), "updated_at")?,
+        ttl_secs: None,
+        priority: Priority::default(),
+        visibility: Visibility::default(),
+        stage: stage.map(str::to_string),
+    }));
+}
+
+fn import_row(
+    state: &AppState,
+    admin: &Principal,
+    header: &Header,
+    record: Vec<String>,
+) -> Result<Uuid, AppError> {
+    if record.len() != header.width {
+        return Err(invalid(format!(
+            "Expected {} fields like the header row, got {}",
+            header.width,
+            record.len()
+        )));
+    }
+    let applicant_name = cell(header, &record, "applicant_name")
+        .ok_or_else(|| invalid("applicant_name is empty"))?;
+    let tenant = cell(header, &record, "tenant")
+        .map(str::to_string)
+        .or_else(|| admin.tenant.clone());
+    if !admin.can_access(tenant.as_deref()) {
+        return Err(AppError::new(
+            ErrorCode::TenantForbidden,
+            "Applications can only be imported for your own tenant",
+        ));
+    }
+    let now = Utc::now();
+    let application = Application {
+        id: Uuid::new_v4(),
+        tenant,
+        applicant_name: applicant_name.to_string(),
+        applicant_email: cell(header, &record, "applicant_email").map(str::to_string),
+        visa_type: cell(header, &record, "visa_type").map(str::to_string),
+        created_at: parse_time(cell(header, &record, "created_at"), "created_at")?.unwrap_or(now),
+        version: 1,
+        archived_at: None,
+        paused_at: None,
+        completed_stages: Vec::new(),
+        documents: Vec::new(),
+        appointments: Vec::new(),
+        notes: Vec::new(),
+    };
+    let event = initial_event(state, header, &record, &application)?;
+
+    let quotas = state.config().quotas;
+    let id = application.id;
+    state.store.write(|data| -> Result<(), AppError> {
+        quota::check_active_applications(&quotas, data, application.tenant.as_deref())?;
+        data.applications.insert(id, application);
+        if let Some(event) = event {
+            let event_id = data.next_event_id();
+            let seq = data.next_seq(Some(id));
+            let stored = StoredEvent {
+                id: event_id,
+                seq,
+                at: now,
+                actor: Some(admin.subject.clone()),
+                event,
+                retracted: None,
+            };
+            data.track_progress(&stored);
+            data.events.push(stored);
+        }
+        return Ok(());
+    })?;
+    return Ok(id);
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportedRow {
+    line: usize,
+    application_id: Uuid,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FailedRow {
+    line: usize,
+    error: ErrorDetail,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    imported: usize,
+    applications: Vec<ImportedRow>,
+    /// Rows that were skipped; fix and upload just these again.
+    failed: Vec<FailedRow>,
+}
+
+impl ImportReport {
+    fn add(&mut self, line: usize, result: Result<Uuid, AppError>) {
+        match result {
+            Ok(application_id) => {
+                self.imported += 1;
+                self.applications.push(ImportedRow {
+                    line,
+                    application_id,
+                });
+            }
+            Err(err) => self.failed.push(FailedRow {
+                line,
+                error: err.into_detail(),
+            }),
+        }
+    }
+}
+
+/// Reads the header from the first record and imports every one after it.
+fn process(
+    state: &AppState,
+    admin: &Principal,
+    header: &mut Option<Header>,
+    report: &mut ImportReport,
+    line: usize,
+    record: Vec<String>,
+) -> Result<(), AppError> {
+    match header {
+        Some(header) => report.add(line, import_row(state, admin, header, record)),
+        None => *header = Some(Header::parse(record)?),
+    }
+    return Ok(());
+}
+
+/// `POST /admin/import`: creates applications from a CSV export of the agency's
+/// spreadsheet, one per row after the header. Rows with a `percentage` also get an event
+/// with that status, recorded as history only: nothing is broadcast and no webhooks fire.
+/// The upload is processed as it streams in; rows that fail are reported and skipped.
+pub async fn import(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    body: Body,
+) -> Result<Json<EventResponse<ImportReport>>, AppError> {
+    let mut reader = CsvReader::default();
+    let mut header: Option<Header> = None;
+    let mut report = ImportReport::default();
+    // rows imported before a broken line stay, so the error has to say how far it got
+    let broken = |message: String, report: &ImportReport| {
+        if report.imported == 0 {
+            return invalid(message);
+        }
+        return invalid(format!(
+            "{}; the {} rows before it were imported",
+            message, report.imported
+        ));
+    };
+
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk =
+            chunk.map_err(|err| broken(format!("Failed to read the upload: {}", err), &report))?;
+        let records = reader.push(&chunk).map_err(|err| broken(err, &report))?;
+        for (line, record) in records {
+            process(&state, &admin, &mut header, &mut report, line, record)?;
+        }
+    }
+    if let Some((line, record)) = reader.finish().map_err(|err| broken(err, &report))? {
+        process(&state, &admin, &mut header, &mut report, line, record)?;
+    }
+    if header.is_none() {
+        return Err(invalid("The file is empty; expected a header row"));
+    }
+
+    tracing::info!(
+        "{} imported {} applications, {} rows failed",
+        admin.subject,
+        report.imported,
+        report.failed.len()
+    );
+    return Ok(Json(EventResponse::ok(report)));
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::DateTime;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, jwk::JwkSet};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::{
+    auth::{Principal, Role},
+    config::JwtConfig,
+    outbound::HttpClient,
+};
+
+/// Refetching on an unknown `kid` is capped so forged tokens can't hammer the IdP.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+struct CachedKeys {
+    keys: HashMap<String, DecodingKey>,
+    fetched_at: Option<Instant>,
+    /// Last refresh, successful or not, so an unreachable IdP is not retried per request.
+    attempted_at: Option<Instant>,
+}
+
+/// Validates bearer JWTs against keys published at a JWKS URL. Keys are cached for
+/// `cache_secs` and refreshed early when a token references a key id we haven't seen,
+/// which is how the IdP's key rotation shows up.
+pub struct JwtVerifier {
+    config: JwtConfig,
+    client: HttpClient,
+    cache: RwLock<CachedKeys>,
+}
+
+impl JwtVerifier {
+    pub fn new(config: JwtConfig, client: HttpClient) -> Self {
+        return Self {
+            config,
+            client,
+            cache: RwLock::new(CachedKeys {
+                keys: HashMap::new(),
+                fetched_at: None,
+                attempted_at: None,
+            }),
+        };
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<Principal, String> {
+        let claims = self.claims(token).await?;
+        let subject = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or("Token has no subject")?
+            .to_string();
+        let roles = claim_at(&claims, &self.config.roles_claim)
+            .and_then(Value::as_array)
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| serde_json::from_value::<Role>(value.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let tenant = self
+            .config
+            .tenant_claim
+            .as_deref()
+            .and_then(|path| claim_at(&claims, path))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let expires_at = claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .and_then(|exp| DateTime::from_timestamp(exp, 0));
+
+        return Ok(Principal {
+            subject,
+            roles,
+            tenant,
+            anonymous: false,
+            application_id: None,
+            expires_at,
+        });
+    }
+
+    /// The claims of a token whose signature, expiry, issuer and audience check out.
+    pub async fn claims(&self, token: &str) -> Result<Value, String> {
+        let header = jsonwebtoken::decode_header(token).map_err(|err| err.to_string())?;
+        if !is_asymmetric(header.alg) {
+            return Err(format!("Unsupported token algorithm {:?}", header.alg));
+        }
+        let kid = header.kid.ok_or("Token has no key id")?;
+
+        let key = self.key(&kid).await?;
+        let mut validation = Validation::new(header.alg);
+        match &self.config.issuer {
+            Some(issuer) => validation.set_issuer(&[issuer]),
+            None => validation.iss = None,
+        }
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        return jsonwebtoken::decode::<Value>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| err.to_string());
+    }
+
+    async fn key(&self, kid: &str) -> Result<DecodingKey, String> {
+        {
+            let cache = self.cache.read().await;
+            let fresh = cache
+                .fetched_at
+                .is_some_and(|at| at.elapsed() < Duration::from_secs(self.config.cache_secs));
+            if let Some(key) = cache.keys.get(kid)
+                && fresh
+            {
+                return Ok(key.clone());
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        // another request may have refreshed while we waited for the lock
+        let recently_attempted = cache
+            .attempted_at
+            .is_some_and(|at| at.elapsed() < MIN_REFRESH_INTERVAL);
+        if !recently_attempted {
+            cache.attempted_at = Some(Instant::now());
+            match self.fetch().await {
+                Ok(keys) => {
+                    cache.keys = keys;
+                    cache.fetched_at = Some(Instant::now());
+                }
+                // keep serving the stale keys if the IdP is briefly unreachable
+                Err(err) => tracing::error!("Failed to refresh JWKS: {}", err),
+            }
+        }
+
+        return cache
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| format!("Unknown key id {}", kid));
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, DecodingKey>, String> {
+        let jwks: JwkSet = self
+            .client
+            .send(self.client.get(&self.config.jwks_url))
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| err.to_string())?
+            .json()
+            .await
+            .map_err(|err| err.to_string())?;
+
+        let mut keys = HashMap::new();
+        for jwk in &jwks.keys {
+            let Some(kid) = &jwk.common.key_id else {
+                continue;
+            };
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => {
+                    keys.insert(kid.clone(), key);
+                }
+                Err(err) => tracing::warn!("Skipping JWK {}: {}", kid, err),
+            }
+        }
+        tracing::debug!("Loaded {} keys from {}", keys.len(), self.config.jwks_url);
+        return Ok(keys);
+    }
+}
+
+fn is_asymmetric(alg: Algorithm) -> bool {
+    return !matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512);
+}
+
+/// Follows a dotted path such as Keycloak's `realm_access.roles`.
+pub fn claim_at<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+    return path
+        .split('.')
+        .try_fold(claims, |value, segment| value.get(segment));
+}
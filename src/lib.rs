@@ -0,0 +1,6 @@
+#![allow(clippy::needless_return)]
+
+//! Library side of the tracker: a client other services can use to publish events and
+//! follow the SSE stream. The server itself lives in the binary.
+
+pub mod client;
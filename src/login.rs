@@ -0,0 +1,370 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    Json,
+    extract::State,
+    http::{Method, StatusCode, request::Parts},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tower_sessions::{
+    Expiry, Session, SessionManagerLayer, SessionStore,
+    cookie::{
+        SameSite,
+        time::{Duration, OffsetDateTime},
+    },
+    session::{Id, Record},
+    session_store,
+};
+use uuid::Uuid;
+
+use crate::{
+    api_key,
+    auth::{self, Principal, Role},
+    config::{AuthMode, Config, Profile},
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+};
+
+const COOKIE_NAME: &str = "visa_tracker_session";
+/// Session entry holding the logged-in [`Principal`].
+const PRINCIPAL_KEY: &str = "principal";
+/// Session entry recording the [`Credential`] the session was started with.
+const CREDENTIAL_KEY: &str = "credential";
+/// Session entry holding when that credential lapses, for credentials that do.
+const EXPIRES_AT_KEY: &str = "expires_at";
+/// Granularity of the stored expiry of sessions that are kept alive by use.
+const EXTEND_STEP: chrono::Duration = chrono::Duration::minutes(1);
+/// Session entry holding the token that state-changing requests echo in [`CSRF_HEADER`].
+const CSRF_KEY: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// A login session as persisted. Like API keys, only the SHA-256 of the session id (the
+/// cookie value) is stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginSession {
+    hash: String,
+    data: HashMap<String, Value>,
+    expires_at: DateTime<Utc>,
+}
+
+/// What a session was started with. Checked again on every request, so revoking an API
+/// key or removing a token from the configuration also ends the sessions it started.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Credential {
+    ApiKey {
+        id: Uuid,
+    },
+    /// One of `auth.tokens`, by subject.
+    Token {
+        subject: String,
+    },
+    /// A JWT or an OIDC sign-in, which cannot be checked again; the session ends when the
+    /// token would have expired.
+    Federated,
+}
+
+fn hash_id(id: &Id) -> String {
+    return hex::encode(Sha256::digest(id.to_string().as_bytes()));
+}
+
+fn to_chrono(at: OffsetDateTime) -> DateTime<Utc> {
+    return DateTime::from_timestamp(at.unix_timestamp(), 0).unwrap_or(DateTime::UNIX_EPOCH);
+}
+
+/// Keeps the sessions of the staff dashboard in the store, next to API keys, so logins
+/// survive a restart.
+#[derive(Clone)]
+pub struct StoredSessions(Arc<AppState>);
+
+impl fmt::Debug for StoredSessions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str("StoredSessions");
+    }
+}
+
+impl StoredSessions {
+    fn put(&self, record: &Record) {
+        let hash = hash_id(&record.id);
+        let session = LoginSession {
+            hash: hash.clone(),
+            data: record.data.clone(),
+            expires_at: to_chrono(record.expiry_date),
+        };
+        let now = Utc::now();
+        self.0.store.write(|data| {
+            data.login_sessions
+                .retain(|stored| stored.hash != hash && stored.expires_at > now);
+            data.login_sessions.push(session);
+        });
+    }
+}
+
+#[async_trait]
+impl SessionStore for StoredSessions {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        while self.load(&record.id).await?.is_some() {
+            record.id = Id::default();
+        }
+        self.put(record);
+        return Ok(());
+    }
+
+    /// Every request of a session pushes its expiry back; only moves of at least
+    /// [`EXTEND_STEP`] are written, so browsing the dashboard does not rewrite the store.
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let hash = hash_id(&record.id);
+        let expires_at = to_chrono(record.expiry_date);
+        let unchanged = self.0.store.read(|data| {
+            data.login_sessions.iter().any(|stored| {
+                stored.hash == hash
+                    && stored.data == record.data
+                    && (expires_at - stored.expires_at).abs() < EXTEND_STEP
+            })
+        });
+        if !unchanged {
+            self.put(record);
+        }
+        return Ok(());
+    }
+
+    async fn load(&self, id: &Id) -> session_store::Result<Option<Record>> {
+        let hash = hash_id(id);
+        let now = Utc::now();
+        let session = self.0.store.read(|data| {
+            data.login_sessions
+                .iter()
+                .find(|stored| stored.hash == hash && stored.expires_at > now)
+                .cloned()
+        });
+        return Ok(session.map(|session| Record {
+            id: *id,
+            data: session.data,
+            expiry_date: OffsetDateTime::from_unix_timestamp(session.expires_at.timestamp())
+                .unwrap_or(OffsetDateTime::UNIX_EPOCH),
+        }));
+    }
+
+    async fn delete(&self, id: &Id) -> session_store::Result<()> {
+        let hash = hash_id(id);
+        self.0
+            .store
+            .write(|data| data.login_sessions.retain(|stored| stored.hash != hash));
+        return Ok(());
+    }
+}
+
+/// Issues the `HttpOnly`, `SameSite=Strict` session cookie. It is marked `Secure` whenever
+/// the server is reached over TLS, which `prod` assumes even when a proxy terminates it.
+pub fn layer(config: &Config, state: Arc<AppState>) -> SessionManagerLayer<StoredSessions> {
+    let secure = config.profile == Profile::Prod || config.server.tls.is_some();
+    return SessionManagerLayer::new(StoredSessions(state))
+        .with_name(COOKIE_NAME)
+        .with_http_only(true)
+        .with_same_site(SameSite::Strict)
+        .with_secure(secure)
+        .with_expiry(Expiry::OnInactivity(Duration::seconds(
+            config.auth.session_ttl_secs as i64,
+        )));
+}
+
+async fn get<T: serde::de::DeserializeOwned>(session: &Session, key: &str) -> Option<T> {
+    match session.get::<T>(key).await {
+        Ok(value) => return value,
+        Err(err) => {
+            tracing::warn!("Failed to load login session: {}", err);
+            return None;
+        }
+    }
+}
+
+/// The caller logged in to the request's session, if any. Requests that may change
+/// something must carry the session's CSRF token, since browsers attach the cookie to
+/// requests other sites trigger too.
+pub async fn principal(state: &AppState, parts: &Parts) -> Result<Option<Principal>, AppError> {
+    let Some(session) = parts.extensions.get::<Session>() else {
+        return Ok(None);
+    };
+    let Some(stored) = get::<Principal>(session, PRINCIPAL_KEY).await else {
+        return Ok(None);
+    };
+    let Some(principal) = revalidate(state, session, stored).await else {
+        tracing::debug!("Ending a login session whose credential is no longer valid");
+        if let Err(err) = session.flush().await {
+            tracing::warn!("Failed to end login session: {}", err);
+        }
+        return Ok(None);
+    };
+    // logging in again replaces the session rather than acting with it
+    let login = parts.uri.path().ends_with("/login");
+    if login || matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(Some(principal));
+    }
+    let expected = get::<String>(session, CSRF_KEY).await;
+    let presented = parts
+        .headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    // compared as digests, so the time taken says nothing about the token
+    let matches = match (expected, presented) {
+        (Some(expected), Some(presented)) => {
+            Sha256::digest(expected.as_bytes()) == Sha256::digest(presented.as_bytes())
+        }
+        _ => false,
+    };
+    if !matches {
+        return Err(AppError::new(
+            ErrorCode::InvalidCsrfToken,
+            "Requests signed in with the session cookie must send its X-CSRF-Token",
+        ));
+    }
+    return Ok(Some(principal));
+}
+
+/// The session's caller as of now, or `None` once the credential it was started with has
+/// expired or been revoked. Sessions from before credentials were recorded are ended too.
+async fn revalidate(state: &AppState, session: &Session, stored: Principal) -> Option<Principal> {
+    let expires_at = get::<DateTime<Utc>>(session, EXPIRES_AT_KEY).await;
+    if expires_at.is_some_and(|at| at <= Utc::now()) {
+        return None;
+    }
+    let current = match get::<Credential>(session, CREDENTIAL_KEY).await? {
+        Credential::ApiKey { id } => api_key::find(&state.store, id)?,
+        Credential::Token { subject } => state.auth.token_principal(&subject)?,
+        Credential::Federated => stored,
+    };
+    return Some(Principal {
+        expires_at,
+        ..current
+    });
+}
+
+/// Logs `principal` in to `session` and hands it a fresh CSRF token. The session ends with
+/// the credential, at the latest when it expires.
+pub async fn start(
+    state: &AppState,
+    session: &Session,
+    principal: &Principal,
+    credential: Credential,
+) -> Result<(), AppError> {
+    if state.auth.mode == AuthMode::None {
+        return Err(AppError::new(
+            ErrorCode::Forbidden,
+            "Logging in needs auth.mode token or jwt; without auth every caller is an admin",
+        ));
+    }
+    // a fresh id, so a session id planted before login is worthless
+    session.cycle_id().await.map_err(session_error)?;
+    session
+        .insert(PRINCIPAL_KEY, principal)
+        .await
+        .map_err(session_error)?;
+    session
+        .insert(CREDENTIAL_KEY, credential)
+        .await
+        .map_err(session_error)?;
+    if let Some(expires_at) = principal.expires_at {
+        session
+            .insert(EXPIRES_AT_KEY, expires_at)
+            .await
+            .map_err(session_error)?;
+        let ttl = chrono::Duration::seconds(state.config().auth.session_ttl_secs as i64);
+        if expires_at < Utc::now() + ttl {
+            let at = OffsetDateTime::from_unix_timestamp(expires_at.timestamp())
+                .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+            session.set_expiry(Some(Expiry::AtDateTime(at)));
+        }
+    }
+    let mut secret = [0u8; 32];
+    rand::rng().fill_bytes(&mut secret);
+    session
+        .insert(CSRF_KEY, hex::encode(secret))
+        .await
+        .map_err(session_error)?;
+    tracing::info!("{} logged in", principal.subject);
+    return Ok(());
+}
+
+fn session_error(err: tower_sessions::session::Error) -> AppError {
+    tracing::error!("Failed to update login session: {}", err);
+    return AppError::from(ErrorCode::UnknownError);
+}
+
+/// Body of `POST /login`: any credential accepted as a bearer token.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LoginRequest {
+    token: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LoginView {
+    subject: String,
+    roles: Vec<Role>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    expires_at: DateTime<Utc>,
+    /// Send as `X-CSRF-Token` with every request that is not a `GET`.
+    csrf_token: String,
+}
+
+async fn view(session: &Session, principal: Principal) -> Result<LoginView, AppError> {
+    let csrf_token = get::<String>(session, CSRF_KEY)
+        .await
+        .ok_or_else(|| AppError::new(ErrorCode::Unauthorized, "Not logged in"))?;
+    return Ok(LoginView {
+        subject: principal.subject,
+        roles: principal.roles,
+        tenant: principal.tenant,
+        expires_at: to_chrono(session.expiry_date()),
+        csrf_token,
+    });
+}
+
+/// Exchanges a token for a session cookie, so the staff dashboard does not have to put
+/// the token into stream URLs. The session expires after `auth.session_ttl_secs` without
+/// a request.
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    WithRejection(Json(body), _): WithRejection<Json<LoginRequest>, AppError>,
+) -> Result<Json<EventResponse<LoginView>>, AppError> {
+    let principal = auth::resolve_token(&state, &body.token).await?;
+    if principal.application_id.is_some() {
+        return Err(AppError::new(
+            ErrorCode::Forbidden,
+            "Tracking links cannot be used to log in",
+        ));
+    }
+    let credential = match api_key::id_of(&principal) {
+        Some(id) => Credential::ApiKey { id },
+        None if state.auth.mode == AuthMode::Token => Credential::Token {
+            subject: principal.subject.clone(),
+        },
+        None => Credential::Federated,
+    };
+    start(&state, &session, &principal, credential).await?;
+    return Ok(Json(EventResponse::ok(view(&session, principal).await?)));
+}
+
+/// `GET /login`: the session's caller and CSRF token, e.g. for a dashboard page reloaded
+/// after an OIDC sign-in.
+pub async fn current(session: Session) -> Result<Json<EventResponse<LoginView>>, AppError> {
+    let principal = get::<Principal>(&session, PRINCIPAL_KEY)
+        .await
+        .ok_or_else(|| AppError::new(ErrorCode::Unauthorized, "Not logged in"))?;
+    return Ok(Json(EventResponse::ok(view(&session, principal).await?)));
+}
+
+/// Ends the session and clears the cookie.
+pub async fn logout(session: Session) -> Result<StatusCode, AppError> {
+    session.flush().await.map_err(session_error)?;
+    return Ok(StatusCode::NO_CONTENT);
+}
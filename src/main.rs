@@ -1,58 +1,304 @@
+#![allow(clippy::needless_return)]
+
+mod announcement;
+mod api;
+mod api_key;
+mod application;
+mod appointment;
+mod audit;
+mod auth;
+mod backup;
+mod bench;
+mod breaker;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod checklist;
+mod cli;
+mod client_ip;
+mod config;
+mod content;
+mod cors;
+mod cron;
+mod dev;
+mod discovery;
+mod document;
+mod drain;
+mod error;
 mod event;
+mod fanout;
+mod flags;
+mod frontend;
+mod history;
+mod i18n;
+mod import;
+mod jwks;
+mod login;
+mod note;
+mod oidc;
+mod outbound;
+mod outbox;
+mod payload_schema;
+mod pii;
+mod quota;
+mod reload;
+mod resume;
+mod retention;
+mod retraction;
+mod room;
+mod schedule;
+mod server;
+mod share;
+mod signature;
+mod state;
+mod stats;
+mod store;
+mod systemd;
+mod tasks;
+mod telemetry;
+mod version;
+mod viewers;
+mod visa_type;
+mod webhook;
 
-use std::{path::PathBuf, sync::Arc};
+use std::{process::ExitCode, sync::Arc, time::Duration};
 
 use axum::{
     Router,
-    http::Method,
-    routing::{get, get_service, post},
-};
-use tower_http::{
-    cors::{Any, CorsLayer},
-    services::ServeFile,
-    trace::TraceLayer,
+    error_handling::HandleErrorLayer,
+    extract::Request,
+    middleware,
+    routing::{any, get},
 };
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use clap::Parser;
+use sentry::integrations::tower::{NewSentryLayer, SentryHttpLayer};
+use tower::{ServiceBuilder, limit::GlobalConcurrencyLimitLayer};
+use tower_http::{compression::CompressionLayer, services::ServeDir, trace::TraceLayer};
 
-use crate::event::AppState;
+use crate::{
+    cli::{Cli, Command},
+    config::Config,
+    state::AppState,
+    telemetry::LogFilter,
+};
 
 #[tokio::main]
-async fn main() {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                format!("{}=debug,tower_http=debug", env!("CARGO_CRATE_NAME")).into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+async fn main() -> ExitCode {
+    let command = Cli::parse().command.unwrap_or(Command::Serve {
+        config: None,
+        dev: false,
+    });
+    if let Command::Serve { config, dev } = command {
+        let config = Config::load(config);
+        let (log_filter, _log_guard) = telemetry::init_tracing(config.logging.file.as_ref());
+        serve(config, dev, log_filter).await;
+        return ExitCode::SUCCESS;
+    }
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:4000")
-        .await
-        .unwrap();
-    let app = app();
+    let (_, _log_guard) = telemetry::init_tracing(None);
+    match command {
+        Command::Serve { .. } => unreachable!("handled above"),
+        Command::Backup { config, out } => return backup::create(config, out),
+        Command::Restore {
+            config,
+            input,
+            force,
+        } => return backup::restore(config, input, force),
+        Command::Bench { target, options } => {
+            return bench::run(target.client(), options).await;
+        }
+        Command::Send {
+            target,
+            percentage,
+            application_id,
+            ttl_secs,
+            confirm_large_jump,
+        } => {
+            return cli::send(
+                target,
+                percentage,
+                application_id,
+                ttl_secs,
+                confirm_large_jump,
+            )
+            .await;
+        }
+        Command::Tail {
+            target,
+            application_id,
+        } => return cli::tail(target, application_id).await,
+    }
+}
+
+async fn serve(config: Config, dev: bool, log_filter: LogFilter) {
+    let _sentry = telemetry::init_sentry(&config);
+    let build = version::build_info();
+    tracing::info!(
+        "Starting visa-tracker {} (commit {}{}, built {}, rustc {})",
+        build.version,
+        build.git_commit,
+        if build.git_dirty { ", dirty" } else { "" },
+        build.build_timestamp,
+        build.rustc_version
+    );
+    tracing::debug!("Running with {:?} profile", config.profile);
+
+    // under socket activation systemd owns the address and `listen_addr` is ignored
+    let listener = match systemd::inherited_listener() {
+        Some(listener) => tokio::net::TcpListener::from_std(listener).unwrap(),
+        None => tokio::net::TcpListener::bind(&config.listen_addr)
+            .await
+            .unwrap(),
+    };
+    let app_state = Arc::new(AppState::new(
+        &config,
+        telemetry::install(),
+        log_filter,
+        dev,
+    ));
+    let flush_interval = Duration::from_secs(config.store.flush_interval_secs);
+    tasks::spawn(&app_state, "announcements", announcement::run);
+    tasks::spawn(&app_state, "store_flush", move |app_state| {
+        flush_store(app_state, flush_interval)
+    });
+    tasks::spawn(&app_state, "channel_sweep", fanout::sweep_channels);
+    if config.sse.fanout_workers > 0 {
+        tasks::spawn(&app_state, "fanout_workers", fanout::run_workers);
+    }
+    tasks::spawn(&app_state, "outbox", outbox::run);
+    tasks::spawn(&app_state, "config_reload", reload::on_sighup);
+    tasks::spawn(&app_state, "retention", retention::run);
+    tasks::spawn(&app_state, "scheduler", schedule::run);
+    tasks::spawn(&app_state, "viewers", viewers::run);
+    if dev {
+        tracing::info!("Dev mode: watching {}", frontend::assets_dir().display());
+        tasks::spawn(&app_state, "asset_watcher", dev::watch_assets);
+    }
+
+    let app = app(&config, app_state.clone(), dev);
     tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    systemd::notify("READY=1");
+    if let Err(err) = server::run(
+        listener,
+        app,
+        &config.server,
+        shutdown_signal(app_state.clone()),
+    )
+    .await
+    {
+        tracing::error!("Failed to serve: {}", err);
+    }
+
+    if let Err(err) = app_state.store.flush() {
+        tracing::error!("Failed to flush store on shutdown: {}", err);
+    }
 }
 
-fn app() -> Router {
-    let assets_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
-    let static_files_service = ServeFile::new(assets_dir.clone().join("index.html"));
-    let fallback_service = ServeFile::new(assets_dir.clone().join("fallback.html"));
-
-    let app_state = Arc::new(AppState::new());
-
-    // ref: https://dev.to/amaendeepm/axum-in-rus-flexibility-cors-control-and-tower-power-4ich
-    let cors_layer = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST]);
-
-    return Router::new()
-        .route("/events", get(event::subscribe))
-        .route("/events/send", post(event::send))
-        .route("/", get_service(static_files_service))
-        .fallback_service(fallback_service)
-        .layer(TraceLayer::new_for_http())
-        .layer(cors_layer)
+async fn flush_store(app_state: Arc<AppState>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = app_state.store.flush() {
+            tracing::error!("Failed to flush store: {}", err);
+        }
+    }
+}
+
+/// Resolves on SIGINT or SIGTERM, after asking every stream to reconnect elsewhere so the
+/// connections can close.
+async fn shutdown_signal(app_state: Arc<AppState>) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.unwrap();
+    };
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .unwrap()
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::debug!("Shutting down");
+    systemd::notify("STOPPING=1");
+    drain::begin(&app_state, "shutdown");
+}
+
+fn app(config: &Config, app_state: Arc<AppState>, dev: bool) -> Router {
+    // `.br`/`.gz` files next to an asset are sent to clients that accept them; unknown
+    // paths go to index.html or a JSON 404, depending on who is asking
+    let assets_service = ServeDir::new(frontend::assets_dir())
+        .precompressed_br()
+        .precompressed_gzip()
+        .call_fallback_on_method_not_allowed(true)
+        .fallback(any(frontend::fallback).with_state(app_state.clone()));
+    // anything not already compressed on disk is compressed on the fly
+    let pages = Router::new()
+        .route("/", get(frontend::index))
+        .route("/index.html", get(frontend::index))
+        .route("/track/{token}", get(share::track))
+        .fallback_service(assets_service)
+        .layer(CompressionLayer::new());
+
+    let v1 = api::v1(config, &app_state);
+
+    // the global limiter shares one semaphore across every route it is layered onto
+    let load_shed_layer = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(error::handle_middleware_error))
+        .load_shed()
+        .layer(GlobalConcurrencyLimitLayer::new(
+            config.limits.max_concurrent_requests,
+        ));
+
+    let router = Router::new()
+        .route("/.well-known/visa-tracker.json", get(discovery::well_known))
+        .route("/metrics", get(telemetry::render))
+        .route("/readyz", get(tasks::ready))
+        .nest("/api/v1", v1.clone())
+        .merge(v1.layer(middleware::from_fn(api::deprecated_alias)))
+        .merge(pages)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth::authenticate,
+        ))
+        .layer(login::layer(config, app_state.clone()))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            client_ip::allow_publishers,
+        ))
+        .layer(load_shed_layer)
+        .layer(middleware::from_fn(i18n::localize))
+        .layer(middleware::from_fn(telemetry::report_server_errors))
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request| {
+                let client_ip = request
+                    .extensions()
+                    .get::<client_ip::ClientIp>()
+                    .map(|client_ip::ClientIp(ip)| ip.to_string())
+                    .unwrap_or_default();
+                tracing::debug_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    version = ?request.version(),
+                    client_ip = %client_ip,
+                )
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            client_ip::resolve,
+        ))
+        .layer(SentryHttpLayer::new())
+        .layer(NewSentryLayer::<Request>::new_from_top())
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            cors::dynamic,
+        ))
         .with_state(app_state);
+    if dev {
+        return router.layer(middleware::from_fn(dev::no_store));
+    }
+    return router;
 }
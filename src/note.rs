@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    application::{authorize, authorize_active, not_found},
+    auth::{Publisher, Viewer},
+    content::{self, CsvRecord, Negotiated},
+    error::{AppError, ErrorCode},
+    event::{EventResponse, Visibility},
+    state::AppState,
+};
+
+const MAX_NOTE_CHARS: usize = 4000;
+
+/// A free-text status note left by a case officer, kept apart from numeric progress.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Note {
+    pub id: Uuid,
+    pub author: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    /// `staff` keeps the note to case officers, in listings as well as on streams.
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+impl CsvRecord for Note {
+    const HEADER: &'static [&'static str] = &["id", "author", "text", "created_at", "visibility"];
+
+    fn fields(&self) -> Vec<String> {
+        return vec![
+            self.id.to_string(),
+            self.author.clone(),
+            self.text.clone(),
+            self.created_at.to_rfc3339(),
+            content::variant_name(&self.visibility),
+        ];
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateNoteRequest {
+    text: String,
+    #[serde(default)]
+    visibility: Visibility,
+}
+
+/// Attaches a note and sends it to the application's subscribers as `event: note`.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Json(payload), _): WithRejection<Json<CreateNoteRequest>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<Note>>), AppError> {
+    let tenant = authorize_active(&state, &publisher, id)?;
+    let text = payload.text.trim();
+    if text.is_empty() || text.chars().count() > MAX_NOTE_CHARS {
+        return Err(AppError::new(
+            ErrorCode::InvalidNote,
+            format!("Note text must be 1-{} characters", MAX_NOTE_CHARS),
+        ));
+    }
+
+    let note = Note {
+        id: Uuid::new_v4(),
+        author: publisher.subject,
+        text: text.to_string(),
+        created_at: Utc::now(),
+        visibility: payload.visibility,
+    };
+    state
+        .store
+        .write(|data| {
+            let application = data.applications.get_mut(&id)?;
+            application.notes.push(note.clone());
+            application.touch();
+            return Some(());
+        })
+        .ok_or_else(|| not_found(id))?;
+
+    let _ = state.broadcast_with(
+        note.visibility,
+        Some(id),
+        tenant,
+        "note",
+        json!({ "application_id": id, "note": note }),
+    );
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(note))));
+}
+
+/// Notes of an application, oldest first, as far as the caller may see them.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    Negotiated(format): Negotiated,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Response, AppError> {
+    authorize(&state, &viewer, id)?;
+    let notes = state
+        .store
+        .read(|data| {
+            data.applications.get(&id).map(|application| {
+                application
+                    .notes
+                    .iter()
+                    .filter(|note| viewer.can_see(note.visibility))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+        })
+        .ok_or_else(|| not_found(id))?;
+    return Ok(content::respond(format, notes));
+}
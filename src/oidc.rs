@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::WithRejection;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use tower_sessions::Session;
+
+use crate::{
+    auth::{Principal, Role},
+    config::{JwtConfig, OidcConfig},
+    error::{AppError, ErrorCode},
+    jwks::{JwtVerifier, claim_at},
+    login,
+    outbound::HttpClient,
+    state::AppState,
+};
+
+/// Binds a sign-in to the browser that started it. `SameSite=Lax`, unlike the session
+/// cookie, so it comes along when the provider redirects back.
+const STATE_COOKIE: &str = "visa_tracker_oidc";
+/// How long staff have to complete the sign-in at the provider.
+const PENDING_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// The provider's endpoints, discovered on first use.
+struct Provider {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    verifier: JwtVerifier,
+}
+
+#[derive(Deserialize, Debug)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// A sign-in sent to the provider and not yet back.
+struct Pending {
+    nonce: String,
+    code_verifier: String,
+    started_at: Instant,
+}
+
+/// Signs staff in through the agency's identity provider with the authorization-code flow
+/// and PKCE, then maps their groups to tracker roles.
+pub struct OidcClient {
+    config: OidcConfig,
+    client: HttpClient,
+    provider: OnceCell<Provider>,
+    /// Keyed by the `state` parameter.
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    return URL_SAFE_NO_PAD.encode(bytes);
+}
+
+fn provider_error(err: impl std::fmt::Display) -> AppError {
+    tracing::error!("Identity provider request failed: {}", err);
+    return AppError::from(ErrorCode::IdentityProviderError);
+}
+
+fn sign_in_failed(message: impl Into<String>) -> AppError {
+    return AppError::new(ErrorCode::Unauthorized, message);
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig, client: HttpClient) -> Self {
+        return Self {
+            config,
+            client,
+            provider: OnceCell::new(),
+            pending: Mutex::new(HashMap::new()),
+        };
+    }
+
+    async fn provider(&self) -> Result<&Provider, AppError> {
+        return self
+            .provider
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let discovery: Discovery = self
+                    .client
+                    .send(self.client.get(&url))
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(provider_error)?
+                    .json()
+                    .await
+                    .map_err(provider_error)?;
+                let verifier = JwtVerifier::new(
+                    JwtConfig {
+                        jwks_url: discovery.jwks_uri,
+                        issuer: Some(discovery.issuer),
+                        audience: Some(self.config.client_id.clone()),
+                        roles_claim: self.config.groups_claim.clone(),
+                        cache_secs: 300,
+                        tenant_claim: self.config.tenant_claim.clone(),
+                    },
+                    self.client.clone(),
+                );
+                return Ok(Provider {
+                    authorization_endpoint: discovery.authorization_endpoint,
+                    token_endpoint: discovery.token_endpoint,
+                    verifier,
+                });
+            })
+            .await;
+    }
+
+    /// Remembers a new sign-in and returns its `state` and the provider URL to send the
+    /// browser to.
+    async fn begin(&self) -> Result<(String, String), AppError> {
+        let provider = self.provider().await?;
+        let state = random_token();
+        let pending = Pending {
+            nonce: random_token(),
+            code_verifier: random_token(),
+            started_at: Instant::now(),
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.code_verifier.as_bytes()));
+        let url = reqwest::Url::parse_with_params(
+            &provider.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.config.client_id),
+                ("redirect_uri", &self.config.redirect_url),
+                ("scope", &self.config.scopes.join(" ")),
+                ("state", &state),
+                ("nonce", &pending.nonce),
+                ("code_challenge", &challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(provider_error)?;
+
+        let mut pending_logins = self.pending.lock().unwrap();
+        pending_logins.retain(|_, pending| pending.started_at.elapsed() < PENDING_TTL);
+        pending_logins.insert(state.clone(), pending);
+        return Ok((state, url.to_string()));
+    }
+
+    /// Redeems the code the provider sent back and resolves the staff member it names.
+    async fn complete(&self, state: &str, code: &str) -> Result<Principal, AppError> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(state)
+            .filter(|pending| pending.started_at.elapsed() < PENDING_TTL)
+            .ok_or_else(|| sign_in_failed("Sign-in expired or was already completed"))?;
+        let provider = self.provider().await?;
+
+        let request = self.client.post(&provider.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("client_secret", &self.config.client_secret),
+            ("code_verifier", &pending.code_verifier),
+        ]);
+        let tokens: Value = self
+            .client
+            .send(request)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+        let id_token = tokens
+            .get("id_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| provider_error("token response has no id_token"))?;
+        let claims = provider.verifier.claims(id_token).await.map_err(|err| {
+            tracing::warn!("Rejected ID token: {}", err);
+            sign_in_failed("The identity provider's token is invalid")
+        })?;
+        if claims.get("nonce").and_then(Value::as_str) != Some(pending.nonce.as_str()) {
+            return Err(sign_in_failed("The identity provider's token is invalid"));
+        }
+
+        return self.principal(&claims);
+    }
+
+    fn principal(&self, claims: &Value) -> Result<Principal, AppError> {
+        let subject = ["preferred_username", "email", "sub"]
+            .iter()
+            .find_map(|claim| claims.get(*claim).and_then(Value::as_str))
+            .ok_or_else(|| sign_in_failed("The identity provider's token has no subject"))?
+            .to_string();
+        let groups = claim_at(claims, &self.config.groups_claim)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str);
+        let mut roles: Vec<Role> = Vec::new();
+        for role in groups.filter_map(|group| self.config.group_roles.get(group)) {
+            if !roles.contains(role) {
+                roles.push(*role);
+            }
+        }
+        if roles.is_empty() {
+            return Err(AppError::new(
+                ErrorCode::Forbidden,
+                format!("{} is not in a group with access to the tracker", subject),
+            ));
+        }
+        let tenant = self
+            .config
+            .tenant_claim
+            .as_deref()
+            .and_then(|path| claim_at(claims, path))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        return Ok(Principal {
+            subject,
+            roles,
+            tenant,
+            anonymous: false,
+            application_id: None,
+            expires_at: None,
+        });
+    }
+}
+
+fn client(state: &AppState) -> Result<&OidcClient, AppError> {
+    return state
+        .auth
+        .oidc
+        .as_ref()
+        .ok_or_else(|| AppError::from(ErrorCode::OidcNotConfigured));
+}
+
+/// `GET /login/oidc`: sends the browser to the identity provider.
+pub async fn start(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let (sign_in, url) = client(&state)?.begin().await?;
+    let cookie = format!(
+        "{}={}; HttpOnly; SameSite=Lax; Path=/; Max-Age={}",
+        STATE_COOKIE,
+        sign_in,
+        PENDING_TTL.as_secs()
+    );
+    let mut response = Redirect::to(&url).into_response();
+    if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    return Ok(response);
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn state_cookie(headers: &HeaderMap) -> Option<&str> {
+    return headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            return (name == STATE_COOKIE).then_some(value);
+        });
+}
+
+/// `GET /login/oidc/callback`: where the provider sends staff back. Starts their session
+/// and forwards them to `post_login_url`.
+pub async fn callback(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    headers: HeaderMap,
+    WithRejection(Query(query), _): WithRejection<Query<CallbackQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let oidc = client(&state)?;
+    if let Some(error) = query.error {
+        return Err(sign_in_failed(format!(
+            "The identity provider refused the sign-in: {}",
+            query.error_description.unwrap_or(error)
+        )));
+    }
+    let (Some(code), Some(sign_in)) = (query.code, query.state) else {
+        return Err(sign_in_failed("The callback is missing code or state"));
+    };
+    // a callback URL forwarded from someone else's browser must not log this one in
+    if state_cookie(&headers) != Some(sign_in.as_str()) {
+        return Err(sign_in_failed("The sign-in was started in another browser"));
+    }
+    let principal = oidc.complete(&sign_in, &code).await?;
+    login::start(&state, &session, &principal, login::Credential::Federated).await?;
+
+    let mut response = Redirect::to(&oidc.config.post_login_url).into_response();
+    if let Ok(cookie) = HeaderValue::from_str(&format!("{}=; Path=/; Max-Age=0", STATE_COOKIE)) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    return Ok(response);
+}
@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use reqwest::{Client, Method, NoProxy, Proxy, RequestBuilder, Response};
+
+use crate::config::OutboundConfig;
+
+/// The configured proxy; `None` leaves the choice to the environment variables.
+pub fn proxy(config: &OutboundConfig) -> Result<Option<Proxy>, String> {
+    let Some(url) = &config.proxy else {
+        return Ok(None);
+    };
+    let proxy =
+        Proxy::all(url).map_err(|err| format!("invalid outbound.proxy {}: {}", url, err))?;
+    return Ok(Some(
+        proxy.no_proxy(NoProxy::from_string(&config.no_proxy.join(","))),
+    ));
+}
+
+/// The client shared by everything that calls out, so webhooks and sign-in take the same
+/// route out of the network, reuse connections and show up in the same metrics.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+/// Groups responses for the `outcome` label, keeping its values few.
+fn outcome(result: &reqwest::Result<Response>) -> &'static str {
+    match result {
+        Ok(response) if response.status().is_success() => return "2xx",
+        Ok(response) if response.status().is_redirection() => return "3xx",
+        Ok(response) if response.status().is_client_error() => return "4xx",
+        Ok(_) => return "5xx",
+        Err(err) if err.is_timeout() => return "timeout",
+        Err(err) if err.is_connect() => return "connect_error",
+        Err(_) => return "error",
+    }
+}
+
+fn is_transient(result: &reqwest::Result<Response>) -> bool {
+    match result {
+        Ok(response) => {
+            return response.status().is_server_error()
+                || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS;
+        }
+        Err(err) => return err.is_connect() || err.is_timeout(),
+    }
+}
+
+impl HttpClient {
+    pub fn new(config: &OutboundConfig) -> Self {
+        let user_agent = config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| format!("visa-tracker/{}", env!("CARGO_PKG_VERSION")));
+        let mut builder = Client::builder()
+            .user_agent(user_agent)
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs));
+        if let Some(proxy) = proxy(config).unwrap_or_else(|err| panic!("{}", err)) {
+            builder = builder.proxy(proxy);
+        }
+        return Self {
+            client: builder.build().expect("the HTTP client settings are valid"),
+            retries: config.retries,
+            retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+        };
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        return self.client.get(url);
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        return self.client.post(url);
+    }
+
+    /// Sends a request built from [`HttpClient::get`] or [`HttpClient::post`], recording
+    /// its outcome and duration by destination host. Reads are retried on transient
+    /// failures; anything else is attempted once, since it may already have taken effect.
+    pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut request = request.build()?;
+        let destination = request.url().host_str().unwrap_or("unknown").to_string();
+        let idempotent = matches!(*request.method(), Method::GET | Method::HEAD);
+        let mut attempt = 0;
+        loop {
+            let retry = request
+                .try_clone()
+                .filter(|_| idempotent && attempt < self.retries);
+            let started = Instant::now();
+            let result = self.client.execute(request).await;
+            metrics::histogram!(
+                "outbound_request_duration_seconds",
+                "destination" => destination.clone()
+            )
+            .record(started.elapsed().as_secs_f64());
+            metrics::counter!(
+                "outbound_requests_total",
+                "destination" => destination.clone(),
+                "outcome" => outcome(&result)
+            )
+            .increment(1);
+
+            match retry {
+                Some(next) if is_transient(&result) => {
+                    attempt += 1;
+                    tracing::debug!(
+                        "Retrying request to {} (attempt {} of {})",
+                        destination,
+                        attempt,
+                        self.retries
+                    );
+                    tokio::time::sleep(self.retry_backoff * attempt).await;
+                    request = next;
+                }
+                _ => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::header,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    auth::Admin,
+    config::OutboxConfig,
+    error::AppError,
+    event::{Broadcast, EventResponse},
+    outbound::HttpClient,
+    signature,
+    state::AppState,
+    store::StoreData,
+    webhook::{self, DeliveryAttempt, Webhook},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    /// Waiting for its first attempt or for the backoff of a failed one to pass.
+    Pending,
+    /// Parked in the dead-letter queue after `outbox.max_attempts` failures, or because the
+    /// webhook was deleted. `POST /admin/webhooks/{id}/replay` puts it back in line.
+    Dead,
+}
+
+/// What the receiver answered to a failed attempt.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CapturedResponse {
+    pub status: u16,
+    /// The start of the response body, for telling apart a receiver bug from an outage.
+    pub body: String,
+}
+
+/// Longest response body kept with a failed attempt.
+const CAPTURED_BODY_BYTES: usize = 1024;
+
+#[derive(Debug)]
+struct Failure {
+    error: String,
+    response: Option<CapturedResponse>,
+}
+
+/// Header carrying the outbox entry id, unchanged across retries of one delivery.
+const DELIVERY_HEADER: &str = "x-visa-tracker-delivery";
+
+/// A notification waiting to go out. Entries are written in the same store write as the
+/// event they announce, so an accepted event is never left without its notifications.
+/// Delivered entries are removed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    /// SSE `event:` name of the announced event.
+    pub kind: String,
+    /// The event envelope, as POSTed. Shared by the entries of every webhook the event
+    /// went to.
+    pub payload: Arc<Value>,
+    pub created_at: DateTime<Utc>,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Present when the last failure was an error response rather than a network error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_response: Option<CapturedResponse>,
+}
+
+impl OutboxEntry {
+    /// The application the announced event belongs to, if any.
+    pub fn application_id(&self) -> Option<Uuid> {
+        return self
+            .payload
+            .get("application_id")
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse().ok());
+    }
+}
+
+/// Queues `event` for every webhook that wants it. Call inside the store write that
+/// records the event.
+pub fn enqueue(data: &mut StoreData, event: &Broadcast) {
+    let now = Utc::now();
+    let mut payload = None;
+    let entries: Vec<OutboxEntry> = data
+        .webhooks
+        .iter()
+        .filter(|webhook| webhook.wants(event))
+        .map(|webhook| OutboxEntry {
+            id: Uuid::new_v4(),
+            webhook_id: webhook.id,
+            kind: event.kind().to_string(),
+            payload: payload
+                .get_or_insert_with(|| Arc::new(event.envelope(true)))
+                .clone(),
+            created_at: now,
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            last_response: None,
+        })
+        .collect();
+    if !entries.is_empty() {
+        metrics::counter!("outbox_enqueued_total").increment(entries.len() as u64);
+    }
+    data.outbox.extend(entries);
+}
+
+/// Delay before attempt `attempts + 1`: doubles from `initial_backoff_ms` up to
+/// `max_backoff_secs`.
+fn backoff(config: &OutboxConfig, attempts: u32) -> chrono::Duration {
+    let initial = config.initial_backoff_ms as i64;
+    let exponent = attempts.saturating_sub(1).min(30);
+    let delay = initial.saturating_mul(1 << exponent);
+    let max = (config.max_backoff_secs as i64).saturating_mul(1000);
+    return chrono::Duration::milliseconds(delay.min(max));
+}
+
+/// Posts the entry, signed with the webhook's secret. Returns the receiver's status code.
+async fn deliver(
+    client: &HttpClient,
+    config: &OutboxConfig,
+    webhook: &Webhook,
+    entry: &OutboxEntry,
+) -> Result<u16, Failure> {
+    let body = entry.payload.to_string();
+    let mut request = client
+        .post(&webhook.url)
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .header(header::CONTENT_TYPE.as_str(), "application/json")
+        .header("x-visa-tracker-event", &entry.kind)
+        .header(DELIVERY_HEADER, entry.id.to_string());
+    if let Some(secret) = &webhook.secret {
+        // signed per attempt, so a retry carries a fresh timestamp
+        let timestamp = Utc::now().timestamp();
+        request = request
+            .header(signature::TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                signature::SIGNATURE_HEADER,
+                signature::sign(secret, timestamp, body.as_bytes()),
+            );
+    }
+    let response = client
+        .send(request.body(body))
+        .await
+        .map_err(|err| Failure {
+            error: err.to_string(),
+            response: None,
+        })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(status.as_u16());
+    }
+    let body = response.bytes().await.unwrap_or_default();
+    let body = &body[..body.len().min(CAPTURED_BODY_BYTES)];
+    return Err(Failure {
+        error: format!("receiver answered {}", status),
+        response: Some(CapturedResponse {
+            status: status.as_u16(),
+            body: String::from_utf8_lossy(body).into_owned(),
+        }),
+    });
+}
+
+/// Records the outcome of one attempt, both on the entry and in the webhook's delivery log.
+fn settle(
+    state: &AppState,
+    entry: &OutboxEntry,
+    attempted_at: DateTime<Utc>,
+    latency: Duration,
+    result: Result<u16, Failure>,
+) {
+    let config = state.config();
+    let entry_id = entry.id;
+    state.store.write(|data| {
+        webhook::record_attempt(
+            data,
+            DeliveryAttempt {
+                delivery_id: entry.id,
+                webhook_id: entry.webhook_id,
+                application_id: entry.application_id(),
+                kind: entry.kind.clone(),
+                attempt: entry.attempts + 1,
+                attempted_at,
+                status: match &result {
+                    Ok(status) => Some(*status),
+                    Err(failure) => failure.response.as_ref().map(|response| response.status),
+                },
+                latency_ms: latency.as_millis() as u64,
+                error: result.as_ref().err().map(|failure| failure.error.clone()),
+            },
+        );
+        let Some(index) = data.outbox.iter().position(|entry| entry.id == entry_id) else {
+            return;
+        };
+        let failure = match result {
+            Ok(_) => {
+                data.outbox.remove(index);
+                metrics::counter!("outbox_delivered_total").increment(1);
+                return;
+            }
+            Err(failure) => failure,
+        };
+        let entry = &mut data.outbox[index];
+        entry.attempts += 1;
+        if entry.attempts >= config.outbox.max_attempts {
+            tracing::warn!(
+                "Giving up on outbox entry {} for webhook {} after {} attempts: {}",
+                entry.id,
+                entry.webhook_id,
+                entry.attempts,
+                failure.error
+            );
+            entry.status = OutboxStatus::Dead;
+            metrics::counter!("outbox_dead_total").increment(1);
+        } else {
+            entry.next_attempt_at = Utc::now() + backoff(&config.outbox, entry.attempts);
+        }
+        entry.last_error = Some(failure.error);
+        entry.last_response = failure.response;
+    });
+}
+
+/// Moves the dead-lettered entries of `webhook_id` back into the queue for immediate
+/// delivery, returning how many there were.
+pub fn replay(data: &mut StoreData, webhook_id: Uuid) -> usize {
+    let now = Utc::now();
+    let mut replayed = 0;
+    for entry in data.outbox.iter_mut() {
+        if entry.webhook_id != webhook_id || entry.status != OutboxStatus::Dead {
+            continue;
+        }
+        entry.status = OutboxStatus::Pending;
+        entry.attempts = 0;
+        entry.next_attempt_at = now;
+        replayed += 1;
+    }
+    return replayed;
+}
+
+/// Background dispatcher: posts due entries and reschedules failed ones with backoff.
+pub async fn run(app_state: Arc<AppState>) {
+    let client = app_state.http.clone();
+    loop {
+        let config = app_state.config();
+        tokio::time::sleep(Duration::from_millis(config.outbox.poll_interval_ms)).await;
+
+        let now = Utc::now();
+        let due: Vec<(OutboxEntry, Option<Webhook>)> = app_state.store.read(|data| {
+            data.outbox
+                .iter()
+                .filter(|entry| entry.status == OutboxStatus::Pending)
+                .filter(|entry| entry.next_attempt_at <= now)
+                .map(|entry| {
+                    let webhook = data
+                        .webhooks
+                        .iter()
+                        .find(|webhook| webhook.id == entry.webhook_id)
+                        .cloned();
+                    return (entry.clone(), webhook);
+                })
+                .collect()
+        });
+        for (entry, webhook) in due {
+            let attempted_at = Utc::now();
+            let started = Instant::now();
+            let result = match &webhook {
+                Some(webhook) => {
+                    if !app_state
+                        .breakers
+                        .allow(&config.outbox, webhook.id, attempted_at)
+                    {
+                        continue;
+                    }
+                    let result = deliver(&client, &config.outbox, webhook, &entry).await;
+                    app_state
+                        .breakers
+                        .record(&config.outbox, webhook.id, result.is_ok());
+                    result
+                }
+                None => {
+                    // nothing to retry against
+                    app_state.store.write(|data| {
+                        let orphan = data.outbox.iter_mut().find(|queued| queued.id == entry.id);
+                        if let Some(orphan) = orphan {
+                            orphan.status = OutboxStatus::Dead;
+                            orphan.last_error = Some("webhook was deleted".to_string());
+                        }
+                    });
+                    continue;
+                }
+            };
+            settle(&app_state, &entry, attempted_at, started.elapsed(), result);
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OutboxQuery {
+    status: Option<OutboxStatus>,
+    webhook_id: Option<Uuid>,
+}
+
+/// `GET /admin/outbox`: undelivered notifications, e.g. `?status=dead&webhook_id=...` for
+/// one receiver's dead-letter queue. Tenant admins see the entries of their own webhooks.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Query(query), _): WithRejection<Query<OutboxQuery>, AppError>,
+) -> Json<EventResponse<Vec<OutboxEntry>>> {
+    let entries = state.store.read(|data| {
+        let visible: Vec<Uuid> = data
+            .webhooks
+            .iter()
+            .filter(|webhook| admin.can_access(webhook.tenant.as_deref()))
+            .map(|webhook| webhook.id)
+            .collect();
+        // entries of deleted webhooks have no owner left and are shown to operators only
+        return data
+            .outbox
+            .iter()
+            .filter(|entry| admin.tenant.is_none() || visible.contains(&entry.webhook_id))
+            .filter(|entry| query.status.is_none_or(|status| entry.status == status))
+            .filter(|entry| query.webhook_id.is_none_or(|id| entry.webhook_id == id))
+            .cloned()
+            .collect();
+    });
+    return Json(EventResponse::ok(entries));
+}
@@ -0,0 +1,69 @@
+use std::{fmt, fs, path::Path, sync::Arc};
+
+use jsonschema::Validator;
+use serde_json::Value;
+
+use crate::error::{AppError, ErrorCode, Violation};
+
+/// Most violations listed in one error response.
+const MAX_VIOLATIONS: usize = 20;
+
+/// An operator-supplied JSON Schema for event payloads, compiled once when the config is
+/// read. Compares by its source, so a reload only counts as a change when the file did.
+#[derive(Clone)]
+pub struct PayloadSchema {
+    source: Value,
+    validator: Arc<Validator>,
+}
+
+impl PayloadSchema {
+    /// Reads and compiles the schema at `path`. Remote `$ref`s are not followed.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read event schema {}: {}", path.display(), err))?;
+        let source: Value = serde_json::from_str(&content)
+            .map_err(|err| format!("invalid event schema {}: {}", path.display(), err))?;
+        let validator = jsonschema::validator_for(&source)
+            .map_err(|err| format!("invalid event schema {}: {}", path.display(), err))?;
+        return Ok(Self {
+            source,
+            validator: Arc::new(validator),
+        });
+    }
+
+    /// Refuses a payload that breaks any rule, naming each offending location.
+    pub fn check(&self, payload: &Value) -> Result<(), AppError> {
+        let violations: Vec<Violation> = self
+            .validator
+            .iter_errors(payload)
+            .take(MAX_VIOLATIONS)
+            .map(|err| Violation {
+                pointer: err.instance_path().as_str().to_string(),
+                message: err.to_string(),
+            })
+            .collect();
+        if violations.is_empty() {
+            return Ok(());
+        }
+        return Err(AppError::new(
+            ErrorCode::SchemaViolation,
+            format!(
+                "Event payload does not match the configured schema ({} violations)",
+                violations.len()
+            ),
+        )
+        .violations(violations));
+    }
+}
+
+impl PartialEq for PayloadSchema {
+    fn eq(&self, other: &Self) -> bool {
+        return self.source == other.source;
+    }
+}
+
+impl fmt::Debug for PayloadSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_tuple("PayloadSchema").field(&self.source).finish();
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fs, path::Path};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rand::RngCore;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use serde_json::Value;
+
+/// Marks an encrypted field: `enc:v1:` and the base64 of nonce, ciphertext and tag.
+const PREFIX: &str = "enc:v1:";
+
+/// Encrypts applicant names, emails and note texts in the data file with AES-256-GCM,
+/// together with the queued webhook bodies and audited requests that may repeat them.
+/// They stay in plain text in memory, so handlers never see ciphertext. Each field is
+/// bound to its place in the snapshot, so ciphertexts cannot be swapped between cases.
+pub struct FieldCipher {
+    key: LessSafeKey,
+}
+
+impl FieldCipher {
+    /// Reads a base64-encoded 256-bit key, e.g. from `openssl rand -base64 32` or a secret
+    /// mounted by the KMS.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read encryption key {}: {}", path.display(), err))?;
+        let bytes = STANDARD
+            .decode(content.trim())
+            .map_err(|err| format!("invalid encryption key {}: {}", path.display(), err))?;
+        let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| {
+            format!(
+                "invalid encryption key {}: expected 32 bytes, got {}",
+                path.display(),
+                bytes.len()
+            )
+        })?;
+        return Ok(Self {
+            key: LessSafeKey::new(key),
+        });
+    }
+
+    fn seal(&self, location: &str, plaintext: &str) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rng().fill_bytes(&mut nonce);
+        let mut sealed = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(location.as_bytes()),
+                &mut sealed,
+            )
+            .expect("AES-GCM input is far below its size limit");
+        let mut encoded = nonce.to_vec();
+        encoded.append(&mut sealed);
+        return format!("{}{}", PREFIX, STANDARD.encode(encoded));
+    }
+
+    fn open(&self, location: &str, value: &str) -> Result<String, String> {
+        let invalid = || format!("cannot decrypt {}", location);
+        let bytes = STANDARD.decode(value).map_err(|_| invalid())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| invalid())?;
+        let mut sealed = sealed.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(location.as_bytes()), &mut sealed)
+            .map_err(|_| invalid())?;
+        return String::from_utf8(plaintext.to_vec()).map_err(|_| invalid());
+    }
+}
+
+/// How a personal field is stored once sealed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// A string, sealed as is.
+    Text,
+    /// Any JSON, e.g. a webhook body carrying note texts, sealed as its serialization.
+    Json,
+}
+
+/// Calls `f` with the JSON pointer, value and kind of every personal field in a snapshot:
+/// the applicants' names, emails and notes, plus the notification and audit payloads that
+/// carry copies of them.
+fn for_each_field(
+    snapshot: &mut Value,
+    mut f: impl FnMut(&str, &mut Value, Field) -> Result<(), String>,
+) -> Result<(), String> {
+    let applications = snapshot
+        .get_mut("applications")
+        .and_then(Value::as_object_mut)
+        .into_iter()
+        .flatten();
+    for (id, application) in applications {
+        for field in ["applicant_name", "applicant_email"] {
+            if let Some(value) = application.get_mut(field) {
+                f(
+                    &format!("/applications/{}/{}", id, field),
+                    value,
+                    Field::Text,
+                )?;
+            }
+        }
+        let notes = application
+            .get_mut("notes")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for note in notes {
+            let note_id = note.get("id").and_then(Value::as_str).unwrap_or_default();
+            let location = format!("/applications/{}/notes/{}/text", id, note_id);
+            if let Some(value) = note.get_mut("text") {
+                f(&location, value, Field::Text)?;
+            }
+        }
+    }
+    for collection in ["outbox", "audit"] {
+        let entries = snapshot
+            .get_mut(collection)
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for entry in entries {
+            let entry_id = match entry.get("id") {
+                Some(Value::String(id)) => id.clone(),
+                Some(id) => id.to_string(),
+                None => String::new(),
+            };
+            let location = format!("/{}/{}/payload", collection, entry_id);
+            if let Some(value) = entry.get_mut("payload") {
+                f(&location, value, Field::Json)?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Encrypts the personal fields of a snapshot about to be written.
+pub fn seal(snapshot: &mut Value, cipher: &FieldCipher) {
+    let _ = for_each_field(snapshot, |location, value, field| {
+        match (field, &*value) {
+            (Field::Text, Value::String(plaintext)) => {
+                *value = Value::String(cipher.seal(location, plaintext));
+            }
+            (Field::Json, _) => {
+                *value = Value::String(cipher.seal(location, &value.to_string()));
+            }
+            (Field::Text, _) => {}
+        }
+        return Ok(());
+    });
+}
+
+/// Decrypts the personal fields of a snapshot just read. Fields still in plain text, from
+/// before encryption was enabled, are kept and get encrypted on the next flush.
+pub fn open(snapshot: &mut Value, cipher: Option<&FieldCipher>) -> Result<(), String> {
+    return for_each_field(snapshot, |location, value, field| {
+        let Some(sealed) = value.as_str().and_then(|text| text.strip_prefix(PREFIX)) else {
+            return Ok(());
+        };
+        let Some(cipher) = cipher else {
+            return Err(format!(
+                "{} is encrypted but store.encryption_key_path is not set",
+                location
+            ));
+        };
+        let plaintext = cipher.open(location, sealed)?;
+        *value = match field {
+            Field::Text => Value::String(plaintext),
+            Field::Json => serde_json::from_str(&plaintext)
+                .map_err(|err| format!("cannot parse decrypted {}: {}", location, err))?,
+        };
+        return Ok(());
+    });
+}
@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{Json, extract::State};
+use chrono::{DateTime, Days, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    auth::Admin,
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+    store::StoreData,
+};
+
+/// Usage limits, each unlimited when unset. Applications created by operators (without a
+/// tenant) are exempt from the per-tenant limit.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Quotas {
+    /// Events an application may receive per UTC day; more are refused with 429.
+    pub events_per_application_per_day: Option<usize>,
+    /// Unarchived applications a tenant may have; creating or unarchiving more is refused
+    /// with 403 until one is archived.
+    pub active_applications_per_tenant: Option<usize>,
+    /// Streams that may follow one application at once; more are refused with 429.
+    pub subscribers_per_application: Option<usize>,
+}
+
+fn start_of_day(now: DateTime<Utc>) -> DateTime<Utc> {
+    return now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+}
+
+/// Events stored for the application since the start of the current UTC day.
+fn events_today(data: &StoreData, application_id: Uuid, now: DateTime<Utc>) -> usize {
+    let since = start_of_day(now);
+    return data
+        .events
+        .iter()
+        .filter(|stored| stored.event.application_id == Some(application_id) && stored.at >= since)
+        .count();
+}
+
+fn active_applications(data: &StoreData, tenant: &str) -> usize {
+    return data
+        .applications
+        .values()
+        .filter(|application| {
+            application.archived_at.is_none() && application.tenant.as_deref() == Some(tenant)
+        })
+        .count();
+}
+
+/// Refuses another event for the application once today's allowance is used up, asking
+/// the publisher to come back at midnight UTC.
+pub fn check_events(state: &AppState, application_id: Uuid) -> Result<(), AppError> {
+    let Some(limit) = state.config().quotas.events_per_application_per_day else {
+        return Ok(());
+    };
+    let now = Utc::now();
+    let used = state
+        .store
+        .read(|data| events_today(data, application_id, now));
+    if used < limit {
+        return Ok(());
+    }
+    let midnight = start_of_day(now) + Days::new(1);
+    return Err(AppError::new(
+        ErrorCode::EventQuotaExceeded,
+        format!(
+            "Application {} already received its {} events for today",
+            application_id, limit
+        ),
+    )
+    .retry_after((midnight - now).num_seconds().max(1) as u64));
+}
+
+/// Refuses one more active application for `tenant`. Call under the store's write lock,
+/// so concurrent requests cannot both take the last slot.
+pub fn check_active_applications(
+    quotas: &Quotas,
+    data: &StoreData,
+    tenant: Option<&str>,
+) -> Result<(), AppError> {
+    let (Some(limit), Some(tenant)) = (quotas.active_applications_per_tenant, tenant) else {
+        return Ok(());
+    };
+    if active_applications(data, tenant) < limit {
+        return Ok(());
+    }
+    return Err(AppError::new(
+        ErrorCode::ApplicationQuotaExceeded,
+        format!(
+            "Tenant {} already has {} active applications; archive one first",
+            tenant, limit
+        ),
+    ));
+}
+
+/// Refuses another stream of the application while it has as many as allowed.
+pub fn check_subscribers(state: &AppState, application_id: Uuid) -> Result<(), AppError> {
+    let Some(limit) = state.config().quotas.subscribers_per_application else {
+        return Ok(());
+    };
+    if (state.stats.viewers_of(application_id) as usize) < limit {
+        return Ok(());
+    }
+    return Err(AppError::new(
+        ErrorCode::SubscriberQuotaExceeded,
+        format!(
+            "Application {} already has {} subscribers",
+            application_id, limit
+        ),
+    )
+    .retry_after(state.config().sse.keep_alive_secs));
+}
+
+#[derive(Serialize, Debug)]
+pub struct TenantUsage {
+    tenant: String,
+    active_applications: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ApplicationUsage {
+    application_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    events_today: usize,
+    subscribers: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QuotaUsage {
+    limits: Quotas,
+    tenants: Vec<TenantUsage>,
+    /// Active applications with events today or open streams, busiest first.
+    applications: Vec<ApplicationUsage>,
+}
+
+/// `GET /admin/quotas`: the configured limits next to current usage. Tenant admins see
+/// their own tenant only.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Json<EventResponse<QuotaUsage>> {
+    let now = Utc::now();
+    let viewers = state.stats.viewers_by_application();
+    let (tenants, mut applications) = state.store.read(|data| {
+        let mut tenants: BTreeMap<String, usize> = BTreeMap::new();
+        let mut applications = Vec::new();
+        for application in data.applications.values() {
+            if application.archived_at.is_some() || !admin.can_access(application.tenant.as_deref())
+            {
+                continue;
+            }
+            if let Some(tenant) = &application.tenant {
+                *tenants.entry(tenant.clone()).or_default() += 1;
+            }
+            let events_today = events_today(data, application.id, now);
+            let subscribers = viewers.get(&Some(application.id)).copied().unwrap_or(0);
+            if events_today > 0 || subscribers > 0 {
+                applications.push(ApplicationUsage {
+                    application_id: application.id,
+                    tenant: application.tenant.clone(),
+                    events_today,
+                    subscribers,
+                });
+            }
+        }
+        return (tenants, applications);
+    });
+    applications.sort_by(|a, b| {
+        b.events_today
+            .cmp(&a.events_today)
+            .then(b.subscribers.cmp(&a.subscribers))
+    });
+    return Json(EventResponse::ok(QuotaUsage {
+        limits: state.config().quotas,
+        tenants: tenants
+            .into_iter()
+            .map(|(tenant, active_applications)| TenantUsage {
+                tenant,
+                active_applications,
+            })
+            .collect(),
+        applications,
+    }));
+}
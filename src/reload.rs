@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::{
+    auth::Admin,
+    config::Config,
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+};
+
+#[derive(Serialize, Debug, Default)]
+pub struct ReloadReport {
+    /// Settings whose new values are now in effect.
+    pub applied: Vec<&'static str>,
+    /// Settings that changed in the file but are only read at startup; they keep their
+    /// current values until the next restart.
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Re-reads the config file and adopts what can change without a restart. Open streams are
+/// untouched; they pick up e.g. a new keep-alive interval when they reconnect.
+pub fn reload(state: &AppState) -> Result<ReloadReport, String> {
+    let current = state.config();
+    let mut next = Config::read(current.source.clone())?;
+    let mut report = ReloadReport::default();
+
+    macro_rules! live {
+        ($name:literal, $($field:tt).+) => {
+            if next.$($field).+ != current.$($field).+ {
+                report.applied.push($name);
+            }
+        };
+    }
+    macro_rules! restart {
+        ($name:literal, $($field:tt).+) => {
+            if next.$($field).+ != current.$($field).+ {
+                report.restart_required.push($name);
+                next.$($field).+ = current.$($field).+.clone();
+            }
+        };
+    }
+
+    live!("cors", cors);
+    live!("proxy", proxy);
+    live!("access", access);
+    live!("drain", drain);
+    live!("signature", signature);
+    live!("events", events);
+    live!("checklist", checklist);
+    live!("visa_types", visa_types);
+    live!("quotas", quotas);
+    live!("outbox", outbox);
+    live!("frontend", frontend);
+    live!("sse.keep_alive_secs", sse.keep_alive_secs);
+    live!("sse.heartbeat_secs", sse.heartbeat_secs);
+    live!("sse.viewers_debounce_ms", sse.viewers_debounce_ms);
+    live!("sse.queue_capacity", sse.queue_capacity);
+    live!("sse.channel_idle_secs", sse.channel_idle_secs);
+    live!("sse.max_connection_secs", sse.max_connection_secs);
+    live!("sse.reconnect_jitter_ms", sse.reconnect_jitter_ms);
+    live!("sse.resume_ttl_secs", sse.resume_ttl_secs);
+    live!("retention.max_age_days", retention.max_age_days);
+    live!(
+        "retention.max_events_per_application",
+        retention.max_events_per_application
+    );
+    restart!("profile", profile);
+    restart!("listen_addr", listen_addr);
+    restart!("server", server);
+    restart!("limits", limits);
+    restart!("auth", auth);
+    restart!("store", store);
+    restart!("outbound", outbound);
+    restart!("logging", logging);
+    restart!("sentry", sentry);
+    restart!("sse.overflow_policy", sse.overflow_policy);
+    restart!("sse.fanout_workers", sse.fanout_workers);
+    restart!("sse.slow_consumer_max_lag", sse.slow_consumer_max_lag);
+    restart!(
+        "sse.slow_consumer_max_lag_secs",
+        sse.slow_consumer_max_lag_secs
+    );
+    restart!("retention.interval_secs", retention.interval_secs);
+    // runtime changes through /admin/flags and /admin/announcements win over the file until
+    // the next restart
+    restart!("flags", flags);
+    restart!("announcements", announcements);
+
+    let queue_capacity = next.sse.queue_capacity;
+    state.replace_config(next)?;
+    if queue_capacity != current.sse.queue_capacity {
+        state.hub.resize(queue_capacity);
+    }
+    tracing::info!(
+        "Config reloaded; applied {:?}, restart required for {:?}",
+        report.applied,
+        report.restart_required
+    );
+    return Ok(report);
+}
+
+pub async fn trigger(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<ReloadReport>>, AppError> {
+    admin.require_operator()?;
+    tracing::info!("{} requested a config reload", admin.subject);
+    match reload(&state) {
+        Ok(report) => return Ok(Json(EventResponse::ok(report))),
+        Err(err) => {
+            tracing::error!("Config reload failed: {}", err);
+            return Err(AppError::new(ErrorCode::ConfigReloadFailed, err));
+        }
+    }
+}
+
+/// Reloads on every SIGHUP, the conventional signal for it.
+pub async fn on_sighup(app_state: Arc<AppState>) {
+    let mut hangup =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).unwrap();
+    while hangup.recv().await.is_some() {
+        if let Err(err) = reload(&app_state) {
+            tracing::error!("Config reload failed: {}", err);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use rand::RngCore;
+use uuid::Uuid;
+
+/// Prefix of resume tokens, so they are told apart from API keys and share links.
+const TOKEN_PREFIX: &str = "vtr_";
+
+/// What a stream subscribed to and how far it got, kept so `GET /events?resume=` can
+/// restore it after a disconnect. Held in memory only; tokens do not survive a restart.
+#[derive(Clone)]
+pub struct Session {
+    pub filter: Option<Uuid>,
+    pub types: Option<HashSet<String>>,
+    pub legacy: bool,
+    /// Sequence number of the last event delivered. Only tracked for streams of one
+    /// application, since sequences are per application.
+    position: Arc<AtomicU64>,
+    /// Streams currently using the session; it only expires once this drops to zero.
+    open: Arc<AtomicUsize>,
+    last_active: Arc<Mutex<Instant>>,
+}
+
+impl Session {
+    pub fn position(&self) -> u64 {
+        return self.position.load(Ordering::Relaxed);
+    }
+
+    /// Records the delivery of the filtered application's event `seq`.
+    pub fn advance(&self, seq: u64) {
+        self.position.fetch_max(seq, Ordering::Relaxed);
+    }
+
+    /// Marks the session as in use until the returned guard is dropped with the stream.
+    pub fn attach(&self) -> Attached {
+        self.open.fetch_add(1, Ordering::Relaxed);
+        return Attached(self.clone());
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        return self.open.load(Ordering::Relaxed) == 0
+            && self.last_active.lock().unwrap().elapsed() > ttl;
+    }
+}
+
+/// Keeps a session alive while its stream is open.
+pub struct Attached(Session);
+
+impl Drop for Attached {
+    fn drop(&mut self) {
+        *self.0.last_active.lock().unwrap() = Instant::now();
+        self.0.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Sessions {
+    /// Starts a session at `position` and returns its token.
+    pub fn open(
+        &self,
+        filter: Option<Uuid>,
+        types: Option<HashSet<String>>,
+        legacy: bool,
+        position: u64,
+        ttl: Duration,
+    ) -> (String, Session) {
+        let mut secret = [0u8; 24];
+        rand::rng().fill_bytes(&mut secret);
+        let token = format!("{}{}", TOKEN_PREFIX, hex::encode(secret));
+        let session = Session {
+            filter,
+            types,
+            legacy,
+            position: Arc::new(AtomicU64::new(position)),
+            open: Arc::default(),
+            last_active: Arc::new(Mutex::new(Instant::now())),
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| !session.is_expired(ttl));
+        sessions.insert(token.clone(), session.clone());
+        return (token, session);
+    }
+
+    /// The session behind `token`, unless it is unknown or sat unused longer than `ttl`.
+    pub fn resume(&self, token: &str, ttl: Duration) -> Option<Session> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| !session.is_expired(ttl));
+        return sessions.get(token).cloned();
+    }
+}
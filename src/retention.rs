@@ -0,0 +1,119 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{Json, extract::State};
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{
+    auth::Admin, config::RetentionConfig, error::AppError, event::EventResponse, state::AppState,
+    store::Store,
+};
+
+#[derive(Serialize, Debug, Default)]
+pub struct PruneReport {
+    pub pruned_by_age: usize,
+    pub pruned_by_count: usize,
+    pub audit_entries_pruned: usize,
+}
+
+/// Drops stored events older than `max_age_days`, then keeps only the newest
+/// `max_events_per_application` per application. The audit log is trimmed the same way.
+pub fn prune(store: &Store, config: &RetentionConfig) -> PruneReport {
+    let mut report = PruneReport {
+        audit_entries_pruned: prune_audit(store, config),
+        ..PruneReport::default()
+    };
+    if config.max_age_days.is_none() && config.max_events_per_application.is_none() {
+        return report;
+    }
+
+    store.write(|data| {
+        if let Some(max_age_days) = config.max_age_days {
+            let cutoff = Utc::now() - chrono::Duration::days(max_age_days as i64);
+            let before = data.events.len();
+            data.events.retain(|stored| stored.at >= cutoff);
+            report.pruned_by_age = before - data.events.len();
+        }
+
+        if let Some(max_events) = config.max_events_per_application {
+            // events are appended in order, so walking backwards visits the newest first
+            let mut kept_per_application = HashMap::new();
+            let mut keep = vec![false; data.events.len()];
+            for (index, stored) in data.events.iter().enumerate().rev() {
+                let kept = kept_per_application
+                    .entry(stored.event.application_id)
+                    .or_insert(0usize);
+                if *kept < max_events {
+                    *kept += 1;
+                    keep[index] = true;
+                }
+            }
+            let before = data.events.len();
+            let mut keep = keep.into_iter();
+            data.events.retain(|_| keep.next().unwrap());
+            report.pruned_by_count = before - data.events.len();
+        }
+        if report.pruned_by_age + report.pruned_by_count > 0 {
+            data.reindex_progress();
+        }
+    });
+
+    metrics::counter!("events_pruned_total", "reason" => "age")
+        .increment(report.pruned_by_age as u64);
+    metrics::counter!("events_pruned_total", "reason" => "count")
+        .increment(report.pruned_by_count as u64);
+    if report.pruned_by_age + report.pruned_by_count > 0 {
+        tracing::info!(
+            "Pruned {} events by age and {} by count",
+            report.pruned_by_age,
+            report.pruned_by_count
+        );
+    }
+    return report;
+}
+
+/// Drops audit entries past `audit_max_age_days`, then all but the newest
+/// `max_audit_entries`. Entries are appended in order, so the stale ones lead.
+fn prune_audit(store: &Store, config: &RetentionConfig) -> usize {
+    let cutoff = config
+        .audit_max_age_days
+        .map(|days| Utc::now() - chrono::Duration::days(days as i64));
+    let stale = store.read(|data| {
+        let expired = cutoff.map_or(0, |cutoff| {
+            return data.audit.partition_point(|entry| entry.at < cutoff);
+        });
+        let excess = data.audit.len().saturating_sub(config.max_audit_entries);
+        return expired.max(excess);
+    });
+    // the common case writes nothing, so the snapshot is left alone
+    if stale == 0 {
+        return 0;
+    }
+    let pruned = store.write(|data| {
+        let stale = stale.min(data.audit.len());
+        data.audit.drain(..stale);
+        return stale;
+    });
+    metrics::counter!("audit_entries_pruned_total").increment(pruned as u64);
+    tracing::info!("Pruned {} audit entries", pruned);
+    return pruned;
+}
+
+pub async fn run(app_state: Arc<AppState>) {
+    // the interval is fixed at startup; the limits themselves follow config reloads
+    let interval_secs = app_state.config().retention.interval_secs;
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        ticker.tick().await;
+        prune(&app_state.store, &app_state.config().retention);
+    }
+}
+
+pub async fn trigger(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<PruneReport>>, AppError> {
+    admin.require_operator()?;
+    let report = prune(&state.store, &state.config().retention);
+    return Ok(Json(EventResponse::ok(report)));
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    application,
+    auth::Publisher,
+    error::{AppError, ErrorCode},
+    event::{EventResponse, StoredEvent},
+    state::AppState,
+};
+
+/// Marks a stored event as withdrawn. The event stays in the history so clients can strike
+/// it through, but no longer counts towards the application's progress.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Retraction {
+    pub at: DateTime<Utc>,
+    pub by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Body of `POST /events/{id}/retract`; send `{}` to retract without a reason.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RetractRequest {
+    reason: Option<String>,
+}
+
+fn not_found(id: u64) -> AppError {
+    return AppError::new(
+        ErrorCode::EventNotFound,
+        format!("Event {} does not exist", id),
+    );
+}
+
+/// Retracts a mistaken event and broadcasts an `event: retracted` tombstone carrying the
+/// original event's id and sequence number.
+pub async fn retract(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Path(id), _): WithRejection<Path<u64>, AppError>,
+    WithRejection(Json(payload), _): WithRejection<Json<RetractRequest>, AppError>,
+) -> Result<Json<EventResponse<StoredEvent>>, AppError> {
+    let stored = state
+        .store
+        .read(|data| data.events.iter().find(|stored| stored.id == id).cloned())
+        .ok_or_else(|| not_found(id))?;
+    // events without an application carry no tenant, so only operators may withdraw them
+    let tenant = match stored.event.application_id {
+        Some(application_id) => application::authorize_active(&state, &publisher, application_id)
+            .map_err(|_| not_found(id))?,
+        None => {
+            publisher.require_operator()?;
+            None
+        }
+    };
+
+    let retraction = Retraction {
+        at: Utc::now(),
+        by: publisher.subject.clone(),
+        reason: payload.reason.filter(|reason| !reason.trim().is_empty()),
+    };
+    let retracted = state.store.write(|data| {
+        let stored = data.events.iter_mut().find(|stored| stored.id == id)?;
+        if stored.retracted.is_some() {
+            return Some(Err(AppError::new(
+                ErrorCode::EventAlreadyRetracted,
+                format!("Event {} was already retracted", id),
+            )));
+        }
+        stored.retracted = Some(retraction.clone());
+        let retracted = stored.clone();
+        data.reindex_progress();
+        return Some(Ok(retracted));
+    });
+    let retracted = retracted.ok_or_else(|| not_found(id))??;
+
+    tracing::info!("{} retracted event {}", publisher.subject, id);
+    // the tombstone reaches exactly those who saw the original
+    let _ = state.broadcast_with(
+        retracted.event.visibility,
+        retracted.event.application_id,
+        tenant,
+        "retracted",
+        json!({
+            "event_id": id,
+            "seq": retracted.seq,
+            "application_id": retracted.event.application_id,
+            "reason": retraction.reason,
+        }),
+    );
+    return Ok(Json(EventResponse::ok(retracted)));
+}
@@ -0,0 +1,207 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    application,
+    auth::{Admin, Principal, Viewer},
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    fanout::Disconnect,
+    state::AppState,
+};
+
+const MAX_NAME_LEN: usize = 64;
+
+/// A named group of applications, e.g. the cases of one consulate office, followed as a
+/// single stream at `GET /rooms/{name}/events`. Names are unique across the instance.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Room {
+    pub name: String,
+    /// A tenant's rooms only take that tenant's applications; operator rooms take any.
+    pub tenant: Option<String>,
+    pub application_ids: BTreeSet<Uuid>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+fn not_found(name: &str) -> AppError {
+    return AppError::new(
+        ErrorCode::RoomNotFound,
+        format!("Room {} does not exist", name),
+    );
+}
+
+fn check_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::new(
+            ErrorCode::InvalidRoom,
+            format!(
+                "Room names are 1 to {} letters, digits, '-' or '_'",
+                MAX_NAME_LEN
+            ),
+        ));
+    }
+    return Ok(());
+}
+
+/// The room called `name`, if `principal` may follow it. Share links are confined to their
+/// application and never see rooms.
+pub fn find(state: &AppState, principal: &Principal, name: &str) -> Result<Room, AppError> {
+    return state
+        .store
+        .read(|data| data.rooms.iter().find(|room| room.name == name).cloned())
+        .filter(|_| principal.application_id.is_none())
+        .filter(|room| principal.can_access(room.tenant.as_deref()))
+        .ok_or_else(|| not_found(name));
+}
+
+/// Drops an erased application from every room.
+pub fn forget(state: &AppState, application_id: Uuid) {
+    let rooms = state.store.write(|data| {
+        let mut changed = Vec::new();
+        for room in &mut data.rooms {
+            if room.application_ids.remove(&application_id) {
+                changed.push(room.clone());
+            }
+        }
+        return changed;
+    });
+    for room in rooms {
+        state.hub.set_room(&room.name, &room.application_ids);
+    }
+}
+
+/// `GET /rooms`: every room the caller may manage.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Json<EventResponse<Vec<Room>>> {
+    let rooms = state.store.read(|data| {
+        data.rooms
+            .iter()
+            .filter(|room| admin.can_access(room.tenant.as_deref()))
+            .cloned()
+            .collect()
+    });
+    return Json(EventResponse::ok(rooms));
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    WithRejection(Path(name), _): WithRejection<Path<String>, AppError>,
+) -> Result<Json<EventResponse<Room>>, AppError> {
+    return Ok(Json(EventResponse::ok(find(&state, &viewer, &name)?)));
+}
+
+/// `PUT /rooms/{name}/applications/{id}`: adds the application to the room, creating the
+/// room for the caller's tenant if it does not exist yet. Open room streams receive the
+/// application's events from then on.
+pub async fn add_application(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path((name, id)), _): WithRejection<Path<(String, Uuid)>, AppError>,
+) -> Result<Json<EventResponse<Room>>, AppError> {
+    check_name(&name)?;
+    let tenant = application::authorize_active(&state, &admin, id)?;
+    let room = state.store.write(|data| -> Result<Room, AppError> {
+        let room = match data.rooms.iter().position(|room| room.name == name) {
+            Some(index) => &mut data.rooms[index],
+            None => {
+                data.rooms.push(Room {
+                    name: name.clone(),
+                    tenant: admin.tenant.clone(),
+                    application_ids: BTreeSet::new(),
+                    created_by: admin.subject.clone(),
+                    created_at: Utc::now(),
+                });
+                data.rooms.last_mut().unwrap()
+            }
+        };
+        if !admin.can_access(room.tenant.as_deref()) {
+            return Err(AppError::new(
+                ErrorCode::InvalidRoom,
+                format!("Room name {} is taken", name),
+            ));
+        }
+        if room.tenant.is_some() && room.tenant != tenant {
+            return Err(AppError::new(
+                ErrorCode::TenantForbidden,
+                format!("Room {} only takes its own tenant's applications", name),
+            ));
+        }
+        room.application_ids.insert(id);
+        return Ok(room.clone());
+    })?;
+    state.hub.set_room(&room.name, &room.application_ids);
+    tracing::info!(
+        "{} added application {} to room {}",
+        admin.subject,
+        id,
+        name
+    );
+    return Ok(Json(EventResponse::ok(room)));
+}
+
+/// `DELETE /rooms/{name}/applications/{id}`: stops the room's streams receiving the
+/// application's events. The room stays, even when empty.
+pub async fn remove_application(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path((name, id)), _): WithRejection<Path<(String, Uuid)>, AppError>,
+) -> Result<Json<EventResponse<Room>>, AppError> {
+    find(&state, &admin, &name)?;
+    let room = state
+        .store
+        .write(|data| {
+            let room = data.rooms.iter_mut().find(|room| room.name == name)?;
+            room.application_ids.remove(&id);
+            return Some(room.clone());
+        })
+        .ok_or_else(|| not_found(&name))?;
+    state.hub.set_room(&room.name, &room.application_ids);
+    tracing::info!(
+        "{} removed application {} from room {}",
+        admin.subject,
+        id,
+        name
+    );
+    return Ok(Json(EventResponse::ok(room)));
+}
+
+/// `DELETE /rooms/{name}`: removes the room and closes its streams.
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(name), _): WithRejection<Path<String>, AppError>,
+) -> Result<Json<EventResponse<Room>>, AppError> {
+    find(&state, &admin, &name)?;
+    let room = state
+        .store
+        .write(|data| {
+            let index = data.rooms.iter().position(|room| room.name == name)?;
+            return Some(data.rooms.remove(index));
+        })
+        .ok_or_else(|| not_found(&name))?;
+    let closed = state.hub.remove_room(&name, Disconnect::Archived);
+    tracing::info!(
+        "{} deleted room {}, closing {} streams",
+        admin.subject,
+        name,
+        closed
+    );
+    return Ok(Json(EventResponse::ok(room)));
+}
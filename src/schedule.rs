@@ -0,0 +1,184 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    auth::Publisher,
+    error::{AppError, ErrorCode, unknown_fields},
+    event::{self, AppEvent, EventResponse},
+    state::AppState,
+};
+
+/// How often the scheduler looks for events that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An event accepted now and published at `publish_at`, e.g. a pre-announced status change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledEvent {
+    pub id: Uuid,
+    pub publish_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Subject of the publisher, recorded as the event's actor once it goes out.
+    pub scheduled_by: String,
+    pub created_at: DateTime<Utc>,
+    pub event: AppEvent,
+}
+
+/// Body of `POST /events/schedule`: the event as for `/events/send`, plus when to publish it.
+#[derive(Deserialize, Debug)]
+pub struct ScheduleRequest {
+    publish_at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: AppEvent,
+}
+
+fn not_found(id: Uuid) -> AppError {
+    return AppError::new(
+        ErrorCode::ScheduledEventNotFound,
+        format!("Scheduled event {} does not exist", id),
+    );
+}
+
+/// Validates the event like `/events/send` would and keeps it until `publish_at`.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Json(body), _): WithRejection<Json<Value>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<ScheduledEvent>>), AppError> {
+    let known: Vec<&str> = AppEvent::FIELDS
+        .iter()
+        .copied()
+        .chain(["publish_at"])
+        .collect();
+    AppError::check_fields(unknown_fields(&body, "", &known))?;
+    let payload: ScheduleRequest = serde_json::from_value(body).map_err(AppError::invalid_body)?;
+    let now = Utc::now();
+    if payload.publish_at <= now {
+        return Err(AppError::new(
+            ErrorCode::InvalidSchedule,
+            format!(
+                "publish_at {} is not after server time {}; use /events/send to publish now",
+                payload.publish_at.to_rfc3339(),
+                now.to_rfc3339()
+            ),
+        ));
+    }
+    let tenant = event::admit(&state, &publisher, &payload.event)?;
+
+    let scheduled = ScheduledEvent {
+        id: Uuid::new_v4(),
+        publish_at: payload.publish_at,
+        tenant,
+        scheduled_by: publisher.subject.clone(),
+        created_at: now,
+        event: payload.event,
+    };
+    state
+        .store
+        .write(|data| data.scheduled.push(scheduled.clone()));
+    tracing::info!(
+        "{} scheduled event {} for {}",
+        publisher.subject,
+        scheduled.id,
+        scheduled.publish_at.to_rfc3339()
+    );
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(scheduled))));
+}
+
+/// `GET /events/scheduled`: pending events of the caller's tenant, soonest first.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+) -> Json<EventResponse<Vec<ScheduledEvent>>> {
+    let mut pending: Vec<ScheduledEvent> = state.store.read(|data| {
+        data.scheduled
+            .iter()
+            .filter(|scheduled| publisher.can_access(scheduled.tenant.as_deref()))
+            .cloned()
+            .collect()
+    });
+    pending.sort_by_key(|scheduled| scheduled.publish_at);
+    return Json(EventResponse::ok(pending));
+}
+
+/// `DELETE /events/scheduled/{id}`: withdraws an event that has not gone out yet.
+pub async fn cancel(
+    State(state): State<Arc<AppState>>,
+    Publisher(publisher): Publisher,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<ScheduledEvent>>, AppError> {
+    let removed = state.store.write(|data| {
+        let index = data.scheduled.iter().position(|scheduled| {
+            scheduled.id == id && publisher.can_access(scheduled.tenant.as_deref())
+        })?;
+        return Some(data.scheduled.remove(index));
+    });
+    let scheduled = removed.ok_or_else(|| not_found(id))?;
+    tracing::info!("{} cancelled scheduled event {}", publisher.subject, id);
+    return Ok(Json(EventResponse::ok(scheduled)));
+}
+
+/// Background scheduler: publishes events whose `publish_at` has passed. Events for
+/// applications archived or purged in the meantime are dropped.
+pub async fn run(app_state: Arc<AppState>) {
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let now = Utc::now();
+        // the write lock marks the snapshot dirty, so only take it when something is due
+        let any_due = app_state.store.read(|data| {
+            data.scheduled
+                .iter()
+                .any(|scheduled| scheduled.publish_at <= now)
+        });
+        if !any_due {
+            continue;
+        }
+        let due: Vec<ScheduledEvent> = app_state.store.write(|data| {
+            let (due, pending) = data
+                .scheduled
+                .drain(..)
+                .partition(|scheduled| scheduled.publish_at <= now);
+            data.scheduled = pending;
+            return due;
+        });
+        for scheduled in due {
+            let active = scheduled.event.application_id.is_none_or(|id| {
+                app_state.store.read(|data| {
+                    data.applications
+                        .get(&id)
+                        .is_some_and(|application| application.archived_at.is_none())
+                })
+            });
+            if !active {
+                tracing::warn!(
+                    "Dropped scheduled event {}: its application is gone or archived",
+                    scheduled.id
+                );
+                continue;
+            }
+            let listeners = event::record(
+                &app_state,
+                scheduled.event,
+                scheduled.tenant,
+                &scheduled.scheduled_by,
+                now,
+            );
+            tracing::debug!(
+                "Published scheduled event {} to {} listeners",
+                scheduled.id,
+                listeners
+            );
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    fs, future::Future, io, net::SocketAddr, path::Path, pin::pin, sync::Arc, time::Duration,
+};
+
+use axum::{Router, extract::ConnectInfo, http::Request};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::{
+        conn::auto::Builder,
+        graceful::{GracefulShutdown, Watcher},
+    },
+    service::TowerToHyperService,
+};
+use rustls_pki_types::{
+    CertificateDer, PrivateKeyDer,
+    pem::{self, PemObject},
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        RootCertStore, ServerConfig as RustlsConfig, crypto::ring, server::WebPkiClientVerifier,
+    },
+    server::TlsStream,
+};
+use tower::{ServiceExt, util::MapRequest};
+use x509_parser::{certificate::X509Certificate, prelude::FromDer};
+
+use crate::{
+    auth::ClientCert,
+    config::{ServerConfig, TlsConfig},
+};
+
+/// Serves `app` on `listener` until `shutdown` resolves, then waits up to
+/// `server.shutdown_timeout_secs` for open connections to finish. Each request carries the
+/// peer address as `ConnectInfo`, as under `axum::serve`.
+pub async fn run(
+    listener: TcpListener,
+    app: Router,
+    config: &ServerConfig,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    let tls = match &config.tls {
+        Some(tls) => Some(Handshake {
+            acceptor: tls_acceptor(tls, config.http2)?,
+            timeout: Duration::from_secs(tls.handshake_timeout_secs),
+        }),
+        None => None,
+    };
+    let builder = builder(config);
+    let graceful = GracefulShutdown::new();
+    let mut shutdown = pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    // usually out of file descriptors; give connections a moment to close
+                    tracing::warn!("Failed to accept connection: {}", err);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        if let Err(err) = stream.set_nodelay(true) {
+            tracing::debug!("Failed to set TCP_NODELAY for {}: {}", peer, err);
+        }
+        tokio::spawn(serve_connection(
+            stream,
+            peer,
+            app.clone(),
+            builder.clone(),
+            tls.clone(),
+            graceful.watcher(),
+        ));
+    }
+
+    drop(listener);
+    let timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    if tokio::time::timeout(timeout, graceful.shutdown())
+        .await
+        .is_err()
+    {
+        tracing::warn!(
+            "Connections still open after {}s; closing them",
+            timeout.as_secs()
+        );
+    }
+    return Ok(());
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    app: Router,
+    builder: Builder<TokioExecutor>,
+    tls: Option<Handshake>,
+    watcher: Watcher,
+) {
+    let result = match tls {
+        Some(handshake) => match handshake.accept(stream).await {
+            Ok(stream) => {
+                let cert = stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .and_then(common_name)
+                    .map(ClientCert);
+                let service = service(app, peer, cert);
+                let connection =
+                    builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+                watcher.watch(connection).await
+            }
+            Err(err) => {
+                tracing::debug!("TLS handshake with {} failed: {}", peer, err);
+                return;
+            }
+        },
+        None => {
+            let service = service(app, peer, None);
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            watcher.watch(connection).await
+        }
+    };
+    if let Err(err) = result {
+        tracing::debug!("Connection from {} ended with an error: {}", peer, err);
+    }
+}
+
+/// The TLS side of the listener.
+#[derive(Clone)]
+struct Handshake {
+    acceptor: TlsAcceptor,
+    /// Without a limit, a client that opens a socket and never finishes the handshake
+    /// holds the connection forever.
+    timeout: Duration,
+}
+
+impl Handshake {
+    async fn accept(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        return tokio::time::timeout(self.timeout, self.acceptor.accept(stream))
+            .await
+            .unwrap_or_else(|_| {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "handshake timed out",
+                ));
+            });
+    }
+}
+
+/// Attaches what is known about the connection to each of its requests.
+fn service(
+    app: Router,
+    peer: SocketAddr,
+    cert: Option<ClientCert>,
+) -> TowerToHyperService<
+    MapRequest<Router, impl FnMut(Request<Incoming>) -> Request<Incoming> + Clone>,
+> {
+    return TowerToHyperService::new(app.map_request(move |mut request: Request<Incoming>| {
+        request.extensions_mut().insert(ConnectInfo(peer));
+        if let Some(cert) = &cert {
+            request.extensions_mut().insert(cert.clone());
+        }
+        return request;
+    }));
+}
+
+/// The subject common name of a DER certificate the handshake already verified.
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(cert).ok()?;
+    let name = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    return Some(name.to_string());
+}
+
+fn builder(config: &ServerConfig) -> Builder<TokioExecutor> {
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .keep_alive(config.http1_keep_alive)
+        .timer(TokioTimer::new())
+        .header_read_timeout(Duration::from_secs(config.header_read_timeout_secs));
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .max_concurrent_streams(config.http2_max_concurrent_streams)
+        .keep_alive_interval(
+            config
+                .http2_keep_alive_interval_secs
+                .map(Duration::from_secs),
+        )
+        .keep_alive_timeout(Duration::from_secs(config.http2_keep_alive_timeout_secs));
+    if !config.http2 {
+        builder = builder.http1_only();
+    }
+    return builder;
+}
+
+fn tls_acceptor(config: &TlsConfig, http2: bool) -> io::Result<TlsAcceptor> {
+    let invalid = |path: &Path, err: pem::Error| {
+        return io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        );
+    };
+    let certs = CertificateDer::pem_slice_iter(&fs::read(&config.cert_path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| invalid(&config.cert_path, err))?;
+    let key = PrivateKeyDer::from_pem_slice(&fs::read(&config.key_path)?)
+        .map_err(|err| invalid(&config.key_path, err))?;
+
+    let provider = Arc::new(ring::default_provider());
+    let builder = RustlsConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let builder = match &config.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_slice_iter(&fs::read(path)?) {
+                roots
+                    .add(cert.map_err(|err| invalid(path, err))?)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.require_client_cert {
+                verifier.build()
+            } else {
+                verifier.allow_unauthenticated().build()
+            };
+            let verifier =
+                verifier.map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut tls = builder
+        .with_single_cert(certs, key)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    tls.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    return Ok(TlsAcceptor::from(Arc::new(tls)));
+}
@@ -0,0 +1,232 @@
+use std::{io::Cursor, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderName, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    application,
+    auth::{Principal, Role, Viewer},
+    config::Config,
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    frontend,
+    state::AppState,
+    store::Store,
+};
+
+/// Every share token starts with this, which lets the authenticator tell them from other
+/// credentials.
+pub const TOKEN_PREFIX: &str = "vts_";
+
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+const MAX_TTL_SECS: u64 = 90 * 24 * 60 * 60;
+/// Smallest edge of a QR code image in pixels, legible on receipt printers.
+const QR_MIN_SIZE: u32 = 256;
+
+/// A read-only link to one application's progress, e.g. for the applicant's family.
+/// Like API keys, only the SHA-256 of the token is stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub application_id: Uuid,
+    hash: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Body of `POST /applications/{id}/share`; send `{}` for the default lifetime of a week.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ShareRequest {
+    ttl_secs: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ShareLinkView {
+    id: Uuid,
+    application_id: Uuid,
+    expires_at: DateTime<Utc>,
+    /// Path of the tracking page, to be joined with the server's public address.
+    url: String,
+    token: String,
+}
+
+fn hash_token(token: &str) -> String {
+    return hex::encode(Sha256::digest(token.as_bytes()));
+}
+
+/// Resolves a presented share token to a viewer confined to its application. Holders
+/// are not identified, so they only see public events.
+pub fn authenticate(store: &Store, token: &str) -> Option<Principal> {
+    let hash = hash_token(token);
+    let now = Utc::now();
+    return store.read(|data| {
+        let link = data
+            .share_links
+            .iter()
+            .find(|link| link.hash == hash && link.expires_at > now)?;
+        let application = data.applications.get(&link.application_id)?;
+        return Some(Principal {
+            subject: format!("share-link:{}", link.id),
+            roles: vec![Role::Viewer],
+            tenant: application.tenant.clone(),
+            anonymous: true,
+            application_id: Some(link.application_id),
+            expires_at: Some(link.expires_at),
+        });
+    });
+}
+
+/// Mints an expiring tracking link for the application. Identified callers only: anonymous
+/// viewers and share links themselves cannot hand out further links.
+fn mint(
+    state: &AppState,
+    viewer: &Principal,
+    id: Uuid,
+    ttl_secs: Option<u64>,
+) -> Result<ShareLinkView, AppError> {
+    if viewer.anonymous && !viewer.roles.contains(&Role::Admin) {
+        return Err(AppError::new(
+            ErrorCode::Forbidden,
+            "Sign in to share an application",
+        ));
+    }
+    application::authorize_active(state, viewer, id)?;
+    let ttl_secs = ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    if ttl_secs == 0 || ttl_secs > MAX_TTL_SECS {
+        return Err(AppError::new(
+            ErrorCode::InvalidShareLink,
+            format!("ttl_secs must be between 1 and {}", MAX_TTL_SECS),
+        ));
+    }
+
+    let mut secret = [0u8; 32];
+    rand::rng().fill_bytes(&mut secret);
+    let token = format!("{}{}", TOKEN_PREFIX, hex::encode(secret));
+    let now = Utc::now();
+    let link = ShareLink {
+        id: Uuid::new_v4(),
+        application_id: id,
+        hash: hash_token(&token),
+        created_by: viewer.subject.clone(),
+        created_at: now,
+        expires_at: now + chrono::Duration::seconds(ttl_secs as i64),
+    };
+    let view = ShareLinkView {
+        id: link.id,
+        application_id: id,
+        expires_at: link.expires_at,
+        url: format!("/track/{}", token),
+        token,
+    };
+    state.store.write(|data| {
+        data.share_links.retain(|link| link.expires_at > now);
+        data.share_links.push(link);
+    });
+    tracing::info!(
+        "{} shared application {} as link {}",
+        viewer.subject,
+        id,
+        view.id
+    );
+    return Ok(view);
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Json(payload), _): WithRejection<Json<ShareRequest>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<ShareLinkView>>), AppError> {
+    let view = mint(&state, &viewer, id, payload.ttl_secs)?;
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(view))));
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QrQuery {
+    ttl_secs: Option<u64>,
+}
+
+/// `frontend.public_url`, or the origin the request was addressed to.
+fn public_origin(config: &Config, headers: &HeaderMap) -> String {
+    if let Some(public_url) = &config.frontend.public_url {
+        return public_url.trim_end_matches('/').to_string();
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("localhost");
+    return format!("http://{}", host);
+}
+
+/// `GET /applications/{id}/qr.png`: a fresh tracking link as a QR code, for kiosks to
+/// print on receipts. Every request mints a new link, so the image is never cached.
+pub async fn qr(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+    headers: HeaderMap,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+    WithRejection(Query(query), _): WithRejection<Query<QrQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let view = mint(&state, &viewer, id, query.ttl_secs)?;
+    let url = format!("{}{}", public_origin(&state.config(), &headers), view.url);
+    let code = QrCode::new(url.as_bytes()).map_err(|err| {
+        tracing::error!("Failed to encode {} as a QR code: {}", url, err);
+        return AppError::from(ErrorCode::UnknownError);
+    })?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_MIN_SIZE, QR_MIN_SIZE)
+        .build();
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| {
+            tracing::error!("Failed to encode QR code as PNG: {}", err);
+            return AppError::from(ErrorCode::UnknownError);
+        })?;
+    return Ok((
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+            (
+                HeaderName::from_static("x-share-link-id"),
+                view.id.to_string(),
+            ),
+        ],
+        png,
+    )
+        .into_response());
+}
+
+/// `GET /track/{token}`: the tracking page, subscribed to the link's application only.
+pub async fn track(State(state): State<Arc<AppState>>, Path(token): Path<String>) -> Response {
+    let principal = token
+        .starts_with(TOKEN_PREFIX)
+        .then(|| authenticate(&state.store, &token))
+        .flatten();
+    let Some(application_id) = principal.and_then(|principal| principal.application_id) else {
+        return AppError::from(ErrorCode::ShareLinkNotFound).into_response();
+    };
+    let config = state.config();
+    let base = &config.frontend.events_url;
+    let separator = if base.contains('?') { '&' } else { '?' };
+    let events_url = format!(
+        "{}{}application_id={}&access_token={}",
+        base, separator, application_id, token
+    );
+    return frontend::render(&state, &events_url, Some(application_id));
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{
+    config::SignatureConfig,
+    error::{AppError, ErrorCode},
+    state::AppState,
+};
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+
+/// Signatures already accepted, kept until their timestamp leaves the allowed skew window.
+/// Keyed by the decoded MAC, so re-encoding the hex differently is still a replay.
+#[derive(Default)]
+pub struct ReplayGuard {
+    seen: Mutex<HashMap<Vec<u8>, i64>>,
+}
+
+impl ReplayGuard {
+    /// Returns `false` when the MAC was already used.
+    fn remember(&self, mac: Vec<u8>, expires_at: i64, now: i64) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, expiry| *expiry > now);
+        return seen.insert(mac, expires_at).is_none();
+    }
+}
+
+fn invalid(code: ErrorCode, message: &str) -> Response {
+    return AppError::new(code, message).into_response();
+}
+
+/// `X-Signature` is `sha256=` followed by the hex HMAC of `"{timestamp}.{body}"`, the same
+/// shape GitHub uses for webhook deliveries.
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    return mac;
+}
+
+/// The `X-Signature` value for a body we send, e.g. a webhook delivery.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let digest = mac(secret, timestamp, body).finalize().into_bytes();
+    return format!("sha256={}", hex::encode(digest));
+}
+
+/// The MAC carried by `signature`, if it matches the body.
+fn verify_mac(
+    config: &SignatureConfig,
+    timestamp: i64,
+    body: &[u8],
+    signature: &str,
+) -> Option<Vec<u8>> {
+    let Some(Ok(expected)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+        return None;
+    };
+    mac(&config.secret, timestamp, body)
+        .verify_slice(&expected)
+        .ok()?;
+    return Some(expected);
+}
+
+/// Route middleware for `/events/send`, active when `[signature]` is configured.
+pub async fn verify(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let current = state.config();
+    let Some(config) = &current.signature else {
+        return next.run(request).await;
+    };
+
+    let headers = request.headers();
+    let header = |name: &str| {
+        return headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+    };
+    let (Some(signature), Some(timestamp)) = (header(SIGNATURE_HEADER), header(TIMESTAMP_HEADER))
+    else {
+        return invalid(
+            ErrorCode::MissingSignature,
+            "X-Signature and X-Signature-Timestamp headers are required",
+        );
+    };
+    let Ok(timestamp) = timestamp.parse::<i64>() else {
+        return invalid(
+            ErrorCode::InvalidSignature,
+            "X-Signature-Timestamp must be unix seconds",
+        );
+    };
+
+    let now = Utc::now().timestamp();
+    let max_skew = config.max_skew_secs as i64;
+    if (now - timestamp).abs() > max_skew {
+        return invalid(
+            ErrorCode::SignatureExpired,
+            "Signature timestamp is outside the accepted window",
+        );
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, current.limits.send_body_bytes).await else {
+        return AppError::payload_too_large().into_response();
+    };
+    let Some(mac) = verify_mac(config, timestamp, &bytes, &signature) else {
+        return invalid(
+            ErrorCode::InvalidSignature,
+            "Signature does not match the body",
+        );
+    };
+    if !state.replay_guard.remember(mac, timestamp + max_skew, now) {
+        return invalid(
+            ErrorCode::SignatureReplayed,
+            "This signature was already used",
+        );
+    }
+
+    return next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+}
@@ -0,0 +1,201 @@
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::Instant,
+};
+
+use chrono::Utc;
+use serde_json::Value;
+use tower_http::cors::CorsLayer;
+use uuid::Uuid;
+
+use crate::{
+    announcement::Announcement,
+    auth::Authenticator,
+    breaker::Breakers,
+    config::Config,
+    cors,
+    drain::Drain,
+    event::{Broadcast, Frames, Priority, Visibility},
+    fanout::Hub,
+    flags::Flags,
+    frontend::Templates,
+    outbound::HttpClient,
+    outbox,
+    resume::Sessions,
+    signature::ReplayGuard,
+    stats::SubscriberStats,
+    store::Store,
+    tasks::Supervisor,
+    telemetry::LogFilter,
+};
+
+pub struct AppState {
+    pub hub: Arc<Hub>,
+    pub auth: Authenticator,
+    /// For calls to other systems.
+    pub http: HttpClient,
+    pub breakers: Breakers,
+    pub store: Store,
+    /// Replaced as a whole by a config reload; see [`AppState::config`].
+    config: RwLock<Arc<Config>>,
+    /// Rebuilt from `[cors]` on reload.
+    pub cors: RwLock<CorsLayer>,
+    pub replay_guard: ReplayGuard,
+    pub metrics: PrometheusHandle,
+    pub log_filter: LogFilter,
+    pub stats: Arc<SubscriberStats>,
+    pub flags: RwLock<Flags>,
+    /// Seeded from `[[announcements]]`; `PUT /admin/announcements` replaces it until restart.
+    pub announcements: RwLock<Vec<Announcement>>,
+    pub drain: Drain,
+    pub tasks: Supervisor,
+    pub sessions: Sessions,
+    /// Broadcasts of paused applications, in order, until they are resumed. Kept in memory
+    /// only: after a restart clients see a sequence jump and backfill from the history.
+    pub held: Mutex<HashMap<Uuid, Vec<Broadcast>>>,
+    pub templates: Templates,
+    /// Started with `--dev`: assets are watched and never cached.
+    pub dev: bool,
+    pub started_at: Instant,
+    #[cfg(feature = "chaos")]
+    pub chaos: Arc<crate::chaos::Chaos>,
+}
+
+impl AppState {
+    pub fn new(
+        config: &Config,
+        metrics: PrometheusHandle,
+        log_filter: LogFilter,
+        dev: bool,
+    ) -> Self {
+        let http = HttpClient::new(&config.outbound);
+        let store = Store::open(&config.store);
+        let hub = Hub::new(&config.sse);
+        store.read(|data| {
+            for room in &data.rooms {
+                hub.set_room(&room.name, &room.application_ids);
+            }
+        });
+        return Self {
+            hub: Arc::new(hub),
+            auth: Authenticator::new(&config.auth, &http),
+            http,
+            breakers: Breakers::default(),
+            store,
+            config: RwLock::new(Arc::new(config.clone())),
+            cors: RwLock::new(
+                cors::layer(&config.cors, config.profile).unwrap_or_else(|err| panic!("{}", err)),
+            ),
+            replay_guard: ReplayGuard::default(),
+            metrics,
+            log_filter,
+            stats: Arc::new(SubscriberStats::default()),
+            flags: RwLock::new(config.flags),
+            announcements: RwLock::new(config.announcements.clone()),
+            drain: Drain::default(),
+            tasks: Supervisor::default(),
+            sessions: Sessions::default(),
+            held: Mutex::default(),
+            templates: Templates::load(),
+            dev,
+            started_at: Instant::now(),
+            #[cfg(feature = "chaos")]
+            chaos: Arc::default(),
+        };
+    }
+}
+
+impl AppState {
+    /// The current configuration. Hold on to it only for the duration of a request, so
+    /// reloaded settings are picked up.
+    pub fn config(&self) -> Arc<Config> {
+        return self.config.read().unwrap().clone();
+    }
+
+    pub fn flags(&self) -> Flags {
+        return *self.flags.read().unwrap();
+    }
+
+    pub fn replace_config(&self, config: Config) -> Result<(), String> {
+        let cors = cors::layer(&config.cors, config.profile)?;
+        *self.cors.write().unwrap() = cors;
+        *self.config.write().unwrap() = Arc::new(config);
+        return Ok(());
+    }
+
+    /// Milliseconds since startup, if the config asks for them in event payloads.
+    pub fn monotonic_ms(&self) -> Option<u64> {
+        if !self.config().events.include_monotonic {
+            return None;
+        }
+        return Some(self.started_at.elapsed().as_millis() as u64);
+    }
+
+    /// Whether broadcasting is paused for the application.
+    pub fn is_paused(&self, application_id: Uuid) -> bool {
+        return self.store.read(|data| {
+            data.applications
+                .get(&application_id)
+                .is_some_and(|application| application.paused_at.is_some())
+        });
+    }
+
+    /// Fans an event out to every interested subscriber, returning how many there were.
+    /// Events of a paused application are held back instead and reach nobody yet.
+    pub fn publish(&self, event: Broadcast) -> usize {
+        if let Some(id) = event.application_id {
+            // checked under the lock, so a concurrent resume cannot strand the event
+            let mut held = self.held.lock().unwrap();
+            if self.is_paused(id) {
+                held.entry(id).or_default().push(event);
+                return 0;
+            }
+        }
+        self.stats.record_broadcast();
+        return self.hub.publish(event);
+    }
+
+    /// Publishes a named event, stamping it with the application's next sequence number
+    /// and queueing its webhook notifications.
+    pub fn broadcast(
+        &self,
+        application_id: Option<Uuid>,
+        tenant: Option<String>,
+        event: &'static str,
+        data: Value,
+    ) -> usize {
+        return self.broadcast_with(Visibility::Public, application_id, tenant, event, data);
+    }
+
+    /// Like [`AppState::broadcast`], limited to subscribers that may see `visibility`.
+    pub fn broadcast_with(
+        &self,
+        visibility: Visibility,
+        application_id: Option<Uuid>,
+        tenant: Option<String>,
+        event: &'static str,
+        data: Value,
+    ) -> usize {
+        let monotonic_ms = self.monotonic_ms();
+        let broadcast = self.store.write(|store| {
+            let broadcast = Broadcast {
+                application_id,
+                tenant,
+                seq: store.next_seq(application_id),
+                at: Utc::now(),
+                monotonic_ms,
+                event: Some(event),
+                priority: Priority::Normal,
+                visibility,
+                data,
+                ingested_at: Instant::now(),
+                frames: Frames::default(),
+            };
+            outbox::enqueue(store, &broadcast);
+            return broadcast;
+        });
+        return self.publish(broadcast);
+    }
+}
@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::{
+    application::ApplicationStatus,
+    auth::{Admin, Role, Viewer},
+    checklist,
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+};
+
+/// Why a subscriber stream ended.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    /// The client went away: closed the tab, lost its network, or hit a proxy timeout.
+    ClientClosed,
+    /// Fell too far behind, or its queue overflowed under the `disconnect` policy.
+    Lag,
+    /// The instance is draining.
+    Drain,
+    /// Reached `sse.max_connection_secs`.
+    MaxAge,
+    /// The credentials the stream was opened with expired.
+    AuthExpired,
+    /// The application was archived.
+    Archived,
+    /// An event failed to encode, or the stream panicked.
+    Error,
+    /// Closed through the chaos endpoints.
+    #[cfg(feature = "chaos")]
+    Chaos,
+}
+
+impl DisconnectReason {
+    /// Metric label value.
+    pub fn label(&self) -> &'static str {
+        match self {
+            DisconnectReason::ClientClosed => return "client_closed",
+            DisconnectReason::Lag => return "lag",
+            DisconnectReason::Drain => return "drain",
+            DisconnectReason::MaxAge => return "max_age",
+            DisconnectReason::AuthExpired => return "auth_expired",
+            DisconnectReason::Archived => return "archived",
+            DisconnectReason::Error => return "error",
+            #[cfg(feature = "chaos")]
+            DisconnectReason::Chaos => return "chaos",
+        }
+    }
+}
+
+/// Process-wide subscriber gauges; reset on restart.
+#[derive(Default)]
+pub struct SubscriberStats {
+    current: AtomicU64,
+    total_connections: AtomicU64,
+    events_broadcast: AtomicU64,
+    events_delivered: AtomicU64,
+    /// Bytes written to subscriber streams, keep-alives included.
+    bytes_sent: AtomicU64,
+    /// Bytes sent per tenant of the streams' applications, or of the subscriber for
+    /// streams spanning applications.
+    tenant_bytes: Mutex<HashMap<String, u64>>,
+    /// Bytes sent to streams following a single application. Entries are made when such a
+    /// stream connects and dropped when the application is archived or purged.
+    application_bytes: Mutex<HashMap<Uuid, u64>>,
+    user_agents: Mutex<HashMap<String, u64>>,
+    /// Currently connected subscribers per client address.
+    client_ips: Mutex<HashMap<IpAddr, u64>>,
+    /// Current subscribers per application filter; `None` counts unfiltered streams.
+    applications: Mutex<HashMap<Option<Uuid>, u64>>,
+    /// Current subscribers per tenant, billed like bandwidth; `None` counts operator streams.
+    tenants: Mutex<HashMap<Option<String>, u64>>,
+    /// Ended streams per reason.
+    disconnects: Mutex<HashMap<DisconnectReason, u64>>,
+    /// Signalled whenever a subscriber joins or leaves.
+    pub changed: Notify,
+}
+
+impl SubscriberStats {
+    pub fn connect(
+        self: &Arc<Self>,
+        user_agent: &str,
+        client_ip: IpAddr,
+        application_id: Option<Uuid>,
+        tenant: Option<String>,
+    ) -> ConnectionGuard {
+        self.current.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        *self
+            .user_agents
+            .lock()
+            .unwrap()
+            .entry(user_agent.to_string())
+            .or_default() += 1;
+        *self
+            .client_ips
+            .lock()
+            .unwrap()
+            .entry(client_ip)
+            .or_default() += 1;
+        *self
+            .applications
+            .lock()
+            .unwrap()
+            .entry(application_id)
+            .or_default() += 1;
+        *self
+            .tenants
+            .lock()
+            .unwrap()
+            .entry(tenant.clone())
+            .or_default() += 1;
+        if let Some(application_id) = application_id {
+            self.application_bytes
+                .lock()
+                .unwrap()
+                .entry(application_id)
+                .or_default();
+        }
+        self.changed.notify_one();
+        return ConnectionGuard {
+            stats: self.clone(),
+            user_agent: user_agent.to_string(),
+            client_ip,
+            application_id,
+            tenant,
+        };
+    }
+
+    pub fn record_broadcast(&self) {
+        self.events_broadcast.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_delivery(&self) {
+        self.events_delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Adds a connection's bytes since its last report. Streams report in batches, so the
+    /// totals trail the wire slightly.
+    pub fn record_bytes(&self, application_id: Option<Uuid>, tenant: Option<&str>, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(tenant) = tenant {
+            *self
+                .tenant_bytes
+                .lock()
+                .unwrap()
+                .entry(tenant.to_string())
+                .or_default() += bytes;
+        }
+        // a stream outliving its application's archive or purge must not bring it back
+        if let Some(application_id) = application_id
+            && let Some(sent) = self
+                .application_bytes
+                .lock()
+                .unwrap()
+                .get_mut(&application_id)
+        {
+            *sent += bytes;
+        }
+        metrics::counter!("sse_bytes_sent_total").increment(bytes);
+    }
+
+    /// Drops the bandwidth kept for an archived or purged application.
+    pub fn forget_application(&self, application_id: Uuid) {
+        self.application_bytes
+            .lock()
+            .unwrap()
+            .remove(&application_id);
+    }
+
+    pub fn record_disconnect(&self, reason: DisconnectReason) {
+        *self.disconnects.lock().unwrap().entry(reason).or_default() += 1;
+        metrics::counter!("sse_disconnects_total", "reason" => reason.label()).increment(1);
+    }
+
+    pub fn current(&self) -> u64 {
+        return self.current.load(Ordering::Relaxed);
+    }
+
+    pub fn viewers_by_application(&self) -> HashMap<Option<Uuid>, u64> {
+        return self.applications.lock().unwrap().clone();
+    }
+
+    /// Streams currently following `application_id` alone.
+    pub fn viewers_of(&self, application_id: Uuid) -> u64 {
+        return self
+            .applications
+            .lock()
+            .unwrap()
+            .get(&Some(application_id))
+            .copied()
+            .unwrap_or(0);
+    }
+}
+
+/// Held by a subscriber stream; dropping it (client gone) updates the gauges.
+pub struct ConnectionGuard {
+    stats: Arc<SubscriberStats>,
+    user_agent: String,
+    client_ip: IpAddr,
+    application_id: Option<Uuid>,
+    tenant: Option<String>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.stats.current.fetch_sub(1, Ordering::Relaxed);
+        decrement(&self.stats.user_agents, &self.user_agent);
+        decrement(&self.stats.client_ips, &self.client_ip);
+        decrement(&self.stats.applications, &self.application_id);
+        decrement(&self.stats.tenants, &self.tenant);
+        self.stats.changed.notify_one();
+    }
+}
+
+fn decrement<K: Eq + std::hash::Hash>(counts: &Mutex<HashMap<K, u64>>, key: &K) {
+    let mut counts = counts.lock().unwrap();
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+/// How many of the busiest applications `/stats` lists.
+const TOP_APPLICATIONS: usize = 10;
+
+#[derive(Serialize, Debug)]
+pub struct ApplicationBandwidth {
+    application_id: Uuid,
+    bytes_sent: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BandwidthView {
+    bytes_sent: u64,
+    tenants: HashMap<String, u64>,
+    /// The applications whose own streams took the most bytes, busiest first.
+    top_applications: Vec<ApplicationBandwidth>,
+}
+
+impl BandwidthView {
+    fn of(stats: &SubscriberStats) -> Self {
+        let mut top_applications: Vec<ApplicationBandwidth> = stats
+            .application_bytes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(application_id, bytes_sent)| ApplicationBandwidth {
+                application_id: *application_id,
+                bytes_sent: *bytes_sent,
+            })
+            .collect();
+        top_applications.sort_by_key(|usage| std::cmp::Reverse(usage.bytes_sent));
+        top_applications.truncate(TOP_APPLICATIONS);
+        return Self {
+            bytes_sent: stats.bytes_sent.load(Ordering::Relaxed),
+            tenants: stats.tenant_bytes.lock().unwrap().clone(),
+            top_applications,
+        };
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatsView {
+    /// Subscribers connected now: all of them for operators and anonymous viewers, the
+    /// tenant's for tenant callers, and the shared application's for share links.
+    current_subscribers: u64,
+    /// Instance-wide figures, for signed-in operators only.
+    #[serde(flatten)]
+    details: Option<StatsDetails>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatsDetails {
+    total_connections: u64,
+    events_broadcast: u64,
+    average_events_per_connection: f64,
+    /// Currently connected subscribers per `User-Agent`.
+    user_agents: HashMap<String, u64>,
+    /// Streams ended since startup per reason; many `client_closed` with few of the others
+    /// points at the network rather than the server.
+    disconnects: HashMap<DisconnectReason, u64>,
+    /// What the streams cost in bytes since startup, before response compression.
+    bandwidth: BandwidthView,
+}
+
+/// `GET /stats`: subscriber gauges for the frontend's "N people watching" widget, scoped to
+/// what the caller may see.
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Viewer(viewer): Viewer,
+) -> Json<EventResponse<StatsView>> {
+    let stats = &state.stats;
+    if let Some(application_id) = viewer.application_id {
+        return Json(EventResponse::ok(StatsView {
+            current_subscribers: stats.viewers_of(application_id),
+            details: None,
+        }));
+    }
+    if viewer.tenant.is_some() {
+        let current_subscribers = stats
+            .tenants
+            .lock()
+            .unwrap()
+            .get(&viewer.tenant)
+            .copied()
+            .unwrap_or(0);
+        return Json(EventResponse::ok(StatsView {
+            current_subscribers,
+            details: None,
+        }));
+    }
+    // anonymous viewers get the widget's count, not the breakdowns
+    if viewer.anonymous && !viewer.roles.contains(&Role::Admin) {
+        return Json(EventResponse::ok(StatsView {
+            current_subscribers: stats.current(),
+            details: None,
+        }));
+    }
+
+    let total_connections = stats.total_connections.load(Ordering::Relaxed);
+    let events_delivered = stats.events_delivered.load(Ordering::Relaxed);
+    let average_events_per_connection = if total_connections == 0 {
+        0.0
+    } else {
+        events_delivered as f64 / total_connections as f64
+    };
+    return Json(EventResponse::ok(StatsView {
+        current_subscribers: stats.current(),
+        details: Some(StatsDetails {
+            total_connections,
+            events_broadcast: stats.events_broadcast.load(Ordering::Relaxed),
+            average_events_per_connection,
+            user_agents: stats.user_agents.lock().unwrap().clone(),
+            disconnects: stats.disconnects.lock().unwrap().clone(),
+            bandwidth: BandwidthView::of(stats),
+        }),
+    }));
+}
+
+/// `GET /stats/clients`: currently connected subscribers per client address. Addresses
+/// span tenants, so only operators get them.
+pub async fn clients(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<HashMap<IpAddr, u64>>>, AppError> {
+    admin.require_operator()?;
+    let client_ips = state.stats.client_ips.lock().unwrap().clone();
+    return Ok(Json(EventResponse::ok(client_ips)));
+}
+
+const DEFAULT_WINDOWS: &str = "7,30,90";
+
+#[derive(Deserialize, Debug)]
+pub struct ApplicationStatsQuery {
+    /// Comma-separated window lengths in days, e.g. `7,30,90`.
+    windows: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct StageCounts {
+    pending: usize,
+    in_progress: usize,
+    completed: usize,
+}
+
+/// Mean seconds spent in a stage by the applications that have left it; `null` until one has.
+#[derive(Serialize, Debug)]
+pub struct StageDurations {
+    pending: Option<f64>,
+    in_progress: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WindowStats {
+    days: u64,
+    /// Applications created within the window.
+    created: usize,
+    /// Applications that reached 100% within the window, whenever they were created.
+    completed: usize,
+    /// Share of the applications created within the window that are completed by now.
+    completion_rate: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ApplicationStatsView {
+    by_stage: StageCounts,
+    average_stage_secs: StageDurations,
+    windows: Vec<WindowStats>,
+}
+
+/// When an application left each stage, read off its stored progress events.
+#[derive(Debug, Default)]
+struct Timeline {
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+fn parse_windows(windows: Option<&str>) -> Result<Vec<u64>, AppError> {
+    let invalid = || {
+        return AppError::new(
+            ErrorCode::InvalidQueryParameter,
+            "windows must be a comma-separated list of positive day counts",
+        );
+    };
+    let mut days = Vec::new();
+    for window in windows.unwrap_or(DEFAULT_WINDOWS).split(',') {
+        let window: u64 = window.trim().parse().map_err(|_| invalid())?;
+        if window == 0 {
+            return Err(invalid());
+        }
+        days.push(window);
+    }
+    return Ok(days);
+}
+
+fn mean(durations: impl Iterator<Item = chrono::Duration>) -> Option<f64> {
+    let (count, total) = durations.fold((0, 0.0), |(count, total), duration| {
+        return (
+            count + 1,
+            total + duration.num_milliseconds() as f64 / 1000.0,
+        );
+    });
+    if count == 0 {
+        return None;
+    }
+    return Some(total / count as f64);
+}
+
+/// `GET /stats/applications`: stage counts, time spent per stage and completion rates of
+/// the caller's applications (every tenant's for operators), computed from stored history.
+pub async fn applications(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Query(query), _): WithRejection<Query<ApplicationStatsQuery>, AppError>,
+) -> Result<Json<EventResponse<ApplicationStatsView>>, AppError> {
+    let windows = parse_windows(query.windows.as_deref())?;
+    let config = state.config();
+    let now = Utc::now();
+
+    let (statuses, timelines) = state.store.read(|data| {
+        let mut timelines: HashMap<Uuid, Timeline> = data
+            .applications
+            .values()
+            .filter(|application| admin.can_access(application.tenant.as_deref()))
+            .map(|application| {
+                let timeline = Timeline {
+                    created_at: application.created_at,
+                    ..Timeline::default()
+                };
+                return (application.id, timeline);
+            })
+            .collect();
+        let mut latest: HashMap<Uuid, f64> = HashMap::new();
+        for stored in data.events.iter().filter(|stored| stored.is_current(now)) {
+            let Some(id) = stored.event.application_id else {
+                continue;
+            };
+            let Some(timeline) = timelines.get_mut(&id) else {
+                continue;
+            };
+            let percentage = stored.event.percentage;
+            if percentage > 0.0 && timeline.started_at.is_none() {
+                timeline.started_at = Some(stored.at);
+            }
+            if percentage >= 100.0 && timeline.completed_at.is_none() {
+                timeline.completed_at = Some(stored.at);
+            }
+            latest.insert(id, percentage);
+        }
+
+        // the current stage follows the checklist when one is configured, like search does
+        let statuses: Vec<ApplicationStatus> = timelines
+            .keys()
+            .map(|id| {
+                let percentage = if config.checklist.stages.is_empty() {
+                    latest.get(id).copied().unwrap_or(0.0)
+                } else {
+                    checklist::percentage(
+                        &config.checklist,
+                        &data.applications[id].completed_stages,
+                    )
+                };
+                return ApplicationStatus::from_percentage(percentage);
+            })
+            .collect();
+        return (statuses, timelines.into_values().collect::<Vec<_>>());
+    });
+
+    let mut by_stage = StageCounts::default();
+    for status in statuses {
+        match status {
+            ApplicationStatus::Pending => by_stage.pending += 1,
+            ApplicationStatus::InProgress => by_stage.in_progress += 1,
+            ApplicationStatus::Completed => by_stage.completed += 1,
+        }
+    }
+
+    let average_stage_secs = StageDurations {
+        pending: mean(timelines.iter().filter_map(|timeline| {
+            timeline
+                .started_at
+                .map(|started_at| started_at - timeline.created_at)
+        })),
+        in_progress: mean(timelines.iter().filter_map(|timeline| {
+            timeline
+                .completed_at
+                .zip(timeline.started_at)
+                .map(|(completed_at, started_at)| completed_at - started_at)
+        })),
+    };
+
+    let windows = windows
+        .into_iter()
+        .map(|days| {
+            let since = now - chrono::Duration::days(days as i64);
+            let created: Vec<&Timeline> = timelines
+                .iter()
+                .filter(|timeline| timeline.created_at >= since)
+                .collect();
+            let created_completed = created
+                .iter()
+                .filter(|timeline| timeline.completed_at.is_some())
+                .count();
+            let completion_rate = if created.is_empty() {
+                None
+            } else {
+                Some(created_completed as f64 / created.len() as f64)
+            };
+            return WindowStats {
+                days,
+                created: created.len(),
+                completed: timelines
+                    .iter()
+                    .filter(|timeline| timeline.completed_at.is_some_and(|at| at >= since))
+                    .count(),
+                completion_rate,
+            };
+        })
+        .collect();
+
+    return Ok(Json(EventResponse::ok(ApplicationStatsView {
+        by_stage,
+        average_stage_secs,
+        windows,
+    })));
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::PathBuf,
+    sync::{
+        RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use uuid::Uuid;
+
+use crate::{
+    api_key::ApiKey,
+    application::{Application, ErasureReceipt},
+    audit::AuditEntry,
+    config::StoreConfig,
+    event::{LatestProgress, StoredEvent, Visibility},
+    login::LoginSession,
+    outbox::OutboxEntry,
+    pii::{self, FieldCipher},
+    room::Room,
+    schedule::ScheduledEvent,
+    share::ShareLink,
+    webhook::{DeliveryAttempt, Webhook},
+};
+
+/// Everything the server persists. New collections must be `#[serde(default)]` so older
+/// data files keep loading.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct StoreData {
+    #[serde(default)]
+    pub api_keys: Vec<ApiKey>,
+    #[serde(default)]
+    pub audit: Vec<AuditEntry>,
+    #[serde(default)]
+    pub applications: BTreeMap<Uuid, Application>,
+    #[serde(default)]
+    pub events: Vec<StoredEvent>,
+    #[serde(default)]
+    pub erasures: Vec<ErasureReceipt>,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Recent delivery attempts, capped per webhook.
+    #[serde(default)]
+    pub webhook_deliveries: Vec<DeliveryAttempt>,
+    /// Notifications not yet delivered, see [`crate::outbox`].
+    #[serde(default)]
+    pub outbox: Vec<OutboxEntry>,
+    /// Events waiting for their `publish_at`, see [`crate::schedule`].
+    #[serde(default)]
+    pub scheduled: Vec<ScheduledEvent>,
+    /// Read-only tracking links, see [`crate::share`].
+    #[serde(default)]
+    pub share_links: Vec<ShareLink>,
+    /// Staff dashboard sessions, see [`crate::login`].
+    #[serde(default)]
+    pub login_sessions: Vec<LoginSession>,
+    /// Named groups of applications, see [`crate::room`].
+    #[serde(default)]
+    pub rooms: Vec<Room>,
+    /// Last sequence number handed out per application.
+    #[serde(default)]
+    pub sequences: BTreeMap<Uuid, u64>,
+    /// Last sequence number of events not tied to an application.
+    #[serde(default)]
+    pub global_seq: u64,
+    /// Last event id handed out. Ids are never reused, even once their events are pruned.
+    #[serde(default)]
+    pub last_event_id: u64,
+    /// Last audit entry id handed out, never reused either.
+    #[serde(default)]
+    pub last_audit_id: u64,
+    /// The newest current event per application (`None` for events without one) and
+    /// visibility, so heartbeats need not scan the history. Rebuilt on load.
+    #[serde(skip)]
+    pub progress: HashMap<Option<Uuid>, HashMap<Visibility, LatestProgress>>,
+}
+
+impl StoreData {
+    pub fn next_seq(&mut self, application_id: Option<Uuid>) -> u64 {
+        let seq = match application_id {
+            Some(id) => self.sequences.entry(id).or_default(),
+            None => &mut self.global_seq,
+        };
+        *seq += 1;
+        return *seq;
+    }
+
+    pub fn next_event_id(&mut self) -> u64 {
+        self.last_event_id += 1;
+        return self.last_event_id;
+    }
+
+    pub fn next_audit_id(&mut self) -> u64 {
+        self.last_audit_id += 1;
+        return self.last_audit_id;
+    }
+
+    /// Keeps the id counters ahead of the stored events and audit entries, which data files
+    /// from before the counters, and restored backups, do not account for.
+    pub fn catch_up_ids(&mut self) {
+        let highest = self
+            .events
+            .iter()
+            .map(|stored| stored.id)
+            .max()
+            .unwrap_or(0);
+        self.last_event_id = self.last_event_id.max(highest);
+        let highest = self.audit.iter().map(|entry| entry.id).max().unwrap_or(0);
+        self.last_audit_id = self.last_audit_id.max(highest);
+    }
+
+    /// Notes an event appended to the history in the progress index.
+    pub fn track_progress(&mut self, stored: &StoredEvent) {
+        self.progress
+            .entry(stored.event.application_id)
+            .or_default()
+            .insert(stored.event.visibility, LatestProgress::of(stored));
+    }
+
+    /// Rebuilds the progress index; needed whenever events are removed, retracted or
+    /// replaced rather than appended.
+    pub fn reindex_progress(&mut self) {
+        let now = Utc::now();
+        self.progress.clear();
+        for stored in self.events.iter().filter(|stored| stored.is_current(now)) {
+            self.progress
+                .entry(stored.event.application_id)
+                .or_default()
+                .insert(stored.event.visibility, LatestProgress::of(stored));
+        }
+    }
+
+    /// The percentage of the newest current event of `application_id` whose visibility
+    /// `visible` accepts, `None` before any.
+    pub fn latest_percentage(
+        &self,
+        application_id: Option<Uuid>,
+        now: DateTime<Utc>,
+        visible: impl Fn(Visibility) -> bool,
+    ) -> Option<f64> {
+        let latest = self
+            .progress
+            .get(&application_id)?
+            .iter()
+            .filter(|(visibility, _)| visible(**visibility))
+            .map(|(_, latest)| latest)
+            .max_by_key(|latest| latest.id)?;
+        if latest
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            // only the history knows what an expired event was covering up
+            return self
+                .events
+                .iter()
+                .rev()
+                .find(|stored| {
+                    stored.event.application_id == application_id
+                        && stored.is_current(now)
+                        && visible(stored.event.visibility)
+                })
+                .map(|stored| stored.event.percentage);
+        }
+        return Some(latest.percentage);
+    }
+
+    /// The last sequence number handed out, `0` before anything was broadcast.
+    pub fn current_seq(&self, application_id: Option<Uuid>) -> u64 {
+        match application_id {
+            Some(id) => return self.sequences.get(&id).copied().unwrap_or(0),
+            None => return self.global_seq,
+        }
+    }
+}
+
+/// In-memory persistence layer, snapshotted to a JSON file. A single lock guards all
+/// collections, so every `write` closure is applied atomically.
+pub struct Store {
+    data: RwLock<StoreData>,
+    path: Option<PathBuf>,
+    /// Encrypts personal fields in the snapshot; see [`crate::pii`].
+    cipher: Option<FieldCipher>,
+    dirty: AtomicBool,
+}
+
+impl Store {
+    pub fn open(config: &StoreConfig) -> Self {
+        let cipher = config
+            .encryption_key_path
+            .as_deref()
+            .map(|path| FieldCipher::load(path).unwrap_or_else(|err| panic!("{}", err)));
+        let mut data: StoreData = match &config.path {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(content) => serde_json::from_str(&content)
+                    .map_err(|err| err.to_string())
+                    .and_then(|mut snapshot| {
+                        pii::open(&mut snapshot, cipher.as_ref())?;
+                        return serde_json::from_value(snapshot).map_err(|err| err.to_string());
+                    })
+                    .unwrap_or_else(|err| panic!("corrupt data file {}: {}", path.display(), err)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => StoreData::default(),
+                Err(err) => panic!("cannot read data file {}: {}", path.display(), err),
+            },
+            None => StoreData::default(),
+        };
+        data.catch_up_ids();
+        data.reindex_progress();
+
+        return Self {
+            data: RwLock::new(data),
+            path: config.path.clone(),
+            cipher,
+            dirty: AtomicBool::new(false),
+        };
+    }
+
+    /// The key personal fields are sealed with in the data file, if any.
+    pub fn cipher(&self) -> Option<&FieldCipher> {
+        return self.cipher.as_ref();
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&StoreData) -> R) -> R {
+        let data = self.data.read().unwrap();
+        return f(&data);
+    }
+
+    pub fn write<R>(&self, f: impl FnOnce(&mut StoreData) -> R) -> R {
+        let mut data = self.data.write().unwrap();
+        let result = f(&mut data);
+        self.dirty.store(true, Ordering::Release);
+        return result;
+    }
+
+    /// Writes the snapshot if anything changed since the last flush. The file is replaced
+    /// atomically so a crash mid-write never leaves a truncated snapshot behind.
+    pub fn flush(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let mut snapshot = self.read(|data| serde_json::to_value(data))?;
+        if let Some(cipher) = &self.cipher {
+            pii::seal(&mut snapshot, cipher);
+        }
+        let content = serde_json::to_vec(&snapshot)?;
+        let tmp_path = path.with_extension("tmp");
+        let result = fs::write(&tmp_path, content).and_then(|_| fs::rename(&tmp_path, path));
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Release);
+        }
+        return result;
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    env,
+    net::TcpListener,
+    os::{
+        fd::{FromRawFd, RawFd},
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram},
+    },
+};
+
+/// First descriptor passed by socket activation (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The listening socket systemd passed in through `LISTEN_FDS`, when the process was
+/// started by a `.socket` unit. Only the first socket is used.
+pub fn inherited_listener() -> Option<TcpListener> {
+    let pid = env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    if pid != std::process::id() {
+        return None;
+    }
+    let count = env::var("LISTEN_FDS").ok()?.parse::<u32>().ok()?;
+    if count == 0 {
+        return None;
+    }
+    if count > 1 {
+        tracing::warn!("Systemd passed {} sockets, only the first is used", count);
+    }
+
+    // SAFETY: LISTEN_PID names this process, so descriptor 3 was opened by systemd for us
+    // and nothing else in the process owns it.
+    let listener = unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    if let Err(err) = listener.local_addr() {
+        tracing::error!("Socket passed by systemd is not a TCP listener: {}", err);
+        return None;
+    }
+    if let Err(err) = listener.set_nonblocking(true) {
+        tracing::error!("Failed to make the systemd socket non-blocking: {}", err);
+        return None;
+    }
+    return Some(listener);
+}
+
+/// Sends a state such as `READY=1` to the service manager. A no-op unless running under
+/// a `Type=notify` unit, which sets `NOTIFY_SOCKET`.
+pub fn notify(state: &str) {
+    let Ok(path) = env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        // a leading `@` names a socket in the abstract namespace
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(&path)?,
+        };
+        return socket.send_to_addr(state.as_bytes(), &addr);
+    });
+    if let Err(err) = result {
+        tracing::warn!("Failed to notify systemd of {}: {}", state, err);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{auth::Admin, error::AppError, event::EventResponse, state::AppState};
+
+/// Wait before the first restart, doubling with each quick failure after it.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A run at least this long counts as healthy again and resets the backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Panicked or returned, and waiting out its backoff before the next start.
+    Restarting,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TaskStatus {
+    name: &'static str,
+    state: TaskState,
+    started_at: DateTime<Utc>,
+    restarts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_failure: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_failure_at: Option<DateTime<Utc>>,
+}
+
+/// Keeps the background loops (dispatcher, scheduler, pruner, ...) alive: a loop that
+/// panics or returns is started again after a backoff, and its state is reported by
+/// `/readyz` and `GET /admin/tasks`.
+#[derive(Default)]
+pub struct Supervisor {
+    tasks: Mutex<BTreeMap<&'static str, TaskStatus>>,
+}
+
+impl Supervisor {
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut TaskStatus)) {
+        if let Some(status) = self.tasks.lock().unwrap().get_mut(name) {
+            f(status);
+        }
+    }
+
+    /// Names of the tasks currently down.
+    fn failing(&self) -> Vec<&'static str> {
+        return self
+            .tasks
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|status| status.state != TaskState::Running)
+            .map(|status| status.name)
+            .collect();
+    }
+}
+
+/// The message a panic was raised with, as far as it is text.
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return "panicked".to_string();
+}
+
+/// Runs `task` under supervision of the state's [`Supervisor`].
+pub fn spawn<F, Fut>(app_state: &Arc<AppState>, name: &'static str, task: F)
+where
+    F: Fn(Arc<AppState>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    app_state.tasks.tasks.lock().unwrap().insert(
+        name,
+        TaskStatus {
+            name,
+            state: TaskState::Running,
+            started_at: Utc::now(),
+            restarts: 0,
+            last_failure: None,
+            last_failure_at: None,
+        },
+    );
+    let app_state = app_state.clone();
+    tokio::spawn(async move {
+        let supervisor = &app_state.tasks;
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let failure = match tokio::spawn(task(app_state.clone())).await {
+                Ok(()) => "returned".to_string(),
+                Err(err) if err.is_panic() => panic_message(err.into_panic()),
+                Err(err) => err.to_string(),
+            };
+            if started.elapsed() >= STABLE_AFTER {
+                backoff = INITIAL_BACKOFF;
+            }
+            tracing::error!(
+                "Background task {} stopped ({}); restarting in {}s",
+                name,
+                failure,
+                backoff.as_secs()
+            );
+            metrics::counter!("task_restarts_total", "task" => name).increment(1);
+            supervisor.update(name, |status| {
+                status.state = TaskState::Restarting;
+                status.last_failure = Some(failure);
+                status.last_failure_at = Some(Utc::now());
+            });
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            supervisor.update(name, |status| {
+                status.state = TaskState::Running;
+                status.started_at = Utc::now();
+                status.restarts += 1;
+            });
+        }
+    });
+}
+
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+    ready: bool,
+    draining: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failing_tasks: Vec<&'static str>,
+}
+
+/// `GET /readyz`: 200 while every background task runs and the instance is not draining,
+/// 503 otherwise, so the load balancer stops sending new clients.
+pub async fn ready(State(state): State<Arc<AppState>>) -> Response {
+    let failing_tasks = state.tasks.failing();
+    let draining = state.drain.is_draining();
+    let ready = failing_tasks.is_empty() && !draining;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = Readiness {
+        ready,
+        draining,
+        failing_tasks,
+    };
+    return (status, Json(EventResponse::ok(body))).into_response();
+}
+
+/// `GET /admin/tasks`: every supervised background task with its restarts and last failure.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<Vec<TaskStatus>>>, AppError> {
+    admin.require_operator()?;
+    let tasks = state
+        .tasks
+        .tasks
+        .lock()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect();
+    return Ok(Json(EventResponse::ok(tasks)));
+}
@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use serde::{Deserialize, Serialize};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    EnvFilter, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+use crate::{
+    auth::Admin,
+    config::{Config, LogFileConfig, LogRotation, Profile},
+    error::{AppError, ErrorCode},
+    event::EventResponse,
+    state::AppState,
+};
+
+/// Seconds from an event being taken in until a stream yields it, by stream route and
+/// event priority.
+pub const DELIVERY_LATENCY: &str = "event_delivery_latency_seconds";
+
+/// Upper bounds of the [`DELIVERY_LATENCY`] buckets, from queue hand-off to throttled and
+/// lagging subscribers.
+const DELIVERY_LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Installs the global Prometheus recorder; `metrics::counter!` and friends anywhere in
+/// the crate end up in the `/metrics` output. Histograms without buckets configured here
+/// are exported as summaries.
+pub fn install() -> PrometheusHandle {
+    return PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(DELIVERY_LATENCY.to_string()),
+            DELIVERY_LATENCY_BUCKETS,
+        )
+        .expect("delivery latency buckets are not empty")
+        .install_recorder()
+        .expect("metrics recorder can only be installed once");
+}
+
+pub async fn render(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    return (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    );
+}
+
+/// The filter deciding which spans and events get logged, changeable while the server runs
+/// through `PUT /admin/log-level`.
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// What `RUST_LOG` (or the built-in default) set at startup.
+    initial: String,
+}
+
+impl LogFilter {
+    pub fn current(&self) -> String {
+        return self
+            .handle
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_else(|_| self.initial.clone());
+    }
+}
+
+/// Installs the global tracing subscriber: stdout, optionally rotating log files, and
+/// the Sentry integration. File output goes through a background writer so a slow disk
+/// never blocks request or stream handling; keep the returned guard alive so buffered
+/// lines are flushed on exit.
+pub fn init_tracing(file: Option<&LogFileConfig>) -> (LogFilter, Option<WorkerGuard>) {
+    let (file_layer, guard) = match file.map(rolling_file).transpose() {
+        Ok(Some((writer, guard))) => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(writer),
+            ),
+            Some(guard),
+        ),
+        Ok(None) => (None, None),
+        Err(err) => panic!("cannot open log directory: {}", err),
+    };
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| format!("{}=debug,tower_http=debug", env!("CARGO_CRATE_NAME")).into());
+    let initial = filter.to_string();
+    let (filter, handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(file_layer)
+        // error events become Sentry events, lower levels breadcrumbs; inert without a DSN
+        .with(sentry::integrations::tracing::layer())
+        .init();
+    return (LogFilter { handle, initial }, guard);
+}
+
+/// Body of `PUT /admin/log-level`: `RUST_LOG` syntax, e.g.
+/// `visa_tracker=debug,tower_http=trace`. `null` goes back to the startup filter.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LogLevelUpdate {
+    filter: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LogLevel {
+    filter: String,
+    initial: String,
+}
+
+impl LogLevel {
+    fn of(log_filter: &LogFilter) -> Self {
+        return Self {
+            filter: log_filter.current(),
+            initial: log_filter.initial.clone(),
+        };
+    }
+}
+
+pub async fn get_log_level(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Result<Json<EventResponse<LogLevel>>, AppError> {
+    admin.require_operator()?;
+    return Ok(Json(EventResponse::ok(LogLevel::of(&state.log_filter))));
+}
+
+/// `PUT /admin/log-level`: swaps the tracing filter in place, e.g. to trace `tower_http`
+/// during an incident, without a restart that would drop every stream. Lasts until the
+/// next change or restart.
+pub async fn set_log_level(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<LogLevelUpdate>, AppError>,
+) -> Result<Json<EventResponse<LogLevel>>, AppError> {
+    admin.require_operator()?;
+    let log_filter = &state.log_filter;
+    let directives = payload.filter.unwrap_or_else(|| log_filter.initial.clone());
+    let filter = EnvFilter::builder()
+        .parse(&directives)
+        .map_err(|err| AppError::new(ErrorCode::InvalidLogFilter, err.to_string()))?;
+    log_filter
+        .handle
+        .reload(filter)
+        .map_err(|err| AppError::new(ErrorCode::UnknownError, err.to_string()))?;
+    tracing::info!("{} set the log filter to {}", admin.subject, directives);
+    return Ok(Json(EventResponse::ok(LogLevel::of(log_filter))));
+}
+
+fn rolling_file(
+    config: &LogFileConfig,
+) -> Result<(NonBlocking, WorkerGuard), tracing_appender::rolling::InitError> {
+    let rotation = match config.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&config.prefix);
+    if let Some(max_files) = config.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+    let appender = builder.build(&config.directory)?;
+    return Ok(tracing_appender::non_blocking(appender));
+}
+
+/// Starts the Sentry client when `[sentry]` is configured. Reporting stops when the
+/// returned guard is dropped, so keep it for the lifetime of the server.
+pub fn init_sentry(config: &Config) -> Option<sentry::ClientInitGuard> {
+    let sentry_config = config.sentry.as_ref()?;
+    let environment = sentry_config
+        .environment
+        .clone()
+        .unwrap_or_else(|| match config.profile {
+            Profile::Dev => "dev".to_string(),
+            Profile::Prod => "prod".to_string(),
+        });
+    let guard = sentry::init(
+        sentry::ClientOptions::new()
+            .dsn(&sentry_config.dsn)
+            .maybe_release(sentry::release_name!())
+            .environment(environment)
+            .sample_rate(sentry_config.sample_rate),
+    );
+    tracing::info!("Reporting errors to Sentry");
+    return Some(guard);
+}
+
+/// Reports 5xx responses to Sentry. The per-request hub set up by the Sentry tower layers
+/// attaches the method, URL and headers.
+pub async fn report_server_errors(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    if response.status().is_server_error() {
+        sentry::capture_message(
+            &format!("{} {} responded {}", method, path, response.status()),
+            sentry::Level::Error,
+        );
+    }
+    return response;
+}
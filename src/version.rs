@@ -0,0 +1,29 @@
+use axum::Json;
+use serde::Serialize;
+
+use crate::event::EventResponse;
+
+/// Identifies the build that is serving; collected by `build.rs` at compile time.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    /// Whether the working tree had uncommitted changes at build time.
+    pub git_dirty: bool,
+    pub build_timestamp: &'static str,
+    pub rustc_version: &'static str,
+}
+
+pub fn build_info() -> BuildInfo {
+    return BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("VERGEN_GIT_SHA"),
+        git_dirty: env!("VERGEN_GIT_DIRTY") == "true",
+        build_timestamp: env!("VERGEN_BUILD_TIMESTAMP"),
+        rustc_version: env!("VERGEN_RUSTC_SEMVER"),
+    };
+}
+
+pub async fn get() -> Json<EventResponse<BuildInfo>> {
+    return Json(EventResponse::ok(build_info()));
+}
@@ -0,0 +1,57 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde_json::json;
+
+use crate::state::AppState;
+
+/// Broadcasts `event: viewers` with the number of subscribers watching each application.
+/// Joins and leaves are debounced so a reconnect storm produces one update, not thousands.
+pub async fn run(app_state: Arc<AppState>) {
+    let mut last_sent = HashMap::new();
+    let mut last_total = 0;
+
+    loop {
+        app_state.stats.changed.notified().await;
+        let debounce = Duration::from_millis(app_state.config().sse.viewers_debounce_ms);
+        tokio::time::sleep(debounce).await;
+
+        let current = app_state.stats.viewers_by_application();
+        let watched = current.keys().filter_map(|id| *id);
+        let total: u64 = current.values().sum();
+
+        // applications nobody watches anymore get a final zero
+        let mut changed: Vec<_> = last_sent.keys().copied().chain(watched).collect();
+        changed.sort();
+        changed.dedup();
+
+        for application_id in changed {
+            let viewers = current.get(&Some(application_id)).copied().unwrap_or(0);
+            if last_sent.get(&application_id) == Some(&viewers) {
+                continue;
+            }
+            let tenant = app_state.store.read(|data| {
+                data.applications
+                    .get(&application_id)
+                    .and_then(|application| application.tenant.clone())
+            });
+            let _ = app_state.broadcast(
+                Some(application_id),
+                tenant,
+                "viewers",
+                json!({ "application_id": application_id, "viewers": viewers }),
+            );
+            if viewers == 0 {
+                last_sent.remove(&application_id);
+            } else {
+                last_sent.insert(application_id, viewers);
+            }
+        }
+
+        // unfiltered operator streams (e.g. the landing page) see the total across all
+        // applications; it spans tenants, so tenant streams don't get it
+        if total != last_total {
+            let _ = app_state.broadcast(None, None, "viewers", json!({ "viewers": total }));
+            last_total = total;
+        }
+    }
+}
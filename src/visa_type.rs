@@ -0,0 +1,62 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    state::AppState,
+};
+
+/// The stages a case of one visa type goes through.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct VisaSchema {
+    pub stages: Vec<String>,
+}
+
+/// Reads a JSON object of schemas keyed by visa type, e.g.
+/// `{ "tourist": { "stages": ["submitted", "biometrics", "decision"] } }`. Keys are matched
+/// case-insensitively, like the `visa_type` search filter.
+pub fn load(path: &Path) -> Result<HashMap<String, VisaSchema>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("cannot read visa type schemas {}: {}", path.display(), err))?;
+    let schemas: HashMap<String, VisaSchema> = serde_json::from_str(&content)
+        .map_err(|err| format!("invalid visa type schemas {}: {}", path.display(), err))?;
+    return Ok(schemas
+        .into_iter()
+        .map(|(visa_type, schema)| (visa_type.to_lowercase(), schema))
+        .collect());
+}
+
+/// Refuses a `stage` that the application's visa type does not have. Applications without
+/// a visa type, or of a type without a schema, accept any stage.
+pub fn check_stage(state: &AppState, application_id: Uuid, stage: &str) -> Result<(), AppError> {
+    let Some(visa_type) = state.store.read(|data| {
+        data.applications
+            .get(&application_id)
+            .and_then(|application| application.visa_type.clone())
+    }) else {
+        return Ok(());
+    };
+    return check_type_stage(state, &visa_type, stage);
+}
+
+/// Like [`check_stage`], for a case that is not stored yet.
+pub fn check_type_stage(state: &AppState, visa_type: &str, stage: &str) -> Result<(), AppError> {
+    let config = state.config();
+    let Some(schema) = config.visa_types.schemas.get(&visa_type.to_lowercase()) else {
+        return Ok(());
+    };
+    if schema.stages.iter().any(|known| known == stage) {
+        return Ok(());
+    }
+    return Err(AppError::new(
+        ErrorCode::UnknownStage,
+        format!(
+            "Stage {} does not exist for visa type {}; valid stages are: {}",
+            stage,
+            visa_type,
+            schema.stages.join(", ")
+        ),
+    ));
+}
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    auth::Admin,
+    error::{AppError, ErrorCode},
+    event::{Broadcast, EventResponse, Visibility},
+    outbox,
+    state::AppState,
+    store::StoreData,
+};
+
+/// Every webhook secret starts with this, which makes leaked secrets easy to scan for.
+const SECRET_PREFIX: &str = "whsec_";
+
+/// Presence updates only make sense on a live stream and are never posted to webhooks.
+const STREAM_ONLY_TYPES: &[&str] = &["viewers"];
+
+/// Recent attempts kept per webhook for `GET /admin/webhooks/{id}/deliveries`.
+const RECENT_DELIVERIES: usize = 100;
+
+/// A receiver that gets every matching event POSTed to it through the outbox.
+///
+/// Each delivery is signed like the `/events/send` bodies we accept: `X-Signature` is
+/// `sha256=` followed by the hex HMAC-SHA256 of `"{timestamp}.{body}"` under the webhook's
+/// secret, with the unix timestamp in `X-Signature-Timestamp`. Receivers should recompute
+/// it over the raw body, compare in constant time, and reject stale timestamps.
+/// `X-Visa-Tracker-Delivery` stays the same across retries of one delivery, so it can be
+/// used to drop duplicates.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    /// Shown once at registration. Webhooks registered before signing existed have none
+    /// and are delivered unsigned.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Event types to deliver; every type when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    /// Webhooks of a tenant only receive that tenant's events; operator webhooks get all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// The audience the receiver belongs to: `public` (the default) gets public events,
+    /// `applicant` adds the applicant's, and `staff` gets every event.
+    #[serde(default)]
+    pub visibility: Visibility,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn wants(&self, event: &Broadcast) -> bool {
+        let kind = event.kind();
+        if STREAM_ONLY_TYPES.contains(&kind) {
+            return false;
+        }
+        if self.tenant.is_some() && self.tenant != event.tenant {
+            return false;
+        }
+        let visible = match self.visibility {
+            Visibility::Public => event.visibility.is_public(),
+            Visibility::Applicant => event.visibility != Visibility::Staff,
+            Visibility::Staff => true,
+        };
+        if !visible {
+            return false;
+        }
+        return self.types.is_empty() || self.types.iter().any(|wanted| wanted == kind);
+    }
+}
+
+/// A webhook as shown through the API; the secret only appears when registering.
+#[derive(Serialize, Debug)]
+pub struct WebhookView {
+    id: Uuid,
+    url: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    types: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    visibility: Visibility,
+    created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl From<&Webhook> for WebhookView {
+    fn from(value: &Webhook) -> Self {
+        return Self {
+            id: value.id,
+            url: value.url.clone(),
+            types: value.types.clone(),
+            tenant: value.tenant.clone(),
+            visibility: value.visibility,
+            created_at: value.created_at,
+            secret: None,
+        };
+    }
+}
+
+/// One attempt to deliver an outbox entry, kept for debugging receivers.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryAttempt {
+    /// The outbox entry, sent as `X-Visa-Tracker-Delivery`.
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    /// The application of the delivered event, so erasing it can drop the attempt too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application_id: Option<Uuid>,
+    pub kind: String,
+    pub attempt: u32,
+    pub attempted_at: DateTime<Utc>,
+    /// `None` when no response arrived, e.g. on a connection error or timeout.
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Appends an attempt, keeping only the most recent ones of its webhook.
+pub fn record_attempt(data: &mut StoreData, attempt: DeliveryAttempt) {
+    let webhook_id = attempt.webhook_id;
+    data.webhook_deliveries.push(attempt);
+    let count = data
+        .webhook_deliveries
+        .iter()
+        .filter(|attempt| attempt.webhook_id == webhook_id)
+        .count();
+    if count > RECENT_DELIVERIES {
+        let oldest = data
+            .webhook_deliveries
+            .iter()
+            .position(|attempt| attempt.webhook_id == webhook_id)
+            .unwrap();
+        data.webhook_deliveries.remove(oldest);
+    }
+}
+
+pub fn not_found(id: Uuid) -> AppError {
+    return AppError::new(
+        ErrorCode::WebhookNotFound,
+        format!("Webhook {} does not exist", id),
+    );
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateWebhookRequest {
+    url: String,
+    #[serde(default)]
+    types: Vec<String>,
+    #[serde(default)]
+    visibility: Visibility,
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Json(payload), _): WithRejection<Json<CreateWebhookRequest>, AppError>,
+) -> Result<(StatusCode, Json<EventResponse<WebhookView>>), AppError> {
+    let url = reqwest::Url::parse(&payload.url).map_err(|err| {
+        return AppError::new(
+            ErrorCode::InvalidWebhook,
+            format!("url is not valid: {}", err),
+        );
+    })?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::new(
+            ErrorCode::InvalidWebhook,
+            "url must use http or https",
+        ));
+    }
+
+    let mut secret = [0u8; 32];
+    rand::rng().fill_bytes(&mut secret);
+    let secret = format!("{}{}", SECRET_PREFIX, hex::encode(secret));
+
+    let webhook = Webhook {
+        id: Uuid::new_v4(),
+        url: url.to_string(),
+        secret: Some(secret.clone()),
+        types: payload.types,
+        tenant: admin.tenant.clone(),
+        visibility: payload.visibility,
+        created_at: Utc::now(),
+    };
+    state
+        .store
+        .write(|data| data.webhooks.push(webhook.clone()));
+    tracing::info!(
+        "{} registered webhook {} for {}",
+        admin.subject,
+        webhook.id,
+        webhook.url
+    );
+    let mut view = WebhookView::from(&webhook);
+    view.secret = Some(secret);
+    return Ok((StatusCode::CREATED, Json(EventResponse::ok(view))));
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+) -> Json<EventResponse<Vec<WebhookView>>> {
+    let webhooks = state.store.read(|data| {
+        data.webhooks
+            .iter()
+            .filter(|webhook| admin.can_access(webhook.tenant.as_deref()))
+            .map(WebhookView::from)
+            .collect()
+    });
+    return Json(EventResponse::ok(webhooks));
+}
+
+/// Unregisters the webhook and forgets its delivery attempts. Deliveries still in the
+/// outbox are dead-lettered by the dispatcher.
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<WebhookView>>, AppError> {
+    let removed = state.store.write(|data| {
+        let index = data
+            .webhooks
+            .iter()
+            .position(|webhook| webhook.id == id && admin.can_access(webhook.tenant.as_deref()))?;
+        data.webhook_deliveries
+            .retain(|attempt| attempt.webhook_id != id);
+        return Some(data.webhooks.remove(index));
+    });
+    let webhook = removed.ok_or_else(|| not_found(id))?;
+    state.breakers.remove(id);
+    tracing::info!("{} deleted webhook {}", admin.subject, id);
+    return Ok(Json(EventResponse::ok(WebhookView::from(&webhook))));
+}
+
+/// `GET /admin/webhooks/{id}/deliveries`, also served at `/webhooks/{id}/deliveries`: the
+/// latest attempts, newest first, with the status code and latency of each.
+pub async fn deliveries(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<Vec<DeliveryAttempt>>>, AppError> {
+    let attempts = state.store.read(|data| {
+        data.webhooks
+            .iter()
+            .find(|webhook| webhook.id == id && admin.can_access(webhook.tenant.as_deref()))?;
+        return Some(
+            data.webhook_deliveries
+                .iter()
+                .rev()
+                .filter(|attempt| attempt.webhook_id == id)
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+    });
+    let attempts = attempts.ok_or_else(|| not_found(id))?;
+    return Ok(Json(EventResponse::ok(attempts)));
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReplayReport {
+    webhook_id: Uuid,
+    replayed: usize,
+}
+
+/// Retries every dead-lettered delivery of the webhook, e.g. once its receiver is fixed.
+/// Closes its circuit breaker, so they go out right away.
+pub async fn replay(
+    State(state): State<Arc<AppState>>,
+    Admin(admin): Admin,
+    WithRejection(Path(id), _): WithRejection<Path<Uuid>, AppError>,
+) -> Result<Json<EventResponse<ReplayReport>>, AppError> {
+    let replayed = state.store.write(|data| {
+        data.webhooks
+            .iter()
+            .find(|webhook| webhook.id == id && admin.can_access(webhook.tenant.as_deref()))?;
+        return Some(outbox::replay(data, id));
+    });
+    let replayed = replayed.ok_or_else(|| not_found(id))?;
+    state.breakers.remove(id);
+    tracing::info!(
+        "{} replayed {} dead-lettered deliveries of webhook {}",
+        admin.subject,
+        replayed,
+        id
+    );
+    return Ok(Json(EventResponse::ok(ReplayReport {
+        webhook_id: id,
+        replayed,
+    })));
+}